@@ -0,0 +1,139 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    middleware,
+    response::IntoResponse,
+    routing::{get, post},
+    Json,
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use crate::AppState;
+use crate::api::middleware::{require_permission, AuthUser};
+use crate::models::Permission;
+use crate::services::alert_service::{Alert, AlertFilter};
+
+const DEFAULT_PER_PAGE: i64 = 50;
+const MAX_PER_PAGE: i64 = 500;
+
+/// Longest silence that can be set, in minutes (30 days).
+const MAX_SILENCE_MINUTES: i64 = 30 * 24 * 60;
+
+#[derive(Deserialize)]
+pub struct PageParams {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct AlertPage {
+    pub alerts: Vec<Alert>,
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+}
+
+#[derive(Deserialize)]
+pub struct SilenceRequest {
+    pub minutes: i64,
+    pub reason: Option<String>,
+}
+
+pub fn routes() -> Router<AppState> {
+    let view = Router::new()
+        .route("/", get(list_alerts))
+        .route("/silences", get(list_silences))
+        .route("/:id", get(get_alert))
+        .route_layer(middleware::from_fn_with_state(Permission::View, require_permission));
+
+    let operate = Router::new()
+        .route("/:id/ack", post(acknowledge_alert))
+        .route("/:id/silence", post(silence_alert))
+        .route("/:id/unsilence", post(unsilence_alert))
+        .route_layer(middleware::from_fn_with_state(Permission::Operate, require_permission));
+
+    view.merge(operate)
+}
+
+async fn list_alerts(
+    State(state): State<AppState>,
+    Query(filter): Query<AlertFilter>,
+    Query(params): Query<PageParams>,
+) -> impl IntoResponse {
+    let page = params.page.unwrap_or(1).max(1);
+    let per_page = params.per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE);
+
+    match state.alerts.list(&filter, per_page, (page - 1) * per_page).await {
+        Ok((alerts, total)) => Json(AlertPage {
+            alerts,
+            total,
+            page,
+            per_page,
+        })
+        .into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn get_alert(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.alerts.get(&id).await {
+        Ok(Some(alert)) => Json(alert).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Alert not found").into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn acknowledge_alert(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.alerts.acknowledge(&id, &auth.user.username).await {
+        Ok(Some(alert)) => Json(alert).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Alert not found").into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn silence_alert(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<String>,
+    Json(payload): Json<SilenceRequest>,
+) -> impl IntoResponse {
+    if !(1..=MAX_SILENCE_MINUTES).contains(&payload.minutes) {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("minutes must be between 1 and {}", MAX_SILENCE_MINUTES),
+        )
+            .into_response();
+    }
+
+    let duration = chrono::Duration::minutes(payload.minutes);
+    match state.alerts.silence(&id, duration, payload.reason.as_deref(), &auth.user.username).await {
+        Ok(Some(silence)) => Json(silence).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Alert not found").into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn unsilence_alert(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.alerts.unsilence(&id).await {
+        Ok(Some(alert)) => Json(alert).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Alert not found").into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn list_silences(State(state): State<AppState>) -> impl IntoResponse {
+    match state.alerts.active_silences().await {
+        Ok(silences) => Json(silences).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
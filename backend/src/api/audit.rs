@@ -0,0 +1,188 @@
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    middleware,
+    response::IntoResponse,
+    routing::get,
+    Json,
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use crate::AppState;
+use crate::api::middleware::require_permission;
+use crate::models::Permission;
+use crate::services::audit_service::{AuditEntry, AuditFilter, MAX_EXPORT_ROWS};
+
+const DEFAULT_PER_PAGE: i64 = 50;
+const MAX_PER_PAGE: i64 = 500;
+
+#[derive(Deserialize)]
+pub struct PageParams {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct ExportParams {
+    /// `csv` (default) or `json`.
+    pub format: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct AuditPage {
+    pub entries: Vec<AuditEntry>,
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(list_entries))
+        .route("/export", get(export_entries))
+        .route_layer(middleware::from_fn_with_state(Permission::ViewAuditLog, require_permission))
+}
+
+async fn list_entries(
+    State(state): State<AppState>,
+    Query(filter): Query<AuditFilter>,
+    Query(params): Query<PageParams>,
+) -> impl IntoResponse {
+    let page = params.page.unwrap_or(1).max(1);
+    let per_page = params.per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE);
+
+    match state.audit.query(&filter, per_page, (page - 1) * per_page).await {
+        Ok((entries, total)) => Json(AuditPage {
+            entries,
+            total,
+            page,
+            per_page,
+        })
+        .into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn export_entries(
+    State(state): State<AppState>,
+    Query(filter): Query<AuditFilter>,
+    Query(params): Query<ExportParams>,
+) -> impl IntoResponse {
+    let format = params.format.unwrap_or_else(|| "csv".into());
+    if format != "csv" && format != "json" {
+        return (StatusCode::BAD_REQUEST, "format must be csv or json").into_response();
+    }
+
+    let entries = match state.audit.query(&filter, MAX_EXPORT_ROWS, 0).await {
+        Ok((entries, _)) => entries,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
+
+    let (content_type, body) = if format == "json" {
+        match serde_json::to_string_pretty(&entries) {
+            Ok(json) => ("application/json", json),
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        }
+    } else {
+        ("text/csv", to_csv(&entries))
+    };
+
+    (
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"audit-log.{}\"", format),
+            ),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+fn to_csv(entries: &[AuditEntry]) -> String {
+    let mut out = String::from("id,timestamp,user_id,username,source_ip,method,endpoint,action,resource_id,status,outcome\n");
+    for e in entries {
+        let fields = [
+            e.id.to_string(),
+            e.timestamp.to_rfc3339(),
+            e.user_id.clone().unwrap_or_default(),
+            e.username.clone().unwrap_or_default(),
+            e.source_ip.clone().unwrap_or_default(),
+            e.method.clone(),
+            e.endpoint.clone(),
+            e.action.clone(),
+            e.resource_id.clone().unwrap_or_default(),
+            e.status.to_string(),
+            e.outcome.clone(),
+        ];
+        let row: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+/// Quotes a field if needed, and neutralises leading characters that
+/// spreadsheet applications would evaluate as formulas.
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn csv_field_neutralises_formulas() {
+        assert_eq!(csv_field("=HYPERLINK(\"http://x\")"), "\"'=HYPERLINK(\"\"http://x\"\")\"");
+        assert_eq!(csv_field("+1+1"), "'+1+1");
+        assert_eq!(csv_field("-2"), "'-2");
+        assert_eq!(csv_field("@SUM(A1)"), "'@SUM(A1)");
+        assert_eq!(csv_field("a=b"), "a=b");
+        assert_eq!(csv_field(""), "");
+    }
+
+    #[test]
+    fn csv_field_quotes_separators() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn to_csv_writes_one_row_per_entry() {
+        let entry = AuditEntry {
+            id: 7,
+            timestamp: Utc.with_ymd_and_hms(2026, 1, 2, 3, 4, 5).unwrap(),
+            user_id: None,
+            username: Some("=cmd|' /C calc'!A0".into()),
+            source_ip: Some("10.0.0.1".into()),
+            method: "POST".into(),
+            endpoint: "/api/containers/web/start".into(),
+            action: "containers.start".into(),
+            resource_id: Some("web".into()),
+            status: 200,
+            outcome: "success".into(),
+        };
+        let csv = to_csv(&[entry]);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("id,timestamp,"));
+        assert_eq!(
+            lines[1],
+            "7,2026-01-02T03:04:05+00:00,,'=cmd|' /C calc'!A0,10.0.0.1,POST,/api/containers/web/start,containers.start,web,200,success"
+        );
+    }
+}
@@ -1,14 +1,13 @@
 use axum::{
     extract::State,
     response::IntoResponse,
-    routing::post,
+    routing::{get, post},
     Json,
     Router,
 };
 use serde::{Serialize, Deserialize};
 use crate::AppState;
-use jsonwebtoken::{encode, Header, EncodingKey};
-use chrono::{Utc, Duration};
+use crate::api::middleware::AuthUser;
 
 #[derive(Deserialize)]
 pub struct LoginRequest {
@@ -19,75 +18,103 @@ pub struct LoginRequest {
 #[derive(Serialize)]
 pub struct LoginResponse {
     pub token: String,
+    pub refresh_token: String,
+    /// Seconds until `token` expires and must be refreshed.
+    pub expires_in: u64,
+    pub user: crate::models::User,
+}
+
+#[derive(Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Serialize)]
+pub struct RefreshResponse {
+    pub token: String,
+    /// Replaces the refresh token that was sent, which no longer works.
+    pub refresh_token: String,
+    pub expires_in: u64,
     pub user: crate::models::User,
 }
 
 pub fn routes() -> Router<AppState> {
     Router::new()
         .route("/login", post(login))
+        .route("/refresh", post(refresh))
+        .route("/logout", post(logout))
         .route("/setup", post(setup_admin))
+        .route("/me", get(me))
 }
 
 async fn login(
-    State(_state): State<AppState>,
+    State(state): State<AppState>,
     Json(payload): Json<LoginRequest>,
 ) -> impl IntoResponse {
-    // In a real app, verify password hash from DB
-    // For this boilerplate, we'll assume basic check
-    
-    let expiration = Utc::now()
-        .checked_add_signed(Duration::hours(24))
-        .expect("valid timestamp")
-        .timestamp() as usize;
-
-    let claims = crate::models::Claims {
-        sub: "admin-uuid".to_owned(),
-        username: payload.username.clone(),
-        role: "admin".to_owned(),
-        exp: expiration,
+    let user = match state.users.verify_credentials(&payload.username, &payload.password).await {
+        Ok(Some(user)) => user,
+        // Same response for unknown users, wrong passwords and disabled accounts
+        Ok(None) => return (axum::http::StatusCode::UNAUTHORIZED, "Invalid username or password").into_response(),
+        Err(e) => return e.into_response(),
     };
 
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret("secret".as_ref()),
-    ).unwrap();
+    let tokens = match state.auth.start_session(&user).await {
+        Ok(tokens) => tokens,
+        Err(e) => return (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
 
     Json(LoginResponse {
-        token,
-        user: crate::models::User {
-            id: "admin-uuid".to_owned(),
-            username: payload.username,
-            role: "admin".to_owned(),
-        },
+        token: tokens.token,
+        refresh_token: tokens.refresh_token,
+        expires_in: tokens.expires_in,
+        user,
     })
+    .into_response()
 }
 
-async fn setup_admin(
+async fn refresh(
     State(state): State<AppState>,
-    Json(payload): Json<LoginRequest>,
+    Json(payload): Json<RefreshRequest>,
 ) -> impl IntoResponse {
-    // Check if any user exists
-    let count: i32 = sqlx::query_scalar("SELECT COUNT(*) FROM users")
-        .fetch_one(&state.db.pool)
-        .await
-        .unwrap_or(0);
+    match state.auth.refresh(&payload.refresh_token).await {
+        Ok(Some((user, tokens))) => Json(RefreshResponse {
+            token: tokens.token,
+            refresh_token: tokens.refresh_token,
+            expires_in: tokens.expires_in,
+            user,
+        })
+        .into_response(),
+        Ok(None) => (axum::http::StatusCode::UNAUTHORIZED, "Invalid or expired refresh token").into_response(),
+        Err(e) => (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
 
-    if count > 0 {
-        return (axum::http::StatusCode::BAD_REQUEST, "Admin already setup").into_response();
+/// Revokes the caller's session, invalidating both its access and refresh token.
+async fn logout(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> impl IntoResponse {
+    match state.auth.revoke_session(&auth.session_id).await {
+        Ok(_) => axum::http::StatusCode::OK.into_response(),
+        Err(e) => (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
     }
+}
 
-    let id = uuid::Uuid::new_v4().to_string();
-    let hashed_password = bcrypt::hash(payload.password, bcrypt::DEFAULT_COST).unwrap();
+async fn setup_admin(
+    State(state): State<AppState>,
+    Json(payload): Json<LoginRequest>,
+) -> impl IntoResponse {
+    if !state.config.security.admin_setup_enabled {
+        return (axum::http::StatusCode::FORBIDDEN, "Admin setup is disabled").into_response();
+    }
 
-    sqlx::query("INSERT INTO users (id, username, password_hash, role) VALUES (?, ?, ?, ?)")
-        .bind(&id)
-        .bind(&payload.username)
-        .bind(&hashed_password)
-        .bind("admin")
-        .execute(&state.db.pool)
-        .await
-        .unwrap();
+    match state.users.create_first_admin(&payload.username, &payload.password).await {
+        Ok(Some(_)) => axum::http::StatusCode::CREATED.into_response(),
+        Ok(None) => (axum::http::StatusCode::BAD_REQUEST, "Admin already setup").into_response(),
+        Err(e) => e.into_response(),
+    }
+}
 
-    axum::http::StatusCode::CREATED.into_response()
+async fn me(auth: AuthUser) -> impl IntoResponse {
+    Json(auth.user)
 }
@@ -1,11 +1,14 @@
 use axum::{
-    extract::{State, Path},
+    extract::State,
+    middleware,
     response::IntoResponse,
     routing::{get, post},
     Json,
     Router,
 };
 use crate::AppState;
+use crate::api::middleware::require_permission;
+use crate::models::Permission;
 use serde::Deserialize;
 
 #[derive(Deserialize)]
@@ -15,9 +18,15 @@ pub struct ComposeAction {
 }
 
 pub fn routes() -> Router<AppState> {
-    Router::new()
+    let view = Router::new()
         .route("/projects", get(list_projects))
+        .route_layer(middleware::from_fn_with_state(Permission::View, require_permission));
+
+    let operate = Router::new()
         .route("/action", post(project_action))
+        .route_layer(middleware::from_fn_with_state(Permission::Operate, require_permission));
+
+    view.merge(operate)
 }
 
 async fn list_projects(State(state): State<AppState>) -> impl IntoResponse {
@@ -1,29 +1,251 @@
 use axum::{
-    extract::{Path, State, Query, ws::{WebSocket, WebSocketUpgrade, Message}},
-    response::IntoResponse,
+    body::Body,
+    extract::{Path, State, Query, rejection::JsonRejection, ws::{WebSocket, WebSocketUpgrade, Message}},
+    http::{header, HeaderMap},
+    middleware,
+    response::{IntoResponse, Response},
     routing::{get, post, delete},
     Json,
     Router,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use crate::AppState;
-use futures::{StreamExt, SinkExt};
+use crate::api::optional_json;
+use crate::api::middleware::require_permission;
+use crate::models::Permission;
+use crate::services::recreate_service::{RecreateError, RecreateOptions};
+use crate::services::resource_service::{ResourceError, ResourceLimits};
+use bollard::container::{LogOutput, LogsOptions};
+use futures::{SinkExt, StreamExt};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
 
 #[derive(Deserialize)]
 pub struct ListOptions {
     pub all: Option<bool>,
 }
 
+/// A container as Docker lists it, plus what Dockium knows about it.
+#[derive(Serialize)]
+struct ContainerListItem {
+    #[serde(flatten)]
+    container: bollard::service::ContainerSummary,
+    /// The registry has a newer image for the container's tag.
+    #[serde(rename = "UpdateAvailable")]
+    update_available: bool,
+}
+
+#[derive(Deserialize)]
+pub struct LogsParams {
+    /// Number of lines from the end, or `all`. Defaults to 100 for the
+    /// socket and `all` for downloads.
+    pub tail: Option<String>,
+    /// Unix seconds or RFC 3339.
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub timestamps: Option<bool>,
+    /// Keep streaming new output. Defaults to true; ignored for downloads.
+    pub follow: Option<bool>,
+    pub stdout: Option<bool>,
+    pub stderr: Option<bool>,
+}
+
+impl LogsParams {
+    fn to_options(&self, follow: bool, default_tail: &str) -> Result<LogsOptions<String>, String> {
+        let tail = self.tail.clone().unwrap_or_else(|| default_tail.to_string());
+        if tail != "all" && tail.parse::<u64>().is_err() {
+            return Err(format!("Invalid tail {:?}: expected a number or \"all\"", tail));
+        }
+
+        Ok(LogsOptions {
+            follow,
+            stdout: self.stdout.unwrap_or(true),
+            stderr: self.stderr.unwrap_or(true),
+            since: parse_time("since", self.since.as_deref())?,
+            until: parse_time("until", self.until.as_deref())?,
+            timestamps: self.timestamps.unwrap_or(false),
+            tail,
+        })
+    }
+}
+
+/// Parses unix seconds or an RFC 3339 timestamp; 0 means unbounded.
+fn parse_time(name: &str, value: Option<&str>) -> Result<i64, String> {
+    let Some(value) = value else {
+        return Ok(0);
+    };
+    if let Ok(secs) = value.parse::<i64>() {
+        return Ok(secs);
+    }
+    chrono::DateTime::parse_from_rfc3339(value)
+        .map(|t| t.timestamp())
+        .map_err(|_| format!("Invalid {} {:?}: expected unix seconds or RFC 3339", name, value))
+}
+
+/// One log line sent over the logs socket.
+#[derive(Serialize)]
+struct LogFrame {
+    /// `stdout` or `stderr`. Containers with a TTY only produce `stdout`.
+    stream: &'static str,
+    /// Present when requested with `timestamps=true`.
+    timestamp: Option<String>,
+    message: String,
+}
+
+/// A partial line is sent as is once it grows past this, so output without
+/// newlines can't buffer without bound.
+const MAX_PARTIAL_LINE: usize = 1024 * 1024;
+
+/// Reassembles lines that Docker split across log chunks, separately for
+/// stdout and stderr, so each frame holds exactly one line.
+struct LogLines {
+    timestamps: bool,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+}
+
+impl LogLines {
+    fn new(timestamps: bool) -> Self {
+        Self { timestamps, stdout: Vec::new(), stderr: Vec::new() }
+    }
+
+    /// Frames for every line the chunk completes.
+    fn push(&mut self, stream: &'static str, chunk: &[u8]) -> Vec<LogFrame> {
+        let timestamps = self.timestamps;
+        let buffer = self.buffer(stream);
+        buffer.extend_from_slice(chunk);
+        let complete = match buffer.iter().rposition(|b| *b == b'\n') {
+            Some(end) => end + 1,
+            None if buffer.len() > MAX_PARTIAL_LINE => buffer.len(),
+            None => return Vec::new(),
+        };
+        let lines: Vec<u8> = buffer.drain(..complete).collect();
+        lines
+            .split_inclusive(|b| *b == b'\n')
+            .map(|line| log_frame(stream, line, timestamps))
+            .collect()
+    }
+
+    /// Frames for output left without a final newline when the log ends.
+    fn finish(&mut self) -> Vec<LogFrame> {
+        let timestamps = self.timestamps;
+        ["stdout", "stderr"]
+            .into_iter()
+            .filter_map(|stream| {
+                let rest = std::mem::take(self.buffer(stream));
+                (!rest.is_empty()).then(|| log_frame(stream, &rest, timestamps))
+            })
+            .collect()
+    }
+
+    fn buffer(&mut self, stream: &str) -> &mut Vec<u8> {
+        if stream == "stderr" {
+            &mut self.stderr
+        } else {
+            &mut self.stdout
+        }
+    }
+}
+
+fn log_frame(stream: &'static str, line: &[u8], timestamps: bool) -> LogFrame {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    let line = String::from_utf8_lossy(line);
+    let (timestamp, message) = match line.split_once(' ') {
+        Some((ts, rest)) if timestamps => (Some(ts.to_string()), rest.to_string()),
+        _ => (None, line.into_owned()),
+    };
+    LogFrame { stream, timestamp, message }
+}
+
+#[derive(Deserialize)]
+pub struct TopParams {
+    /// `cpu` (default) or `memory`.
+    pub sort: Option<String>,
+    /// Only send the heaviest N containers.
+    pub limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct ExecParams {
+    /// Command to run, split on whitespace. Defaults to `/bin/sh`.
+    pub cmd: Option<String>,
+    pub user: Option<String>,
+    pub workdir: Option<String>,
+    /// Initial terminal size.
+    pub cols: Option<u16>,
+    pub rows: Option<u16>,
+}
+
+/// Text frames sent by the exec terminal client. Binary frames are written
+/// to stdin as-is.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum ExecClientMessage {
+    Input { data: String },
+    Resize { cols: u16, rows: u16 },
+}
+
+/// Text frames sent to the exec terminal client. Terminal output is sent as
+/// binary frames.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum ExecServerMessage {
+    Error { message: String },
+    Exit { exit_code: Option<i64> },
+}
+
+impl IntoResponse for RecreateError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            RecreateError::NotFound => axum::http::StatusCode::NOT_FOUND,
+            RecreateError::InvalidInput(_) => axum::http::StatusCode::BAD_REQUEST,
+            RecreateError::RolledBack(_) | RecreateError::Internal(_) => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+impl IntoResponse for ResourceError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            ResourceError::NotFound => axum::http::StatusCode::NOT_FOUND,
+            ResourceError::InvalidInput(_) => axum::http::StatusCode::BAD_REQUEST,
+            ResourceError::Internal(_) => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
 pub fn routes() -> Router<AppState> {
-    Router::new()
+    let view = Router::new()
         .route("/", get(list_containers))
+        .route("/:id/inspect", get(inspect_container))
+        .route("/:id/logs", get(logs_handler))
+        .route("/:id/logs/download", get(download_logs))
+        .route("/:id/stats", get(container_stats))
+        .route("/:id/stats/ws", get(container_stats_ws))
+        .route("/stats/ws", get(top_containers_ws))
+        .route_layer(middleware::from_fn_with_state(Permission::View, require_permission));
+
+    let host_access = Router::new()
         .route("/", post(create_container))
+        .route("/:id/exec", get(exec_handler))
+        .route_layer(middleware::from_fn_with_state(Permission::HostAccess, require_permission));
+
+    let operate = Router::new()
         .route("/:id/start", post(start_container))
         .route("/:id/stop", post(stop_container))
         .route("/:id/restart", post(restart_container))
+        .route("/:id/recreate", post(recreate_container))
+        .route("/:id/update", post(update_container))
+        .route_layer(middleware::from_fn_with_state(Permission::Operate, require_permission));
+
+    let remove = Router::new()
         .route("/:id/remove", delete(remove_container))
-        .route("/:id/inspect", get(inspect_container))
-        .route("/:id/logs", get(logs_handler))
+        .route_layer(middleware::from_fn_with_state(Permission::Remove, require_permission));
+
+    view.merge(host_access).merge(operate).merge(remove)
 }
 
 async fn list_containers(
@@ -31,10 +253,21 @@ async fn list_containers(
     Query(params): Query<ListOptions>,
 ) -> impl IntoResponse {
     let all = params.all.unwrap_or(false);
-    match state.docker.list_containers(all).await {
-        Ok(containers) => Json(containers).into_response(),
-        Err(e) => (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
-    }
+    let containers = match state.docker.list_containers(all).await {
+        Ok(containers) => containers,
+        Err(e) => return (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
+    // The flag is best effort; the list is still useful without it
+    let updates = state.updates.available().await.unwrap_or_default();
+
+    let items: Vec<_> = containers
+        .into_iter()
+        .map(|container| ContainerListItem {
+            update_available: container.id.as_ref().is_some_and(|id| updates.contains(id)),
+            container,
+        })
+        .collect();
+    Json(items).into_response()
 }
 
 async fn start_container(
@@ -91,30 +324,283 @@ async fn logs_handler(
     ws: WebSocketUpgrade,
     State(state): State<AppState>,
     Path(id): Path<String>,
+    Query(params): Query<LogsParams>,
 ) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| handle_logs_ws(socket, state, id))
+    let options = match params.to_options(params.follow.unwrap_or(true), "100") {
+        Ok(options) => options,
+        Err(e) => return (axum::http::StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    ws.on_upgrade(move |socket| handle_logs_ws(socket, state, id, options))
 }
 
-async fn handle_logs_ws(mut socket: WebSocket, state: AppState, id: String) {
-    let mut logs_stream = match state.docker.get_container_logs(&id).await {
+async fn handle_logs_ws(mut socket: WebSocket, state: AppState, id: String, options: LogsOptions<String>) {
+    let timestamps = options.timestamps;
+    let mut logs_stream = match state.docker.get_container_logs(&id, options).await {
         Ok(stream) => stream,
         Err(e) => {
-            let _ = socket.send(Message::Text(format!("Error: {}", e).into())).await;
+            let _ = socket.send(Message::Text(serde_json::json!({ "error": e.to_string() }).to_string())).await;
             return;
         }
     };
 
+    let mut lines = LogLines::new(timestamps);
+    let mut error = None;
     while let Some(log) = logs_stream.next().await {
-        match log {
-            Ok(output) => {
-                let msg = format!("{}", output);
-                if socket.send(Message::Text(msg.into())).await.is_err() {
-                    break;
+        let output = match log {
+            Ok(output) => output,
+            Err(e) => {
+                error = Some(e.to_string());
+                break;
+            }
+        };
+
+        let stream = match output {
+            LogOutput::StdErr { .. } => "stderr",
+            _ => "stdout",
+        };
+        // A chunk may hold several lines, or part of one
+        for frame in lines.push(stream, &output.into_bytes()) {
+            if send_frame(&mut socket, &frame).await.is_err() {
+                return;
+            }
+        }
+    }
+    for frame in lines.finish() {
+        if send_frame(&mut socket, &frame).await.is_err() {
+            return;
+        }
+    }
+    if let Some(error) = error {
+        let _ = socket.send(Message::Text(serde_json::json!({ "error": error }).to_string())).await;
+    }
+    let _ = socket.close().await;
+}
+
+async fn send_frame(socket: &mut WebSocket, frame: &LogFrame) -> Result<(), axum::Error> {
+    let json = serde_json::to_string(frame).unwrap_or_default();
+    socket.send(Message::Text(json)).await
+}
+
+/// Returns the container's log as a plain text attachment, streamed rather
+/// than buffered so large logs don't sit in memory.
+async fn download_logs(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(params): Query<LogsParams>,
+) -> impl IntoResponse {
+    let options = match params.to_options(false, "all") {
+        Ok(options) => options,
+        Err(e) => return (axum::http::StatusCode::BAD_REQUEST, e).into_response(),
+    };
+
+    let stream = match state.docker.get_container_logs(&id, options).await {
+        Ok(stream) => stream,
+        Err(e) => return (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
+
+    // Fail fast on e.g. an unknown container instead of sending an empty file
+    let mut stream = stream.peekable();
+    if let Some(Err(e)) = std::pin::Pin::new(&mut stream).peek().await {
+        let status = match e {
+            bollard::errors::Error::DockerResponseServerError { status_code: 404, .. } => axum::http::StatusCode::NOT_FOUND,
+            _ => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        return (status, e.to_string()).into_response();
+    }
+
+    let body = Body::from_stream(stream.map(|chunk| chunk.map(|output| output.into_bytes())));
+    let file_name: String = id.chars().filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_').collect();
+
+    (
+        [
+            (header::CONTENT_TYPE, "text/plain; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}.log\"", file_name)),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+async fn container_stats(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.docker.container_stats(&id).await {
+        Ok(stats) => Json(stats).into_response(),
+        Err(e) => (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn container_stats_ws(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| handle_container_stats_ws(socket, state, id))
+}
+
+async fn handle_container_stats_ws(mut socket: WebSocket, state: AppState, id: String) {
+    let interval = Duration::from_secs(state.config.monitoring.stats_interval_seconds);
+    let mut stats_stream = state.docker.stream_container_stats(&id);
+    let mut last_sent: Option<Instant> = None;
+
+    while let Some(stats) = stats_stream.next().await {
+        let stats = match stats {
+            Ok(stats) => stats,
+            Err(e) => {
+                let _ = socket.send(Message::Text(serde_json::json!({ "error": e.to_string() }).to_string())).await;
+                break;
+            }
+        };
+
+        // Docker samples every second; only forward at the configured rate
+        if last_sent.is_some_and(|t| t.elapsed() < interval) {
+            continue;
+        }
+        last_sent = Some(Instant::now());
+
+        let json = serde_json::to_string(&stats).unwrap_or_default();
+        if socket.send(Message::Text(json)).await.is_err() {
+            return;
+        }
+    }
+    let _ = socket.close().await;
+}
+
+/// Periodically sends stats for every running container, heaviest first.
+async fn top_containers_ws(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    Query(params): Query<TopParams>,
+) -> impl IntoResponse {
+    let by_memory = match params.sort.as_deref() {
+        None | Some("cpu") => false,
+        Some("memory") => true,
+        Some(other) => {
+            return (axum::http::StatusCode::BAD_REQUEST, format!("Invalid sort {:?}: expected \"cpu\" or \"memory\"", other)).into_response()
+        }
+    };
+    ws.on_upgrade(move |socket| handle_top_containers_ws(socket, state, by_memory, params.limit))
+}
+
+async fn handle_top_containers_ws(mut socket: WebSocket, state: AppState, by_memory: bool, limit: Option<usize>) {
+    let interval = Duration::from_secs(state.config.monitoring.stats_interval_seconds);
+    loop {
+        let message = match state.docker.running_container_stats().await {
+            Ok(mut stats) => {
+                if by_memory {
+                    stats.sort_by_key(|s| std::cmp::Reverse(s.memory_usage));
+                } else {
+                    stats.sort_by(|a, b| b.cpu_percent.total_cmp(&a.cpu_percent));
                 }
+                if let Some(limit) = limit {
+                    stats.truncate(limit);
+                }
+                serde_json::to_string(&stats).unwrap_or_default()
             }
-            Err(_) => break,
+            Err(e) => serde_json::json!({ "error": e.to_string() }).to_string(),
+        };
+
+        if socket.send(Message::Text(message)).await.is_err() {
+            break;
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+async fn exec_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(params): Query<ExecParams>,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| handle_exec_ws(socket, state, id, params))
+}
+
+async fn handle_exec_ws(socket: WebSocket, state: AppState, id: String, params: ExecParams) {
+    let (mut sender, mut receiver) = socket.split();
+
+    let cmd: Vec<String> = params
+        .cmd
+        .as_deref()
+        .unwrap_or("/bin/sh")
+        .split_whitespace()
+        .map(str::to_string)
+        .collect();
+    if cmd.is_empty() {
+        let _ = sender.send(exec_message(ExecServerMessage::Error { message: "Empty command".into() })).await;
+        return;
+    }
+
+    let exec_id = match state.docker.create_exec(&id, cmd, params.user, params.workdir).await {
+        Ok(exec_id) => exec_id,
+        Err(e) => {
+            let _ = sender.send(exec_message(ExecServerMessage::Error { message: e.to_string() })).await;
+            return;
+        }
+    };
+
+    let (mut output, mut input) = match state.docker.start_exec(&exec_id).await {
+        Ok(attached) => attached,
+        Err(e) => {
+            let _ = sender.send(exec_message(ExecServerMessage::Error { message: e.to_string() })).await;
+            return;
+        }
+    };
+
+    if let (Some(cols), Some(rows)) = (params.cols, params.rows) {
+        let _ = state.docker.resize_exec(&exec_id, rows, cols).await;
+    }
+
+    let mut exited = false;
+    loop {
+        tokio::select! {
+            chunk = output.next() => match chunk {
+                Some(Ok(chunk)) => {
+                    if sender.send(Message::Binary(chunk.into_bytes().to_vec())).await.is_err() {
+                        break;
+                    }
+                }
+                // The process exited and Docker closed the stream
+                _ => {
+                    exited = true;
+                    break;
+                }
+            },
+            msg = receiver.next() => match msg {
+                Some(Ok(Message::Binary(data))) => {
+                    if input.write_all(&data).await.is_err() {
+                        break;
+                    }
+                }
+                Some(Ok(Message::Text(text))) => match serde_json::from_str(&text) {
+                    Ok(ExecClientMessage::Input { data }) => {
+                        if input.write_all(data.as_bytes()).await.is_err() {
+                            break;
+                        }
+                    }
+                    Ok(ExecClientMessage::Resize { cols, rows }) => {
+                        let _ = state.docker.resize_exec(&exec_id, rows, cols).await;
+                    }
+                    Err(_) => {}
+                },
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
         }
     }
+
+    if exited {
+        let exit_code = state.docker.exec_exit_code(&exec_id).await.ok().flatten();
+        let _ = sender.send(exec_message(ExecServerMessage::Exit { exit_code })).await;
+        let _ = sender.close().await;
+    }
+    // Dropping the attached streams closes the exec's TTY, which hangs up
+    // the process if the client went away first.
+}
+
+fn exec_message(msg: ExecServerMessage) -> Message {
+    Message::Text(serde_json::to_string(&msg).unwrap_or_default())
 }
 
 async fn create_container(
@@ -133,3 +619,128 @@ async fn create_container(
         Err(e) => (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
     }
 }
+
+/// Replaces the container with one on the same or a new image, keeping its
+/// configuration. The body is optional.
+async fn recreate_container(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    options: Result<Json<RecreateOptions>, JsonRejection>,
+) -> impl IntoResponse {
+    let options = match optional_json(&headers, options) {
+        Ok(options) => options,
+        Err(rejection) => return rejection.into_response(),
+    };
+    match state.recreate.recreate(&id, options).await {
+        Ok(report) => Json(report).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Changes resource limits and the restart policy without restarting the
+/// container, returning the values that changed.
+async fn update_container(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(limits): Json<ResourceLimits>,
+) -> impl IntoResponse {
+    match state.resources.update(&id, limits).await {
+        Ok(report) => Json(report).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(query: serde_json::Value) -> LogsParams {
+        serde_json::from_value(query).unwrap()
+    }
+
+    fn messages(frames: &[LogFrame]) -> Vec<(&str, &str)> {
+        frames.iter().map(|f| (f.stream, f.message.as_str())).collect()
+    }
+
+    #[test]
+    fn lines_split_across_chunks_are_joined() {
+        let mut lines = LogLines::new(false);
+        assert!(lines.push("stdout", b"hel").is_empty());
+        let frames = lines.push("stdout", b"lo\nwor");
+        assert_eq!(messages(&frames), [("stdout", "hello")]);
+        let frames = lines.push("stdout", b"ld\r\nagain\n");
+        assert_eq!(messages(&frames), [("stdout", "world"), ("stdout", "again")]);
+        assert!(lines.finish().is_empty());
+    }
+
+    #[test]
+    fn multi_byte_characters_split_across_chunks_survive() {
+        let bytes = "naïve\n".as_bytes();
+        let mut lines = LogLines::new(false);
+        assert!(lines.push("stdout", &bytes[..3]).is_empty());
+        let frames = lines.push("stdout", &bytes[3..]);
+        assert_eq!(messages(&frames), [("stdout", "naïve")]);
+    }
+
+    #[test]
+    fn streams_are_buffered_separately_and_flushed_at_the_end() {
+        let mut lines = LogLines::new(false);
+        assert!(lines.push("stdout", b"out ").is_empty());
+        assert!(lines.push("stderr", b"err ").is_empty());
+        assert_eq!(messages(&lines.push("stderr", b"line\n")), [("stderr", "err line")]);
+        assert!(lines.push("stdout", b"partial").is_empty());
+        assert_eq!(messages(&lines.finish()), [("stdout", "out partial")]);
+        assert!(lines.finish().is_empty());
+    }
+
+    #[test]
+    fn overlong_partial_lines_are_sent_as_is() {
+        let mut lines = LogLines::new(false);
+        let frames = lines.push("stdout", &vec![b'x'; MAX_PARTIAL_LINE + 1]);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].message.len(), MAX_PARTIAL_LINE + 1);
+        assert!(lines.finish().is_empty());
+    }
+
+    #[test]
+    fn timestamps_are_split_off_each_line() {
+        let mut lines = LogLines::new(true);
+        let frames = lines.push("stdout", b"2024-01-01T00:00:00.000000000Z first line\n2024-01-01T00:00:01.000000000Z ");
+        assert_eq!(frames[0].timestamp.as_deref(), Some("2024-01-01T00:00:00.000000000Z"));
+        assert_eq!(frames[0].message, "first line");
+        let frames = lines.push("stdout", b"second\n");
+        assert_eq!(frames[0].timestamp.as_deref(), Some("2024-01-01T00:00:01.000000000Z"));
+        assert_eq!(frames[0].message, "second");
+    }
+
+    #[test]
+    fn log_options_default_and_validate_tail() {
+        let options = params(serde_json::json!({})).to_options(true, "100").unwrap();
+        assert_eq!(options.tail, "100");
+        assert!(options.follow && options.stdout && options.stderr && !options.timestamps);
+        assert_eq!((options.since, options.until), (0, 0));
+
+        assert_eq!(params(serde_json::json!({ "tail": "all" })).to_options(false, "100").unwrap().tail, "all");
+        assert_eq!(params(serde_json::json!({ "tail": "25" })).to_options(false, "all").unwrap().tail, "25");
+        assert!(params(serde_json::json!({ "tail": "abc" })).to_options(false, "all").is_err());
+        assert!(params(serde_json::json!({ "tail": "-1" })).to_options(false, "all").is_err());
+    }
+
+    #[test]
+    fn log_options_parse_since_and_until() {
+        let options = params(serde_json::json!({
+            "since": "1700000000",
+            "until": "2024-01-01T01:00:00+01:00",
+        }))
+        .to_options(false, "all")
+        .unwrap();
+        assert_eq!(options.since, 1_700_000_000);
+        assert_eq!(options.until, 1_704_067_200);
+
+        let err = params(serde_json::json!({ "since": "yesterday" })).to_options(false, "all").unwrap_err();
+        assert!(err.contains("since"), "{}", err);
+        assert!(parse_time("until", Some("2024-13-01T00:00:00Z")).is_err());
+        assert_eq!(parse_time("since", None), Ok(0));
+    }
+}
@@ -0,0 +1,227 @@
+use axum::{
+    extract::{Query, State, ws::{WebSocket, WebSocketUpgrade, Message}},
+    http::StatusCode,
+    middleware,
+    response::{sse::{Event, KeepAlive, Sse}, IntoResponse},
+    routing::get,
+    Router,
+};
+use futures::{Stream, StreamExt};
+use serde::Deserialize;
+use std::convert::Infallible;
+use tokio::sync::broadcast::error::RecvError;
+use crate::AppState;
+use crate::api::middleware::require_permission;
+use crate::models::Permission;
+use crate::services::docker_service::DockerEvent;
+
+/// Object types reported by the Docker events API.
+const EVENT_TYPES: &[&str] = &[
+    "builder", "config", "container", "daemon", "image", "network", "node", "plugin", "secret", "service", "volume",
+];
+
+#[derive(Deserialize)]
+pub struct EventParams {
+    /// Comma-separated object types, e.g. `container,image`.
+    #[serde(rename = "type")]
+    pub types: Option<String>,
+    /// Comma-separated actions, e.g. `start,die`.
+    pub action: Option<String>,
+    /// Comma-separated `key` or `key=value` labels; all must match.
+    pub label: Option<String>,
+}
+
+/// Parsed [`EventParams`]. Empty lists match everything.
+struct EventFilter {
+    types: Vec<String>,
+    actions: Vec<String>,
+    labels: Vec<(String, Option<String>)>,
+}
+
+impl EventFilter {
+    fn parse(params: &EventParams) -> Result<Self, String> {
+        let list = |value: &Option<String>| -> Vec<String> {
+            value
+                .as_deref()
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect()
+        };
+
+        let types = list(&params.types);
+        if let Some(unknown) = types.iter().find(|t| !EVENT_TYPES.contains(&t.as_str())) {
+            return Err(format!("Unknown event type {:?}: expected one of {}", unknown, EVENT_TYPES.join(", ")));
+        }
+        let labels = list(&params.label)
+            .into_iter()
+            .map(|label| match label.split_once('=') {
+                Some((key, value)) => (key.to_string(), Some(value.to_string())),
+                None => (label, None),
+            })
+            .collect();
+
+        Ok(Self { types, actions: list(&params.action), labels })
+    }
+
+    fn matches(&self, event: &DockerEvent) -> bool {
+        // Some actions carry details after a colon, e.g. `exec_start: sh`
+        let action = event.action.split(':').next().unwrap_or_default();
+        (self.types.is_empty() || self.types.contains(&event.typ))
+            && (self.actions.is_empty() || self.actions.iter().any(|a| a == action))
+            && self.labels.iter().all(|(key, value)| match (event.attributes.get(key), value) {
+                (Some(actual), Some(expected)) => actual == expected,
+                (Some(_), None) => true,
+                (None, _) => false,
+            })
+    }
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(events_sse))
+        .route("/ws", get(events_ws))
+        .route_layer(middleware::from_fn_with_state(Permission::View, require_permission))
+}
+
+/// Matching events, or the number of events skipped because the client
+/// fell behind.
+fn event_stream(state: &AppState, filter: EventFilter) -> impl Stream<Item = Result<DockerEvent, u64>> {
+    let receiver = state.docker.subscribe_events();
+    futures::stream::unfold(receiver, |mut receiver| async move {
+        match receiver.recv().await {
+            Ok(event) => Some((Ok(event), receiver)),
+            Err(RecvError::Lagged(missed)) => Some((Err(missed), receiver)),
+            Err(RecvError::Closed) => None,
+        }
+    })
+    .filter(move |item| {
+        let keep = item.as_ref().map_or(true, |event| filter.matches(event));
+        async move { keep }
+    })
+}
+
+/// Server-sent events: `docker` events carry the JSON event, `lagged`
+/// events the number of events that were dropped.
+async fn events_sse(
+    State(state): State<AppState>,
+    Query(params): Query<EventParams>,
+) -> impl IntoResponse {
+    let filter = match EventFilter::parse(&params) {
+        Ok(filter) => filter,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+
+    let stream = event_stream(&state, filter).map(|item| {
+        Ok::<_, Infallible>(match item {
+            Ok(event) => Event::default()
+                .event("docker")
+                .data(serde_json::to_string(&event).unwrap_or_default()),
+            Err(missed) => Event::default().event("lagged").data(missed.to_string()),
+        })
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default()).into_response()
+}
+
+async fn events_ws(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    Query(params): Query<EventParams>,
+) -> impl IntoResponse {
+    let filter = match EventFilter::parse(&params) {
+        Ok(filter) => filter,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    ws.on_upgrade(move |socket| handle_events_ws(socket, state, filter))
+}
+
+async fn handle_events_ws(mut socket: WebSocket, state: AppState, filter: EventFilter) {
+    let events = event_stream(&state, filter);
+    futures::pin_mut!(events);
+
+    loop {
+        tokio::select! {
+            item = events.next() => {
+                let json = match item {
+                    Some(Ok(event)) => serde_json::to_string(&event).unwrap_or_default(),
+                    Some(Err(missed)) => serde_json::json!({ "lagged": missed }).to_string(),
+                    None => break,
+                };
+                if socket.send(Message::Text(json)).await.is_err() {
+                    return;
+                }
+            }
+            // Notice closed sockets even while no events arrive
+            msg = socket.recv() => match msg {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+    let _ = socket.close().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bollard::service::{EventActor, EventMessage, EventMessageTypeEnum};
+    use std::collections::HashMap;
+
+    fn filter(types: &str, action: &str, label: &str) -> EventFilter {
+        let param = |s: &str| (!s.is_empty()).then(|| s.to_string());
+        EventFilter::parse(&EventParams { types: param(types), action: param(action), label: param(label) }).unwrap()
+    }
+
+    fn event(typ: EventMessageTypeEnum, action: &str, attributes: &[(&str, &str)]) -> DockerEvent {
+        DockerEvent::from(EventMessage {
+            typ: Some(typ),
+            action: Some(action.to_string()),
+            actor: Some(EventActor {
+                id: Some("abc".to_string()),
+                attributes: Some(attributes.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<HashMap<_, _>>()),
+            }),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn labels_match_by_key_or_by_key_and_value() {
+        let web = event(EventMessageTypeEnum::CONTAINER, "start", &[("name", "web"), ("tier", "frontend"), ("empty", "")]);
+
+        assert!(filter("", "", "tier").matches(&web));
+        assert!(filter("", "", "tier=frontend").matches(&web));
+        assert!(!filter("", "", "tier=backend").matches(&web));
+        assert!(!filter("", "", "owner").matches(&web));
+        assert!(filter("", "", "empty").matches(&web));
+        assert!(filter("", "", "empty=").matches(&web));
+        assert!(!filter("", "", "tier=").matches(&web));
+        // Every label has to match
+        assert!(filter("", "", "tier=frontend, name=web").matches(&web));
+        assert!(!filter("", "", "tier=frontend,name=db").matches(&web));
+    }
+
+    #[test]
+    fn types_actions_and_labels_combine() {
+        let start = event(EventMessageTypeEnum::CONTAINER, "start", &[("tier", "frontend")]);
+        let exec = event(EventMessageTypeEnum::CONTAINER, "exec_start: /bin/sh", &[("tier", "frontend")]);
+        let pull = event(EventMessageTypeEnum::IMAGE, "pull", &[("tier", "frontend")]);
+
+        assert!(filter("", "", "").matches(&start));
+        let containers = filter("container", "start,exec_start", "tier=frontend");
+        assert!(containers.matches(&start));
+        assert!(containers.matches(&exec));
+        assert!(!containers.matches(&pull));
+        assert!(!filter("container", "die", "").matches(&start));
+        assert!(!filter("container", "start", "tier=backend").matches(&start));
+        assert!(filter("container,image", "pull", "tier").matches(&pull));
+    }
+
+    #[test]
+    fn unknown_event_types_are_rejected() {
+        let params = EventParams { types: Some("container,widget".to_string()), action: None, label: None };
+        let error = EventFilter::parse(&params).err().unwrap();
+        assert!(error.contains("\"widget\""), "{}", error);
+    }
+}
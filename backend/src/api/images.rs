@@ -1,43 +1,486 @@
 use axum::{
-    extract::{State, Path},
-    response::IntoResponse,
+    body::Body,
+    extract::{State, Path, Query, rejection::JsonRejection, ws::{WebSocket, WebSocketUpgrade, Message}},
+    http::{header, HeaderMap, StatusCode},
+    middleware,
+    response::{sse::{Event, KeepAlive, Sse}, IntoResponse, Response},
     routing::{get, post, delete},
     Json,
     Router,
 };
+use bollard::auth::DockerCredentials;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
 use crate::AppState;
+use crate::api::optional_json;
+use crate::api::middleware::require_permission;
+use crate::models::Permission;
+use crate::services::build_service::{BuildError, BuildRequest};
+use crate::services::docker_service::{split_tag, BuildOutput, ImageProgress};
+use crate::services::registry_service::RegistryError;
+
+#[derive(Deserialize)]
+pub struct PullRequest {
+    pub image: String,
+    #[serde(flatten)]
+    pub registry: RegistryChoice,
+}
+
+/// How to log in to the registry. With neither field set, stored
+/// credentials for the image's registry are used, if any.
+#[derive(Deserialize, Default)]
+pub struct RegistryChoice {
+    /// Credentials for this request only.
+    pub auth: Option<RegistryAuth>,
+    /// A stored registry to take credentials from.
+    pub registry_id: Option<String>,
+}
+
+impl RegistryChoice {
+    async fn credentials(self, state: &AppState, image: &str) -> Result<Option<DockerCredentials>, RegistryError> {
+        match (self.auth, self.registry_id) {
+            (Some(auth), _) => Ok(Some(auth.into())),
+            (None, Some(id)) => Ok(Some(state.registries.credentials(&id, image).await?)),
+            (None, None) => state.registries.credentials_for(image).await,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ExportParams {
+    /// Comma-separated image ids or references.
+    pub images: String,
+}
+
+#[derive(Deserialize)]
+pub struct TagRequest {
+    /// Repository, including the registry host if not Docker Hub.
+    pub repo: String,
+    /// Defaults to `latest`.
+    pub tag: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct RegistryAuth {
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// OAuth token used instead of a username and password.
+    pub identity_token: Option<String>,
+    /// Registry host, e.g. `registry.gitlab.com`.
+    pub server_address: Option<String>,
+}
+
+impl From<RegistryAuth> for DockerCredentials {
+    fn from(auth: RegistryAuth) -> Self {
+        DockerCredentials {
+            username: auth.username,
+            password: auth.password,
+            identitytoken: auth.identity_token,
+            serveraddress: auth.server_address,
+            ..Default::default()
+        }
+    }
+}
+
+impl PullRequest {
+    /// Validates the request and picks the credentials to pull with.
+    async fn resolve(self, state: &AppState) -> Result<(String, Option<DockerCredentials>), RegistryError> {
+        let image = self.image.trim().to_string();
+        if image.is_empty() {
+            return Err(RegistryError::InvalidInput("Image must not be empty".into()));
+        }
+        let credentials = self.registry.credentials(state, &image).await?;
+        Ok((image, credentials))
+    }
+}
+
+/// Frames sent while an image is pulled or pushed, over either a socket or
+/// SSE.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum ProgressMessage {
+    Progress(ImageProgress),
+    Error { message: String },
+    Done { image: String },
+}
+
+/// Frames sent while an image builds.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum BuildMessage {
+    Log { message: String },
+    Progress(ImageProgress),
+    Done { image_id: String, tags: Vec<String> },
+    /// `image_id` is set if the image was built but couldn't be tagged.
+    Error { message: String, image_id: Option<String> },
+}
+
+impl IntoResponse for BuildError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            BuildError::NotFound => StatusCode::NOT_FOUND,
+            BuildError::InvalidInput(_) => StatusCode::BAD_REQUEST,
+            BuildError::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            BuildError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+impl ProgressMessage {
+    fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
 
 pub fn routes() -> Router<AppState> {
-    Router::new()
+    let view = Router::new()
         .route("/", get(list_images))
+        .route("/:id/inspect", get(inspect_image))
+        .route("/:id/history", get(image_history))
+        .route_layer(middleware::from_fn_with_state(Permission::View, require_permission));
+
+    let operate = Router::new()
         .route("/pull", post(pull_image))
+        .route("/pull/stream", post(pull_image_sse))
+        .route("/pull/ws", get(pull_image_ws))
+        .route("/export", get(export_images))
+        .route("/import", post(import_images))
+        .route("/build/context", post(upload_build_context))
+        .route("/build/ws", get(build_image_ws))
+        .route("/:id/tag", post(tag_image))
+        .route("/:id/push", post(push_image))
+        .route_layer(middleware::from_fn_with_state(Permission::Operate, require_permission));
+
+    let remove = Router::new()
         .route("/:id", delete(remove_image))
+        .route_layer(middleware::from_fn_with_state(Permission::Remove, require_permission));
+
+    view.merge(operate).merge(remove)
 }
 
 async fn list_images(State(state): State<AppState>) -> impl IntoResponse {
     match state.docker.list_images().await {
         Ok(images) => Json(images).into_response(),
-        Err(e) => (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
     }
 }
 
 async fn pull_image(
     State(state): State<AppState>,
-    Json(payload): Json<serde_json::Value>,
+    Json(payload): Json<PullRequest>,
 ) -> impl IntoResponse {
-    let image = payload["image"].as_str().unwrap_or_default();
-    match state.docker.pull_image(image).await {
-        Ok(_) => axum::http::StatusCode::OK.into_response(),
-        Err(e) => (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    let (image, credentials) = match payload.resolve(&state).await {
+        Ok(pull) => pull,
+        Err(e) => return e.into_response(),
+    };
+    match state.docker.pull_image(&image, credentials).await {
+        Ok(_) => StatusCode::OK.into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
     }
 }
 
+/// Pulls an image and streams progress as server-sent events.
+async fn pull_image_sse(
+    State(state): State<AppState>,
+    Json(payload): Json<PullRequest>,
+) -> impl IntoResponse {
+    let (image, credentials) = match payload.resolve(&state).await {
+        Ok(pull) => pull,
+        Err(e) => return e.into_response(),
+    };
+
+    progress_sse(state.docker.pull_image_stream(&image, credentials), image)
+}
+
+/// Streams progress frames as server-sent events. Dropping the response when
+/// the client disconnects drops `progress`, which aborts the operation.
+fn progress_sse(
+    progress: impl futures::Stream<Item = anyhow::Result<ImageProgress>> + Send + 'static,
+    image: String,
+) -> Response {
+    let stream = progress_messages(progress, image).map(|msg| Ok::<_, Infallible>(Event::default().data(msg.to_json())));
+    Sse::new(stream).keep_alive(KeepAlive::default()).into_response()
+}
+
+/// Maps progress lines to frames, ending with either `done` or `error`.
+fn progress_messages(
+    progress: impl futures::Stream<Item = anyhow::Result<ImageProgress>>,
+    image: String,
+) -> impl futures::Stream<Item = ProgressMessage> {
+    let progress = Box::pin(progress);
+    futures::stream::unfold(Some((progress, image)), |state| async move {
+        let (mut progress, image) = state?;
+        match progress.next().await {
+            Some(Ok(line)) => Some((ProgressMessage::Progress(line), Some((progress, image)))),
+            Some(Err(e)) => Some((ProgressMessage::Error { message: e.to_string() }, None)),
+            None => Some((ProgressMessage::Done { image }, None)),
+        }
+    })
+}
+
+/// Pulls an image over a socket. The client sends a [`PullRequest`] as the
+/// first text frame, which keeps credentials out of the URL.
+async fn pull_image_ws(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| handle_pull_ws(socket, state))
+}
+
+async fn handle_pull_ws(mut socket: WebSocket, state: AppState) {
+    let request = match socket.recv().await {
+        Some(Ok(Message::Text(text))) => serde_json::from_str::<PullRequest>(&text),
+        _ => return,
+    };
+    let resolved = match request {
+        Ok(request) => request.resolve(&state).await,
+        Err(e) => Err(RegistryError::InvalidInput(format!("Invalid pull request: {}", e))),
+    };
+    let (image, credentials) = match resolved {
+        Ok(pull) => pull,
+        Err(e) => {
+            let message = e.to_string();
+            let _ = socket.send(Message::Text(ProgressMessage::Error { message }.to_json())).await;
+            return;
+        }
+    };
+
+    let messages = progress_messages(state.docker.pull_image_stream(&image, credentials), image);
+    futures::pin_mut!(messages);
+    loop {
+        tokio::select! {
+            msg = messages.next() => match msg {
+                Some(msg) => {
+                    if socket.send(Message::Text(msg.to_json())).await.is_err() {
+                        return;
+                    }
+                }
+                None => break,
+            },
+            // Returning drops the pull stream, which aborts the pull
+            msg = socket.recv() => match msg {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+    let _ = socket.close().await;
+}
+
+/// Streams a tar archive of one or more images as a download, for loading
+/// on another host with `docker load` or `/import`.
+async fn export_images(
+    State(state): State<AppState>,
+    Query(params): Query<ExportParams>,
+) -> impl IntoResponse {
+    let names: Vec<&str> = params.images.split(',').map(str::trim).filter(|n| !n.is_empty()).collect();
+    if names.is_empty() {
+        return (StatusCode::BAD_REQUEST, "No images given").into_response();
+    }
+
+    // Fail fast on e.g. an unknown image instead of sending an empty file
+    let mut stream = state.docker.export_images(&names).peekable();
+    if let Some(Err(e)) = std::pin::Pin::new(&mut stream).peek().await {
+        let status = match e {
+            bollard::errors::Error::DockerResponseServerError { status_code: 404, .. } => StatusCode::NOT_FOUND,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        return (status, e.to_string()).into_response();
+    }
+
+    let file_name = if names.len() == 1 {
+        names[0].chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' }).collect()
+    } else {
+        "images".to_string()
+    };
+    (
+        [
+            (header::CONTENT_TYPE, "application/x-tar".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}.tar\"", file_name)),
+        ],
+        Body::from_stream(stream),
+    )
+        .into_response()
+}
+
+/// Loads images from a tar archive sent as the raw request body, as written
+/// by `/export` or `docker save`, and reports what was loaded.
+async fn import_images(
+    State(state): State<AppState>,
+    body: Body,
+) -> impl IntoResponse {
+    // A broken upload ends the archive early, which Docker reports
+    let archive = body
+        .into_data_stream()
+        .take_while(|chunk| futures::future::ready(chunk.is_ok()))
+        .filter_map(|chunk| futures::future::ready(chunk.ok()));
+    match state.docker.import_images(archive).await {
+        Ok(loaded) => Json(loaded).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+/// Stores a tar build context sent as the raw request body, optionally
+/// gzipped, for a following build.
+async fn upload_build_context(
+    State(state): State<AppState>,
+    body: Body,
+) -> impl IntoResponse {
+    match state.builds.save_context(body.into_data_stream()).await {
+        Ok(context) => (StatusCode::CREATED, Json(context)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Builds an image over a socket. The client sends a [`BuildRequest`] as the
+/// first text frame; closing the socket cancels the build.
+async fn build_image_ws(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| handle_build_ws(socket, state))
+}
+
+async fn handle_build_ws(mut socket: WebSocket, state: AppState) {
+    let request = match socket.recv().await {
+        Some(Ok(Message::Text(text))) => serde_json::from_str::<BuildRequest>(&text)
+            .map_err(|e| BuildError::InvalidInput(format!("Invalid build request: {}", e))),
+        _ => return,
+    };
+    let build = match request {
+        Ok(request) => state.builds.prepare(request).await,
+        Err(e) => Err(e),
+    };
+    let build = match build {
+        Ok(build) => build,
+        Err(e) => {
+            let _ = send_build_message(&mut socket, BuildMessage::Error { message: e.to_string(), image_id: None }).await;
+            return;
+        }
+    };
+
+    let mut tags: Vec<String> = std::iter::once(build.options.t.clone()).filter(|t| !t.is_empty()).collect();
+    let output = state.docker.build_image(build.options, build.credentials, build.context);
+    futures::pin_mut!(output);
+    let mut image_id = None;
+    loop {
+        let message = tokio::select! {
+            item = output.next() => match item {
+                Some(Ok(BuildOutput::Log(message))) => BuildMessage::Log { message },
+                Some(Ok(BuildOutput::Progress(progress))) => BuildMessage::Progress(progress),
+                Some(Ok(BuildOutput::ImageId(id))) => {
+                    image_id = Some(id);
+                    continue;
+                }
+                Some(Err(e)) => {
+                    let _ = send_build_message(&mut socket, BuildMessage::Error { message: e.to_string(), image_id: None }).await;
+                    return;
+                }
+                None => break,
+            },
+            // Returning drops the build stream, which cancels the build
+            msg = socket.recv() => match msg {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => continue,
+            },
+        };
+        if send_build_message(&mut socket, message).await.is_err() {
+            return;
+        }
+    }
+
+    let message = match image_id {
+        Some(image_id) => {
+            let mut failed = None;
+            for tag in build.extra_tags {
+                let (repo, tag_name) = split_tag(&tag);
+                match state.docker.tag_image(&image_id, repo, tag_name).await {
+                    Ok(()) => tags.push(tag.clone()),
+                    Err(e) => {
+                        failed = Some(format!("Failed to tag {}: {}", tag, e));
+                        break;
+                    }
+                }
+            }
+            match failed {
+                Some(message) => BuildMessage::Error { message, image_id: Some(image_id) },
+                None => BuildMessage::Done { image_id, tags },
+            }
+        }
+        None => BuildMessage::Error { message: "Build finished without producing an image".into(), image_id: None },
+    };
+    let _ = send_build_message(&mut socket, message).await;
+    let _ = socket.close().await;
+}
+
+async fn send_build_message(socket: &mut WebSocket, message: BuildMessage) -> Result<(), axum::Error> {
+    socket.send(Message::Text(serde_json::to_string(&message).unwrap_or_default())).await
+}
+
+async fn inspect_image(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.docker.inspect_image(&id).await {
+        Ok(image) => Json(image).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn image_history(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.docker.image_history(&id).await {
+        Ok(history) => Json(history).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn tag_image(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(payload): Json<TagRequest>,
+) -> impl IntoResponse {
+    let repo = payload.repo.trim();
+    if repo.is_empty() {
+        return (StatusCode::BAD_REQUEST, "Repository must not be empty").into_response();
+    }
+    let tag = payload.tag.as_deref().map(str::trim).filter(|t| !t.is_empty()).unwrap_or("latest");
+    match state.docker.tag_image(&id, repo, tag).await {
+        Ok(_) => StatusCode::OK.into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+/// Pushes `:id`, a `repo[:tag]` reference (URL-encoded, since it may contain
+/// slashes), and streams progress as server-sent events. The body is
+/// optional.
+async fn push_image(
+    State(state): State<AppState>,
+    Path(image): Path<String>,
+    headers: HeaderMap,
+    payload: Result<Json<RegistryChoice>, JsonRejection>,
+) -> impl IntoResponse {
+    let registry = match optional_json(&headers, payload) {
+        Ok(registry) => registry,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let credentials = match registry.credentials(&state, &image).await {
+        Ok(credentials) => credentials,
+        Err(e) => return e.into_response(),
+    };
+    progress_sse(state.docker.push_image_stream(&image, credentials), image)
+}
+
 async fn remove_image(
     State(state): State<AppState>,
     Path(id): Path<String>,
 ) -> impl IntoResponse {
     match state.docker.remove_image(&id).await {
-        Ok(_) => axum::http::StatusCode::OK.into_response(),
-        Err(e) => (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        Ok(_) => StatusCode::OK.into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
     }
 }
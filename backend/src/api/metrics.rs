@@ -0,0 +1,94 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    middleware,
+    response::IntoResponse,
+    routing::get,
+    Json,
+    Router,
+};
+use serde::Deserialize;
+use crate::AppState;
+use crate::api::middleware::require_permission;
+use crate::models::Permission;
+use crate::services::metrics_service::{Resolution, CONTAINER_METRICS, HOST_METRICS};
+
+/// Range queried when `from` is not given.
+const DEFAULT_RANGE_SECONDS: i64 = 3600;
+
+#[derive(Deserialize)]
+pub struct QueryParams {
+    pub metric: String,
+    /// Container name; all containers when omitted. Ignored for host metrics.
+    pub container: Option<String>,
+    /// Unix seconds or RFC 3339. Defaults to one hour before `to`.
+    pub from: Option<String>,
+    /// Unix seconds or RFC 3339. Defaults to now.
+    pub to: Option<String>,
+    /// `raw`, `1m`, `1h` or `auto` (default).
+    pub resolution: Option<String>,
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(list_metrics))
+        .route("/query", get(query_metrics))
+        .route_layer(middleware::from_fn_with_state(Permission::View, require_permission))
+}
+
+async fn list_metrics() -> impl IntoResponse {
+    Json(serde_json::json!({
+        "host": HOST_METRICS,
+        "container": CONTAINER_METRICS,
+    }))
+}
+
+async fn query_metrics(
+    State(state): State<AppState>,
+    Query(params): Query<QueryParams>,
+) -> impl IntoResponse {
+    let metric = params.metric.as_str();
+    if !HOST_METRICS.contains(&metric) && !CONTAINER_METRICS.contains(&metric) {
+        return (StatusCode::BAD_REQUEST, format!("Unknown metric {:?}", metric)).into_response();
+    }
+
+    let to = match parse_timestamp("to", params.to.as_deref()) {
+        Ok(to) => to.unwrap_or_else(|| chrono::Utc::now().timestamp()),
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    let from = match parse_timestamp("from", params.from.as_deref()) {
+        Ok(from) => from.unwrap_or(to - DEFAULT_RANGE_SECONDS),
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    if from > to {
+        return (StatusCode::BAD_REQUEST, "from must not be after to").into_response();
+    }
+
+    let resolution = match params.resolution.as_deref() {
+        None | Some("auto") => state.metrics.auto_resolution(from, to),
+        Some(r) => match Resolution::parse(r) {
+            Some(resolution) => resolution,
+            None => {
+                return (StatusCode::BAD_REQUEST, format!("Invalid resolution {:?}: expected raw, 1m, 1h or auto", r)).into_response()
+            }
+        },
+    };
+
+    match state.metrics.query(metric, params.container.as_deref(), from, to, resolution).await {
+        Ok(result) => Json(result).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+/// Parses unix seconds or an RFC 3339 timestamp.
+fn parse_timestamp(name: &str, value: Option<&str>) -> Result<Option<i64>, String> {
+    let Some(value) = value else {
+        return Ok(None);
+    };
+    if let Ok(secs) = value.parse::<i64>() {
+        return Ok(Some(secs));
+    }
+    chrono::DateTime::parse_from_rfc3339(value)
+        .map(|t| Some(t.timestamp()))
+        .map_err(|_| format!("Invalid {} {:?}: expected unix seconds or RFC 3339", name, value))
+}
@@ -0,0 +1,357 @@
+use axum::{
+    async_trait,
+    body::Body,
+    extract::{ConnectInfo, FromRequestParts, MatchedPath, OriginalUri, Query, RawPathParams, Request, State},
+    http::{header, request::Parts, HeaderMap, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use std::net::SocketAddr;
+use crate::models::{Permission, Role, User};
+use crate::services::audit_service::NewAuditEntry;
+use crate::AppState;
+
+/// JSON bodies up to this size are inspected for audit details.
+const AUDIT_BODY_LIMIT: usize = 64 * 1024;
+
+/// Mutating endpoints that aren't worth an audit entry.
+const AUDIT_SKIPPED: &[&str] = &["/api/auth/refresh"];
+
+/// GET endpoints that are audited anyway because they grant shell access.
+const AUDITED_READS: &[&str] = &["/api/containers/:id/exec"];
+
+/// Resources with a single instance, where POST means update rather than create.
+const SINGLETON_RESOURCES: &[&str] = &["settings"];
+
+/// GET routes answering with server-sent events, which browsers open with
+/// `EventSource`. Other streams are POSTs made with `fetch`, which can send
+/// the `Authorization` header.
+const EVENT_STREAM_ROUTES: &[&str] = &["/api/events"];
+
+#[derive(Deserialize)]
+struct TokenQuery {
+    token: Option<String>,
+}
+
+/// The user behind a validated JWT.
+///
+/// Inserted into request extensions by [`require_auth`]; when used as an
+/// extractor outside that middleware it validates the token itself.
+#[derive(Clone)]
+pub struct AuthUser {
+    pub user: User,
+    pub session_id: String,
+}
+
+impl AuthUser {
+    /// Unknown roles are granted nothing.
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        Role::parse(&self.user.role)
+            .map(|role| role.has_permission(permission))
+            .unwrap_or(false)
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for AuthUser {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        if let Some(user) = parts.extensions.get::<AuthUser>() {
+            return Ok(user.clone());
+        }
+
+        let token = bearer_token(&parts.headers).or_else(|| {
+            // Browsers can't set headers on WebSocket handshakes or
+            // EventSource requests, so those carry the token in the query
+            // string instead. Cookies aren't accepted: browsers attach them
+            // to cross-site handshakes too.
+            if accepts_query_token(parts) {
+                query_token(parts)
+            } else {
+                None
+            }
+        });
+
+        let Some(token) = token else {
+            return Err((StatusCode::UNAUTHORIZED, "Missing authentication token").into_response());
+        };
+
+        let claims = match state.auth.verify_token(&token) {
+            Ok(claims) => claims,
+            Err(_) => return Err((StatusCode::UNAUTHORIZED, "Invalid or expired token").into_response()),
+        };
+
+        match state.auth.session_user(&claims).await {
+            Ok(Some(user)) => Ok(AuthUser {
+                user,
+                session_id: claims.sid,
+            }),
+            Ok(None) => Err((StatusCode::UNAUTHORIZED, "Session is no longer valid").into_response()),
+            Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()),
+        }
+    }
+}
+
+/// Rejects requests without a valid token and makes the user available to
+/// handlers through the [`AuthUser`] extractor.
+pub async fn require_auth(
+    user: AuthUser,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    req.extensions_mut().insert(user.clone());
+    let mut response = next.run(req).await;
+    // Lets outer layers such as record_audit see who made the request
+    response.extensions_mut().insert(user);
+    response
+}
+
+/// Rejects requests whose user lacks the given permission. Applied per group of
+/// routes with `route_layer(from_fn_with_state(Permission::X, require_permission))`
+/// and must run inside [`require_auth`].
+pub async fn require_permission(
+    State(permission): State<Permission>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(user) = req.extensions().get::<AuthUser>() else {
+        return (StatusCode::UNAUTHORIZED, "Missing authentication token").into_response();
+    };
+
+    if !user.has_permission(permission) {
+        return (
+            StatusCode::FORBIDDEN,
+            format!("Missing permission: {}", permission.as_str()),
+        )
+            .into_response();
+    }
+
+    next.run(req).await
+}
+
+/// Records every mutating request, and every exec session, in the audit log
+/// once it has completed, including failed and denied ones. Applied with `route_layer` so the matched
+/// route template is available.
+pub async fn record_audit(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let (mut parts, body) = req.into_parts();
+    let endpoint = match parts.extensions.get::<MatchedPath>() {
+        Some(path) => path.as_str().to_string(),
+        None => parts.uri.path().to_string(),
+    };
+
+    let read_only = matches!(parts.method, Method::GET | Method::HEAD | Method::OPTIONS);
+    let audited = if read_only {
+        AUDITED_READS.contains(&endpoint.as_str())
+    } else {
+        !AUDIT_SKIPPED.contains(&endpoint.as_str())
+    };
+    if !audited {
+        return next.run(Request::from_parts(parts, body)).await;
+    }
+
+    let path_id = RawPathParams::from_request_parts(&mut parts, &state)
+        .await
+        .ok()
+        .and_then(|params| params.iter().find(|(k, _)| *k == "id").map(|(_, v)| v.to_string()));
+    let source_ip = parts
+        .extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip().to_string());
+    let method = parts.method.clone();
+
+    let (body, hints) = match audit_body_hints(&parts.headers, body).await {
+        Ok(result) => result,
+        Err(response) => return response,
+    };
+
+    let response = next.run(Request::from_parts(parts, body)).await;
+
+    let user = response.extensions().get::<AuthUser>().map(|auth| &auth.user);
+    let entry = NewAuditEntry {
+        user_id: user.map(|u| u.id.clone()),
+        username: user.map(|u| u.username.clone()).or(hints.username),
+        source_ip,
+        method: method.to_string(),
+        action: action_name(&method, &endpoint, hints.verb.as_deref()),
+        endpoint,
+        resource_id: path_id.or(hints.resource),
+        status: response.status().as_u16(),
+    };
+    if let Err(e) = state.audit.record(entry).await {
+        tracing::error!("Failed to record audit entry: {}", e);
+    }
+
+    response
+}
+
+#[derive(Default)]
+struct AuditHints {
+    resource: Option<String>,
+    username: Option<String>,
+    verb: Option<String>,
+}
+
+/// Pulls a few well-known fields (never credentials) out of small JSON bodies
+/// so entries name the image, project or user acted on. Returns the body
+/// unchanged for the handler.
+async fn audit_body_hints(headers: &HeaderMap, body: Body) -> Result<(Body, AuditHints), Response> {
+    let is_json = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.starts_with("application/json"))
+        .unwrap_or(false);
+    let small = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok())
+        .map(|len| len <= AUDIT_BODY_LIMIT)
+        .unwrap_or(false);
+    if !is_json || !small {
+        return Ok((body, AuditHints::default()));
+    }
+
+    let bytes = axum::body::to_bytes(body, AUDIT_BODY_LIMIT)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()).into_response())?;
+
+    let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap_or_default();
+    let field = |key: &str| json.get(key).and_then(|v| v.as_str()).map(str::to_string);
+    let hints = AuditHints {
+        resource: ["project_path", "image", "name", "Name", "username"]
+            .iter()
+            .find_map(|key| field(key)),
+        username: field("username"),
+        verb: field("action"),
+    };
+
+    Ok((Body::from(bytes), hints))
+}
+
+/// Derives `<resource>.<verb>` from the route template, e.g.
+/// `POST /api/containers/:id/start` becomes `containers.start` and
+/// `DELETE /api/images/:id` becomes `images.delete`.
+fn action_name(method: &Method, endpoint: &str, body_verb: Option<&str>) -> String {
+    let segments: Vec<&str> = endpoint
+        .trim_start_matches("/api/")
+        .split('/')
+        .filter(|s| !s.is_empty() && !s.starts_with(':') && !s.starts_with('*'))
+        .collect();
+
+    let Some((resource, rest)) = segments.split_first() else {
+        return format!("{} {}", method, endpoint);
+    };
+
+    let verb = match (rest, body_verb) {
+        // Generic action endpoints carry the real verb in the body
+        (["action"], Some(verb)) => verb.to_string(),
+        ([], _) => match *method {
+            Method::POST if SINGLETON_RESOURCES.contains(resource) => "update".to_string(),
+            Method::POST => "create".to_string(),
+            Method::PUT | Method::PATCH => "update".to_string(),
+            Method::DELETE => "delete".to_string(),
+            _ => method.as_str().to_lowercase(),
+        },
+        _ => rest.join("."),
+    };
+
+    format!("{}.{}", resource, verb)
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(|t| t.trim().to_string())
+}
+
+fn query_token(parts: &Parts) -> Option<String> {
+    Query::<TokenQuery>::try_from_uri(&parts.uri).ok()?.0.token
+}
+
+/// Only WebSocket handshakes and EventSource requests may authenticate
+/// without the `Authorization` header. Both are GETs; anything else a page
+/// can send cross-site without a preflight must not ride on them.
+fn accepts_query_token(parts: &Parts) -> bool {
+    if parts.method != Method::GET {
+        return false;
+    }
+    if is_websocket_upgrade(&parts.headers) {
+        return true;
+    }
+    let path = match parts.extensions.get::<OriginalUri>() {
+        Some(OriginalUri(uri)) => uri.path(),
+        None => parts.uri.path(),
+    };
+    let path = path.strip_suffix('/').unwrap_or(path);
+    is_event_stream(&parts.headers) && EVENT_STREAM_ROUTES.contains(&path)
+}
+
+fn is_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.contains("text/event-stream"))
+        .unwrap_or(false)
+}
+
+fn is_websocket_upgrade(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::UPGRADE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.eq_ignore_ascii_case("websocket"))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auth_user(role: &str) -> AuthUser {
+        AuthUser {
+            user: User { id: "1".into(), username: "someone".into(), role: role.into() },
+            session_id: "s".into(),
+        }
+    }
+
+    fn parts(method: Method, uri: &str, headers: &[(header::HeaderName, &str)]) -> Parts {
+        let mut request = axum::http::Request::builder().method(method).uri(uri);
+        for (name, value) in headers {
+            request = request.header(name, *value);
+        }
+        request.body(()).unwrap().into_parts().0
+    }
+
+    #[test]
+    fn query_tokens_are_only_accepted_for_websockets_and_event_streams() {
+        let sse = [(header::ACCEPT, "text/event-stream")];
+        let upgrade = [(header::UPGRADE, "websocket")];
+
+        assert!(accepts_query_token(&parts(Method::GET, "/api/events?token=t", &sse)));
+        assert!(accepts_query_token(&parts(Method::GET, "/api/events/?token=t", &sse)));
+        assert!(accepts_query_token(&parts(Method::GET, "/api/containers/web/exec?token=t", &upgrade)));
+
+        // Anything a page can send cross-site without a preflight
+        assert!(!accepts_query_token(&parts(Method::POST, "/api/system/prune?token=t", &sse)));
+        assert!(!accepts_query_token(&parts(Method::POST, "/api/images/pull/stream?token=t", &sse)));
+        assert!(!accepts_query_token(&parts(Method::DELETE, "/api/users/1?token=t", &sse)));
+        assert!(!accepts_query_token(&parts(Method::POST, "/api/containers/web/exec?token=t", &upgrade)));
+        assert!(!accepts_query_token(&parts(Method::GET, "/api/users?token=t", &sse)));
+        assert!(!accepts_query_token(&parts(Method::GET, "/api/events?token=t", &[])));
+    }
+
+    #[test]
+    fn unknown_roles_are_granted_nothing() {
+        assert!(auth_user("operator").has_permission(Permission::Operate));
+        assert!(!auth_user("operator").has_permission(Permission::HostAccess));
+        assert!(!auth_user("superuser").has_permission(Permission::View));
+        assert!(!auth_user("").has_permission(Permission::View));
+    }
+}
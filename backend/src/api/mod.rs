@@ -1,4 +1,12 @@
+pub mod alerts;
+pub mod audit;
 pub mod auth;
+pub mod events;
+pub mod metrics;
+pub mod middleware;
+pub mod notifications;
+pub mod registries;
+pub mod updates;
 pub mod containers;
 pub mod images;
 pub mod networks;
@@ -6,3 +14,75 @@ pub mod volumes;
 pub mod system;
 pub mod compose;
 pub mod settings;
+pub mod users;
+
+use axum::extract::rejection::JsonRejection;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::Json;
+
+/// Reads an optional JSON body: a request without one, or without a JSON
+/// content type, gets the default, while a body that doesn't parse is a 400.
+pub fn optional_json<T: Default>(headers: &HeaderMap, payload: Result<Json<T>, JsonRejection>) -> Result<T, (StatusCode, String)> {
+    match payload {
+        Ok(Json(value)) => Ok(value),
+        Err(JsonRejection::MissingJsonContentType(_)) => Ok(T::default()),
+        Err(_) if !has_body(headers) => Ok(T::default()),
+        Err(rejection) => Err((StatusCode::BAD_REQUEST, rejection.body_text())),
+    }
+}
+
+/// Whether the request declares a body, by length or chunked encoding.
+fn has_body(headers: &HeaderMap) -> bool {
+    match headers.get(header::CONTENT_LENGTH) {
+        Some(len) => len != "0",
+        None => headers.contains_key(header::TRANSFER_ENCODING),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::extract::FromRequest;
+    use axum::http::Request;
+
+    #[derive(Default, serde::Deserialize, PartialEq, Debug)]
+    struct Options {
+        tag: Option<String>,
+    }
+
+    async fn read(content_type: Option<&str>, body: &'static str) -> Result<Options, (StatusCode, String)> {
+        read_with_length(content_type, body, Some(body.len())).await
+    }
+
+    async fn read_with_length(
+        content_type: Option<&str>,
+        body: &'static str,
+        length: Option<usize>,
+    ) -> Result<Options, (StatusCode, String)> {
+        let mut request = Request::post("/");
+        if let Some(length) = length {
+            request = request.header(header::CONTENT_LENGTH, length);
+        }
+        if let Some(content_type) = content_type {
+            request = request.header(header::CONTENT_TYPE, content_type);
+        }
+        let request = request.body(Body::from(body)).unwrap();
+        let headers = request.headers().clone();
+        optional_json(&headers, Json::<Options>::from_request(request, &()).await)
+    }
+
+    #[tokio::test]
+    async fn optional_json_defaults_only_when_there_is_no_body() {
+        assert_eq!(read(None, "").await.unwrap(), Options::default());
+        assert_eq!(read(Some("application/json"), "").await.unwrap(), Options::default());
+        assert_eq!(read(Some("application/json"), r#"{"tag":"v2"}"#).await.unwrap().tag.as_deref(), Some("v2"));
+
+        assert_eq!(read_with_length(Some("application/json"), "", None).await.unwrap(), Options::default());
+
+        for body in ["{", r#"{"tag":1}"#, "null"] {
+            let (status, _) = read(Some("application/json"), body).await.unwrap_err();
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+        }
+    }
+}
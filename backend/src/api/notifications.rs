@@ -0,0 +1,95 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json,
+    Router,
+};
+use crate::AppState;
+use crate::api::middleware::{require_permission, AuthUser};
+use crate::models::Permission;
+use crate::services::notification_service::{ChannelError, ChannelInput};
+
+impl IntoResponse for ChannelError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            ChannelError::NotFound => StatusCode::NOT_FOUND,
+            ChannelError::NameTaken => StatusCode::CONFLICT,
+            ChannelError::InvalidInput(_) => StatusCode::BAD_REQUEST,
+            ChannelError::Delivery(_) => StatusCode::BAD_GATEWAY,
+            ChannelError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+/// Channels hold webhook URLs and SMTP credentials, so managing them needs
+/// the settings permission.
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(list_channels).post(create_channel))
+        .route("/:id", get(get_channel).put(update_channel).delete(delete_channel))
+        .route("/:id/test", post(test_channel))
+        .route_layer(middleware::from_fn_with_state(Permission::ManageSettings, require_permission))
+}
+
+async fn list_channels(State(state): State<AppState>) -> impl IntoResponse {
+    match state.notifications.list().await {
+        Ok(channels) => Json(channels).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn get_channel(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.notifications.get(&id).await {
+        Ok(channel) => Json(channel).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn create_channel(
+    State(state): State<AppState>,
+    Json(payload): Json<ChannelInput>,
+) -> impl IntoResponse {
+    match state.notifications.create(payload).await {
+        Ok(channel) => (StatusCode::CREATED, Json(channel)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn update_channel(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(payload): Json<ChannelInput>,
+) -> impl IntoResponse {
+    match state.notifications.update(&id, payload).await {
+        Ok(channel) => Json(channel).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn delete_channel(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.notifications.delete(&id).await {
+        Ok(()) => StatusCode::OK.into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn test_channel(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.notifications.send_test(&id, &auth.user.username).await {
+        Ok(()) => StatusCode::OK.into_response(),
+        Err(e) => e.into_response(),
+    }
+}
@@ -0,0 +1,94 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json,
+    Router,
+};
+use crate::AppState;
+use crate::api::middleware::require_permission;
+use crate::models::Permission;
+use crate::services::registry_service::{RegistryError, RegistryInput};
+
+impl IntoResponse for RegistryError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            RegistryError::NotFound => StatusCode::NOT_FOUND,
+            RegistryError::HostTaken => StatusCode::CONFLICT,
+            RegistryError::InvalidInput(_) => StatusCode::BAD_REQUEST,
+            RegistryError::Login(_) => StatusCode::BAD_GATEWAY,
+            RegistryError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+/// Registry credentials are secrets, so managing them needs the settings
+/// permission.
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(list_registries).post(create_registry))
+        .route("/:id", get(get_registry).put(update_registry).delete(delete_registry))
+        .route("/:id/test", post(test_registry))
+        .route_layer(middleware::from_fn_with_state(Permission::ManageSettings, require_permission))
+}
+
+async fn list_registries(State(state): State<AppState>) -> impl IntoResponse {
+    match state.registries.list().await {
+        Ok(registries) => Json(registries).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn get_registry(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.registries.get(&id).await {
+        Ok(registry) => Json(registry).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn create_registry(
+    State(state): State<AppState>,
+    Json(payload): Json<RegistryInput>,
+) -> impl IntoResponse {
+    match state.registries.create(payload).await {
+        Ok(registry) => (StatusCode::CREATED, Json(registry)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn update_registry(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(payload): Json<RegistryInput>,
+) -> impl IntoResponse {
+    match state.registries.update(&id, payload).await {
+        Ok(registry) => Json(registry).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn delete_registry(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.registries.delete(&id).await {
+        Ok(()) => StatusCode::OK.into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn test_registry(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.registries.test_login(&id).await {
+        Ok(()) => StatusCode::OK.into_response(),
+        Err(e) => e.into_response(),
+    }
+}
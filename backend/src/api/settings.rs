@@ -1,16 +1,27 @@
 use axum::{
     extract::State,
+    middleware,
     response::IntoResponse,
-    routing::get,
+    routing::{get, post},
     Json,
     Router,
 };
 use crate::AppState;
-use serde_json::Value;
+use crate::api::middleware::require_permission;
+use crate::models::Permission;
+use crate::services::{prune_service, update_service};
+use crate::services::settings_service::INTERNAL_PREFIX;
 
 pub fn routes() -> Router<AppState> {
-    Router::new()
-        .route("/", get(get_settings).post(update_settings))
+    let view = Router::new()
+        .route("/", get(get_settings))
+        .route_layer(middleware::from_fn_with_state(Permission::View, require_permission));
+
+    let manage = Router::new()
+        .route("/", post(update_settings))
+        .route_layer(middleware::from_fn_with_state(Permission::ManageSettings, require_permission));
+
+    view.merge(manage)
 }
 
 async fn get_settings(State(state): State<AppState>) -> impl IntoResponse {
@@ -24,6 +35,16 @@ async fn update_settings(
     State(state): State<AppState>,
     Json(payload): Json<std::collections::HashMap<String, String>>,
 ) -> impl IntoResponse {
+    if let Some(key) = payload.keys().find(|k| k.starts_with(INTERNAL_PREFIX)) {
+        return (axum::http::StatusCode::BAD_REQUEST, format!("Setting {} is read-only", key)).into_response();
+    }
+    for (key, value) in &payload {
+        let valid = prune_service::validate_setting(key, value).and_then(|_| update_service::validate_setting(key, value));
+        if let Err(e) = valid {
+            return (axum::http::StatusCode::BAD_REQUEST, e).into_response();
+        }
+    }
+
     for (key, value) in payload {
         if let Err(e) = state.settings.set_setting(&key, &value).await {
             return (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
@@ -1,18 +1,53 @@
 use axum::{
-    extract::{State, ws::{WebSocket, WebSocketUpgrade, Message}},
+    extract::{Query, State, ws::{WebSocket, WebSocketUpgrade, Message}},
+    http::StatusCode,
+    middleware,
     response::IntoResponse,
-    routing::get,
+    routing::{get, post},
     Json,
     Router,
 };
+use serde::{Deserialize, Serialize};
 use crate::AppState;
+use crate::api::audit::PageParams;
+use crate::api::middleware::{require_permission, AuthUser};
+use crate::models::Permission;
+use crate::services::prune_service::{PruneOptions, PruneRun};
 use std::time::Duration;
 use tokio::time::sleep;
 
+const DEFAULT_PER_PAGE: i64 = 50;
+const MAX_PER_PAGE: i64 = 500;
+
+#[derive(Deserialize)]
+pub struct DiskUsageParams {
+    /// Bypass the cache.
+    #[serde(default)]
+    pub refresh: bool,
+}
+
+#[derive(Serialize)]
+pub struct PruneRunPage {
+    pub runs: Vec<PruneRun>,
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+}
+
 pub fn routes() -> Router<AppState> {
-    Router::new()
+    let view = Router::new()
         .route("/stats", get(get_stats))
         .route("/stats/ws", get(stats_ws_handler))
+        .route("/df", get(get_disk_usage))
+        .route("/prune/runs", get(list_prune_runs))
+        .route("/prune/schedule", get(get_prune_schedule))
+        .route_layer(middleware::from_fn_with_state(Permission::View, require_permission));
+
+    let remove = Router::new()
+        .route("/prune", post(prune))
+        .route_layer(middleware::from_fn_with_state(Permission::Remove, require_permission));
+
+    view.merge(remove)
 }
 
 async fn get_stats(State(state): State<AppState>) -> impl IntoResponse {
@@ -32,10 +67,66 @@ async fn handle_stats_ws(mut socket: WebSocket, state: AppState) {
         let stats = state.system.get_stats();
         let json = serde_json::to_string(&stats).unwrap_or_default();
         
-        if socket.send(Message::Text(json.into())).await.is_err() {
+        if socket.send(Message::Text(json)).await.is_err() {
             break;
         }
         
-        sleep(Duration::from_secs(2)).await;
+        sleep(Duration::from_secs(state.config.monitoring.stats_interval_seconds)).await;
+    }
+}
+
+async fn get_disk_usage(
+    State(state): State<AppState>,
+    Query(params): Query<DiskUsageParams>,
+) -> impl IntoResponse {
+    match state.disk_usage.get(params.refresh).await {
+        Ok(usage) => Json(usage).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn prune(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Json(options): Json<PruneOptions>,
+) -> impl IntoResponse {
+    if options.targets.is_empty() {
+        return (StatusCode::BAD_REQUEST, "At least one target is required").into_response();
+    }
+
+    match state.prune.prune(&options, "manual", Some(&auth.user.username)).await {
+        Ok(report) => {
+            if !report.dry_run {
+                state.disk_usage.invalidate().await;
+            }
+            Json(report).into_response()
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn list_prune_runs(
+    State(state): State<AppState>,
+    Query(params): Query<PageParams>,
+) -> impl IntoResponse {
+    let page = params.page.unwrap_or(1).max(1);
+    let per_page = params.per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE);
+
+    match state.prune.list_runs(per_page, (page - 1) * per_page).await {
+        Ok((runs, total)) => Json(PruneRunPage {
+            runs,
+            total,
+            page,
+            per_page,
+        })
+        .into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn get_prune_schedule(State(state): State<AppState>) -> impl IntoResponse {
+    match state.prune.schedule().await {
+        Ok(schedule) => Json(schedule).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
     }
 }
@@ -0,0 +1,95 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    middleware,
+    response::IntoResponse,
+    routing::{get, post},
+    Json,
+    Router,
+};
+use serde::Serialize;
+use crate::AppState;
+use crate::api::audit::PageParams;
+use crate::api::middleware::{require_permission, AuthUser};
+use crate::models::Permission;
+use crate::services::update_service::ImageUpdate;
+
+const DEFAULT_PER_PAGE: i64 = 50;
+const MAX_PER_PAGE: i64 = 500;
+
+#[derive(Serialize)]
+pub struct UpdatePage {
+    pub updates: Vec<ImageUpdate>,
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+}
+
+pub fn routes() -> Router<AppState> {
+    let view = Router::new()
+        .route("/", get(list_checks))
+        .route("/schedule", get(get_schedule))
+        .route("/history", get(list_history))
+        .route_layer(middleware::from_fn_with_state(Permission::View, require_permission));
+
+    let operate = Router::new()
+        .route("/check", post(check_now))
+        .route("/:id/apply", post(apply_update))
+        .route_layer(middleware::from_fn_with_state(Permission::Operate, require_permission));
+
+    view.merge(operate)
+}
+
+async fn list_checks(State(state): State<AppState>) -> impl IntoResponse {
+    match state.updates.list_checks().await {
+        Ok(checks) => Json(checks).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn get_schedule(State(state): State<AppState>) -> impl IntoResponse {
+    match state.updates.schedule().await {
+        Ok(schedule) => Json(schedule).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn list_history(
+    State(state): State<AppState>,
+    Query(params): Query<PageParams>,
+) -> impl IntoResponse {
+    let page = params.page.unwrap_or(1).max(1);
+    let per_page = params.per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE);
+
+    match state.updates.list_history(per_page, (page - 1) * per_page).await {
+        Ok((updates, total)) => Json(UpdatePage {
+            updates,
+            total,
+            page,
+            per_page,
+        })
+        .into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+/// Checks every running container now, without applying updates.
+async fn check_now(State(state): State<AppState>) -> impl IntoResponse {
+    match state.updates.check_all().await {
+        Ok(checks) => Json(checks).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+/// Pulls the container's image and recreates it, whether or not it is
+/// labelled for automatic updates.
+async fn apply_update(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.updates.apply(&id, "manual", Some(&auth.user.username)).await {
+        Ok(update) => Json(update).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
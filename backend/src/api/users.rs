@@ -0,0 +1,147 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+    Json,
+    Router,
+};
+use serde::Deserialize;
+use crate::AppState;
+use crate::api::middleware::require_permission;
+use crate::models::{Permission, Role};
+use crate::services::user_service::UserError;
+
+#[derive(Deserialize)]
+pub struct CreateUserRequest {
+    pub username: String,
+    pub password: String,
+    pub role: String,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateRoleRequest {
+    pub role: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordRequest {
+    pub password: String,
+}
+
+impl IntoResponse for UserError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            UserError::NotFound => StatusCode::NOT_FOUND,
+            UserError::UsernameTaken | UserError::LastAdmin => StatusCode::CONFLICT,
+            UserError::InvalidInput(_) => StatusCode::BAD_REQUEST,
+            UserError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(list_users).post(create_user))
+        .route("/:id", get(get_user).delete(delete_user))
+        .route("/:id/role", put(update_role))
+        .route("/:id/password", put(reset_password))
+        .route("/:id/disable", post(disable_user))
+        .route("/:id/enable", post(enable_user))
+        .route_layer(middleware::from_fn_with_state(Permission::ManageUsers, require_permission))
+}
+
+fn parse_role(role: &str) -> Result<Role, UserError> {
+    Role::parse(role).ok_or_else(|| UserError::InvalidInput(format!("Unknown role: {}", role)))
+}
+
+async fn list_users(State(state): State<AppState>) -> impl IntoResponse {
+    match state.users.list().await {
+        Ok(users) => Json(users).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn get_user(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.users.get(&id).await {
+        Ok(user) => Json(user).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn create_user(
+    State(state): State<AppState>,
+    Json(payload): Json<CreateUserRequest>,
+) -> impl IntoResponse {
+    let role = match parse_role(&payload.role) {
+        Ok(role) => role,
+        Err(e) => return e.into_response(),
+    };
+
+    match state.users.create(&payload.username, &payload.password, role).await {
+        Ok(user) => (StatusCode::CREATED, Json(user)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn update_role(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(payload): Json<UpdateRoleRequest>,
+) -> impl IntoResponse {
+    let role = match parse_role(&payload.role) {
+        Ok(role) => role,
+        Err(e) => return e.into_response(),
+    };
+
+    match state.users.update_role(&id, role).await {
+        Ok(user) => Json(user).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn reset_password(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(payload): Json<ResetPasswordRequest>,
+) -> impl IntoResponse {
+    match state.users.reset_password(&id, &payload.password).await {
+        Ok(_) => StatusCode::OK.into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn disable_user(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.users.set_disabled(&id, true).await {
+        Ok(user) => Json(user).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn enable_user(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.users.set_disabled(&id, false).await {
+        Ok(user) => Json(user).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn delete_user(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.users.delete(&id).await {
+        Ok(_) => StatusCode::OK.into_response(),
+        Err(e) => e.into_response(),
+    }
+}
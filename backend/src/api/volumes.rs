@@ -1,17 +1,29 @@
 use axum::{
     extract::{State, Path},
+    middleware,
     response::IntoResponse,
     routing::{get, post, delete},
     Json,
     Router,
 };
 use crate::AppState;
+use crate::api::middleware::require_permission;
+use crate::models::Permission;
 
 pub fn routes() -> Router<AppState> {
-    Router::new()
+    let view = Router::new()
         .route("/", get(list_volumes))
+        .route_layer(middleware::from_fn_with_state(Permission::View, require_permission));
+
+    let operate = Router::new()
         .route("/", post(create_volume))
+        .route_layer(middleware::from_fn_with_state(Permission::Operate, require_permission));
+
+    let remove = Router::new()
         .route("/:id", delete(remove_volume))
+        .route_layer(middleware::from_fn_with_state(Permission::Remove, require_permission));
+
+    view.merge(operate).merge(remove)
 }
 
 async fn list_volumes(State(state): State<AppState>) -> impl IntoResponse {
@@ -0,0 +1,638 @@
+use anyhow::{anyhow, bail, Context, Result};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use yaml_rust::{Yaml, YamlLoader};
+
+/// Placeholder shipped in the example config; treated as unset.
+const PLACEHOLDER_JWT_SECRET: &str = "CHANGE_ME_IN_PRODUCTION";
+
+/// Checked in order when neither `--config` nor `DOCKIUM_CONFIG` is given.
+const DEFAULT_CONFIG_PATHS: &[&str] = &["config.yaml", "/etc/dockium/config.yaml"];
+
+/// Typed view of `config.yaml`. Every field has a default, so running without
+/// a config file behaves like the file in `deploy/config.yaml`.
+pub struct Config {
+    /// File the configuration was read from, if any.
+    pub source: Option<PathBuf>,
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub docker: DockerConfig,
+    pub security: SecurityConfig,
+    pub monitoring: MonitoringConfig,
+    pub logging: LoggingConfig,
+}
+
+pub struct ServerConfig {
+    pub host: IpAddr,
+    pub port: u16,
+    /// Present only when `server.ssl.enabled` is true.
+    pub ssl: Option<SslConfig>,
+}
+
+#[derive(Clone)]
+pub struct SslConfig {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    /// Plain HTTP port that redirects to HTTPS, if any.
+    pub redirect_http_port: Option<u16>,
+}
+
+pub struct DatabaseConfig {
+    pub url: String,
+}
+
+pub struct DockerConfig {
+    pub socket_path: String,
+    /// `tcp://` or `http://` address of a remote engine; takes precedence over
+    /// `socket_path` when set.
+    pub remote_host: Option<String>,
+    /// Host directories that image builds may use as their context. Empty
+    /// allows uploaded contexts only.
+    pub build_context_roots: Vec<PathBuf>,
+    /// Largest build context accepted, in bytes. Contexts are held in memory
+    /// while they are sent to Docker.
+    pub max_build_context_bytes: u64,
+}
+
+pub struct SecurityConfig {
+    /// Generated and stored in the settings table when not configured.
+    pub jwt_secret: Option<String>,
+    pub admin_setup_enabled: bool,
+    /// Access token lifetime in seconds.
+    pub session_timeout: u64,
+    /// Seconds a session can go without refreshing before it expires.
+    pub refresh_token_lifetime: u64,
+}
+
+pub struct MonitoringConfig {
+    pub stats_interval_seconds: u64,
+    /// How long `/api/system/df` results are cached. Measuring every
+    /// container's writable layer is slow on busy hosts.
+    pub disk_usage_refresh_seconds: u64,
+    pub history: HistoryConfig,
+    pub alerts: AlertsConfig,
+}
+
+/// Alert rules evaluated in the background. Host thresholds come from
+/// `monitoring.notification_thresholds`.
+#[derive(Clone)]
+pub struct AlertsConfig {
+    pub enabled: bool,
+    pub evaluation_interval_seconds: u64,
+    /// How long a threshold or healthcheck must stay breached before firing.
+    pub for_seconds: u64,
+    /// Percentage points below the threshold a value must fall to resolve.
+    pub hysteresis_percent: f64,
+    pub cpu_percent: f64,
+    pub memory_percent: f64,
+    pub disk_percent: f64,
+    /// Restarts within `restart_loop_window_seconds` that count as a loop.
+    pub restart_loop_count: u64,
+    pub restart_loop_window_seconds: u64,
+}
+
+/// Background collection of host and container metrics into the database.
+#[derive(Clone)]
+pub struct HistoryConfig {
+    pub enabled: bool,
+    /// Seconds between raw samples.
+    pub interval_seconds: u64,
+    pub raw_retention_hours: u64,
+    pub minute_retention_days: u64,
+    pub hour_retention_days: u64,
+}
+
+pub struct LoggingConfig {
+    /// `tracing_subscriber::EnvFilter` directive, e.g. `info` or `dockium_backend=debug`.
+    pub level: String,
+    pub file_path: Option<PathBuf>,
+    /// Rotate the log file once it reaches `max_size_bytes`, keeping
+    /// `max_backups` old files.
+    pub rotate: bool,
+    pub max_size_bytes: u64,
+    pub max_backups: usize,
+}
+
+impl DatabaseConfig {
+    /// Directory holding the SQLite file, where other server state is kept
+    /// too. Falls back to the temp directory for in-memory databases.
+    pub fn data_dir(&self) -> PathBuf {
+        let path = self.url.strip_prefix("sqlite:").unwrap_or(&self.url);
+        let path = path.strip_prefix("//").unwrap_or(path);
+        let path = path.split('?').next().unwrap_or_default();
+        if path.is_empty() || path.starts_with(':') {
+            return std::env::temp_dir();
+        }
+        match Path::new(path).parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn addr(&self) -> SocketAddr {
+        SocketAddr::new(self.host, self.port)
+    }
+}
+
+impl Config {
+    /// Loads the file given by `--config <path>` (or `DOCKIUM_CONFIG`), falling
+    /// back to the first default location that exists, then applies
+    /// environment variable overrides.
+    pub fn load() -> Result<Self> {
+        let explicit = config_path_from_args(std::env::args().skip(1))?
+            .or_else(|| std::env::var_os("DOCKIUM_CONFIG").map(PathBuf::from));
+
+        let source = match explicit {
+            Some(path) => Some(path),
+            None => DEFAULT_CONFIG_PATHS
+                .iter()
+                .map(PathBuf::from)
+                .find(|p| p.is_file()),
+        };
+
+        let doc = match &source {
+            Some(path) => read_yaml(path)?,
+            None => Yaml::Null,
+        };
+
+        let mut config = Self::from_yaml(&doc)?;
+        config.source = source;
+        config.apply_env()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn from_yaml(doc: &Yaml) -> Result<Self> {
+        let root = Section::root(doc)?;
+
+        let server = root.section("server")?;
+        let ssl = server.section("ssl")?;
+        let database = root.section("database")?;
+        let docker = root.section("docker")?;
+        let security = root.section("security")?;
+        let monitoring = root.section("monitoring")?;
+        let history = monitoring.section("history")?;
+        let thresholds = monitoring.section("notification_thresholds")?;
+        let alerts = monitoring.section("alerts")?;
+        let logging = root.section("logging")?;
+
+        Ok(Self {
+            source: None,
+            server: ServerConfig {
+                host: server.parsed("host")?.unwrap_or(IpAddr::from([0, 0, 0, 0])),
+                port: server.integer("port")?.unwrap_or(8080),
+                ssl: if ssl.boolean("enabled")?.unwrap_or(false) {
+                    Some(SslConfig {
+                        cert: ssl.required_string("cert")?.into(),
+                        key: ssl.required_string("key")?.into(),
+                        redirect_http_port: ssl.integer("redirect_http_port")?,
+                    })
+                } else {
+                    None
+                },
+            },
+            database: DatabaseConfig {
+                url: database.string("url")?.unwrap_or_else(|| "sqlite:dockium.db".into()),
+            },
+            docker: DockerConfig {
+                socket_path: docker
+                    .string("socket_path")?
+                    .unwrap_or_else(|| "/var/run/docker.sock".into()),
+                remote_host: docker.string("remote_host")?,
+                build_context_roots: docker
+                    .strings("build_context_roots")?
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(PathBuf::from)
+                    .collect(),
+                max_build_context_bytes: {
+                    let mb = docker.integer::<u64>("max_build_context_mb")?.unwrap_or(512);
+                    mb.checked_mul(1024 * 1024)
+                        .ok_or_else(|| anyhow!("docker.max_build_context_mb is out of range: {}", mb))?
+                },
+            },
+            security: SecurityConfig {
+                jwt_secret: security.string("jwt_secret")?,
+                admin_setup_enabled: security.boolean("admin_setup_enabled")?.unwrap_or(true),
+                session_timeout: security.integer("session_timeout")?.unwrap_or(86400),
+                refresh_token_lifetime: security
+                    .integer("refresh_token_lifetime")?
+                    .unwrap_or(30 * 86400),
+            },
+            monitoring: MonitoringConfig {
+                stats_interval_seconds: monitoring.integer("stats_interval_seconds")?.unwrap_or(2),
+                disk_usage_refresh_seconds: monitoring.integer("disk_usage_refresh_seconds")?.unwrap_or(300),
+                history: HistoryConfig {
+                    enabled: history.boolean("enabled")?.unwrap_or(true),
+                    interval_seconds: history.integer("interval_seconds")?.unwrap_or(15),
+                    raw_retention_hours: history.integer("raw_retention_hours")?.unwrap_or(24),
+                    minute_retention_days: history.integer("minute_retention_days")?.unwrap_or(7),
+                    hour_retention_days: history.integer("hour_retention_days")?.unwrap_or(90),
+                },
+                alerts: AlertsConfig {
+                    enabled: alerts.boolean("enabled")?.unwrap_or(true),
+                    evaluation_interval_seconds: alerts.integer("evaluation_interval_seconds")?.unwrap_or(15),
+                    for_seconds: alerts.integer("for_seconds")?.unwrap_or(60),
+                    hysteresis_percent: alerts.number("hysteresis_percent")?.unwrap_or(5.0),
+                    cpu_percent: thresholds.number("cpu_percent")?.unwrap_or(90.0),
+                    memory_percent: thresholds.number("memory_percent")?.unwrap_or(95.0),
+                    disk_percent: thresholds.number("disk_percent")?.unwrap_or(90.0),
+                    restart_loop_count: alerts.integer("restart_loop_count")?.unwrap_or(3),
+                    restart_loop_window_seconds: alerts.integer("restart_loop_window_seconds")?.unwrap_or(300),
+                },
+            },
+            logging: LoggingConfig {
+                level: logging.string("level")?.unwrap_or_else(|| "info".into()),
+                file_path: logging.string("file_path")?.map(PathBuf::from),
+                rotate: logging.boolean("rotate")?.unwrap_or(false),
+                max_size_bytes: {
+                    let mb = logging.integer::<u64>("max_size_mb")?.unwrap_or(100);
+                    mb.checked_mul(1024 * 1024)
+                        .ok_or_else(|| anyhow!("logging.max_size_mb is out of range: {}", mb))?
+                },
+                max_backups: logging.integer("max_backups")?.unwrap_or(5),
+            },
+        })
+    }
+
+    /// Environment variables take precedence over the file so existing
+    /// deployments (systemd unit, Dockerfile) keep working unchanged.
+    fn apply_env(&mut self) -> Result<()> {
+        if let Some(host) = env_parsed("HOST")? {
+            self.server.host = host;
+        }
+        if let Some(port) = env_parsed("PORT")? {
+            self.server.port = port;
+        }
+        if let Ok(url) = std::env::var("DATABASE_URL") {
+            self.database.url = url;
+        }
+        if let Ok(host) = std::env::var("DOCKER_HOST") {
+            match host.strip_prefix("unix://") {
+                Some(path) => {
+                    self.docker.socket_path = path.to_string();
+                    self.docker.remote_host = None;
+                }
+                None => self.docker.remote_host = Some(host),
+            }
+        }
+        if let Ok(level) = std::env::var("RUST_LOG") {
+            self.logging.level = level;
+        }
+        if let Ok(secret) = std::env::var("JWT_SECRET") {
+            self.security.jwt_secret = Some(secret);
+        }
+        if self.security.jwt_secret.as_deref() == Some(PLACEHOLDER_JWT_SECRET) {
+            self.security.jwt_secret = None;
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.server.port == 0 {
+            bail!("server.port must be between 1 and 65535");
+        }
+        if let Some(ssl) = &self.server.ssl {
+            for (key, path) in [("cert", &ssl.cert), ("key", &ssl.key)] {
+                if !path.is_file() {
+                    bail!("server.ssl.{} {} does not exist", key, path.display());
+                }
+            }
+            if ssl.redirect_http_port.is_some_and(|p| p == 0 || p == self.server.port) {
+                bail!("server.ssl.redirect_http_port must be a free port other than server.port");
+            }
+        }
+        if !self.database.url.starts_with("sqlite:") {
+            bail!("database.url must be a sqlite: URL, got {:?}", self.database.url);
+        }
+        if let Some(host) = &self.docker.remote_host {
+            if !host.starts_with("tcp://") && !host.starts_with("http://") {
+                bail!("docker.remote_host must start with tcp:// or http://, got {:?}", host);
+            }
+        } else if self.docker.socket_path.is_empty() {
+            bail!("docker.socket_path must not be empty");
+        }
+        if let Some(root) = self.docker.build_context_roots.iter().find(|r| !r.is_absolute()) {
+            bail!("docker.build_context_roots must be absolute paths, got {}", root.display());
+        }
+        if self.docker.max_build_context_bytes == 0 {
+            bail!("docker.max_build_context_mb must be at least 1");
+        }
+        if matches!(&self.security.jwt_secret, Some(s) if s.len() < 16) {
+            bail!("security.jwt_secret must be at least 16 characters");
+        }
+        if self.security.session_timeout < 60 {
+            bail!("security.session_timeout must be at least 60 seconds");
+        }
+        if self.security.refresh_token_lifetime < self.security.session_timeout {
+            bail!("security.refresh_token_lifetime must not be shorter than security.session_timeout");
+        }
+        if self.monitoring.stats_interval_seconds == 0 {
+            bail!("monitoring.stats_interval_seconds must be at least 1");
+        }
+        if self.monitoring.disk_usage_refresh_seconds == 0 {
+            bail!("monitoring.disk_usage_refresh_seconds must be at least 1");
+        }
+        let history = &self.monitoring.history;
+        if history.interval_seconds == 0 || history.interval_seconds > 60 {
+            bail!("monitoring.history.interval_seconds must be between 1 and 60");
+        }
+        if history.raw_retention_hours == 0
+            || history.minute_retention_days == 0
+            || history.hour_retention_days == 0
+        {
+            bail!("monitoring.history retention periods must be at least 1");
+        }
+        let alerts = &self.monitoring.alerts;
+        if alerts.evaluation_interval_seconds == 0 {
+            bail!("monitoring.alerts.evaluation_interval_seconds must be at least 1");
+        }
+        for (key, value) in [
+            ("cpu_percent", alerts.cpu_percent),
+            ("memory_percent", alerts.memory_percent),
+            ("disk_percent", alerts.disk_percent),
+        ] {
+            if !(0.0..=100.0).contains(&value) {
+                bail!("monitoring.notification_thresholds.{} must be between 0 and 100", key);
+            }
+        }
+        if !(0.0..100.0).contains(&alerts.hysteresis_percent) {
+            bail!("monitoring.alerts.hysteresis_percent must be between 0 and 100");
+        }
+        if alerts.restart_loop_count == 0 || alerts.restart_loop_window_seconds == 0 {
+            bail!("monitoring.alerts.restart_loop_count and restart_loop_window_seconds must be at least 1");
+        }
+        if self.logging.max_size_bytes == 0 {
+            bail!("logging.max_size_mb must be at least 1");
+        }
+        tracing_subscriber::EnvFilter::try_new(&self.logging.level)
+            .map_err(|e| anyhow!("logging.level {:?} is invalid: {}", self.logging.level, e))?;
+        Ok(())
+    }
+}
+
+/// Accepts both `--config path` and `--config=path`.
+fn config_path_from_args(mut args: impl Iterator<Item = String>) -> Result<Option<PathBuf>> {
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            let path = args.next().ok_or_else(|| anyhow!("--config requires a path"))?;
+            return Ok(Some(PathBuf::from(path)));
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Ok(Some(PathBuf::from(path)));
+        }
+    }
+    Ok(None)
+}
+
+fn read_yaml(path: &Path) -> Result<Yaml> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file {}", path.display()))?;
+    let mut docs = YamlLoader::load_from_str(&contents)
+        .with_context(|| format!("Failed to parse config file {}", path.display()))?;
+    Ok(if docs.is_empty() { Yaml::Null } else { docs.swap_remove(0) })
+}
+
+fn env_parsed<T: FromStr>(name: &str) -> Result<Option<T>>
+where
+    T::Err: std::fmt::Display,
+{
+    match std::env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|e| anyhow!("Invalid value for {}: {:?} ({})", name, value, e)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// A mapping in the YAML document, with typed accessors that report the full
+/// key path on errors. Missing sections and keys read as `None`.
+struct Section<'a> {
+    path: String,
+    node: &'a Yaml,
+}
+
+impl<'a> Section<'a> {
+    fn root(doc: &'a Yaml) -> Result<Self> {
+        match doc {
+            Yaml::Hash(_) | Yaml::Null => Ok(Self { path: String::new(), node: doc }),
+            _ => bail!("Config file must contain a mapping at the top level"),
+        }
+    }
+
+    fn key_path(&self, key: &str) -> String {
+        if self.path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", self.path, key)
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&'a Yaml> {
+        match self.node {
+            Yaml::Hash(map) => match map.get(&Yaml::String(key.to_string())) {
+                None | Some(Yaml::Null) => None,
+                Some(value) => Some(value),
+            },
+            _ => None,
+        }
+    }
+
+    fn section(&self, key: &str) -> Result<Section<'a>> {
+        let path = self.key_path(key);
+        match self.get(key) {
+            None => Ok(Section { path, node: &Yaml::Null }),
+            Some(node @ Yaml::Hash(_)) => Ok(Section { path, node }),
+            Some(_) => bail!("{} must be a mapping", path),
+        }
+    }
+
+    fn string(&self, key: &str) -> Result<Option<String>> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Yaml::String(s)) => Ok(Some(s.clone())),
+            Some(Yaml::Integer(i)) => Ok(Some(i.to_string())),
+            Some(Yaml::Real(r)) => Ok(Some(r.clone())),
+            Some(Yaml::Boolean(b)) => Ok(Some(b.to_string())),
+            Some(other) => bail!("{} must be a string, got {:?}", self.key_path(key), other),
+        }
+    }
+
+    fn strings(&self, key: &str) -> Result<Option<Vec<String>>> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Yaml::Array(items)) => items
+                .iter()
+                .map(|item| match item {
+                    Yaml::String(s) => Ok(s.clone()),
+                    other => bail!("{} must be a list of strings, got {:?}", self.key_path(key), other),
+                })
+                .collect::<Result<_>>()
+                .map(Some),
+            Some(other) => bail!("{} must be a list of strings, got {:?}", self.key_path(key), other),
+        }
+    }
+
+    fn required_string(&self, key: &str) -> Result<String> {
+        self.string(key)?
+            .ok_or_else(|| anyhow!("{} is required", self.key_path(key)))
+    }
+
+    fn integer<T: TryFrom<i64>>(&self, key: &str) -> Result<Option<T>> {
+        let value = match self.get(key) {
+            None => return Ok(None),
+            Some(Yaml::Integer(i)) => *i,
+            Some(Yaml::String(s)) => s
+                .trim()
+                .parse()
+                .map_err(|_| anyhow!("{} must be an integer, got {:?}", self.key_path(key), s))?,
+            Some(other) => bail!("{} must be an integer, got {:?}", self.key_path(key), other),
+        };
+        T::try_from(value)
+            .map(Some)
+            .map_err(|_| anyhow!("{} is out of range: {}", self.key_path(key), value))
+    }
+
+    fn number(&self, key: &str) -> Result<Option<f64>> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Yaml::Integer(i)) => Ok(Some(*i as f64)),
+            Some(Yaml::Real(r)) => r
+                .parse()
+                .map(Some)
+                .map_err(|_| anyhow!("{} must be a number, got {:?}", self.key_path(key), r)),
+            Some(other) => bail!("{} must be a number, got {:?}", self.key_path(key), other),
+        }
+    }
+
+    fn boolean(&self, key: &str) -> Result<Option<bool>> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Yaml::Boolean(b)) => Ok(Some(*b)),
+            Some(other) => bail!("{} must be true or false, got {:?}", self.key_path(key), other),
+        }
+    }
+
+    fn parsed<T: FromStr>(&self, key: &str) -> Result<Option<T>>
+    where
+        T::Err: std::fmt::Display,
+    {
+        match self.string(key)? {
+            None => Ok(None),
+            Some(s) => s
+                .parse()
+                .map(Some)
+                .map_err(|e| anyhow!("{} is invalid: {:?} ({})", self.key_path(key), s, e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(yaml: &str) -> Result<Config> {
+        let doc = YamlLoader::load_from_str(yaml)?.pop().unwrap_or(Yaml::Null);
+        let config = Config::from_yaml(&doc)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// The error message for a config that must be rejected.
+    fn error(yaml: &str) -> String {
+        match parse(yaml) {
+            Ok(_) => panic!("accepted {:?}", yaml),
+            Err(e) => format!("{:#}", e),
+        }
+    }
+
+    fn data_dir(url: &str) -> PathBuf {
+        DatabaseConfig { url: url.into() }.data_dir()
+    }
+
+    #[test]
+    fn data_dir_is_the_database_directory() {
+        assert_eq!(data_dir("sqlite:/data/dockium.db"), PathBuf::from("/data"));
+        assert_eq!(data_dir("sqlite:///var/lib/dockium/dockium.db?mode=rwc"), PathBuf::from("/var/lib/dockium"));
+        assert_eq!(data_dir("sqlite:state/dockium.db"), PathBuf::from("state"));
+        assert_eq!(data_dir("sqlite:dockium.db"), PathBuf::from("."));
+        assert_eq!(data_dir("sqlite::memory:"), std::env::temp_dir());
+    }
+
+    #[test]
+    fn max_build_context_is_converted_to_bytes() {
+        let config = parse("docker:\n  max_build_context_mb: 2\n").unwrap();
+        assert_eq!(config.docker.max_build_context_bytes, 2 * 1024 * 1024);
+        assert_eq!(parse("").unwrap().docker.max_build_context_bytes, 512 * 1024 * 1024);
+
+        assert!(error("docker:\n  max_build_context_mb: 0\n").contains("at least 1"));
+        let overflow = error(&format!("docker:\n  max_build_context_mb: {}\n", u64::MAX / 1024));
+        assert!(overflow.contains("out of range"), "{}", overflow);
+    }
+
+    #[test]
+    fn defaults_apply_to_an_empty_file() {
+        let config = parse("").unwrap();
+        assert_eq!(config.server.addr(), "0.0.0.0:8080".parse().unwrap());
+        assert!(config.server.ssl.is_none());
+        assert_eq!(config.database.url, "sqlite:dockium.db");
+        assert_eq!(config.docker.socket_path, "/var/run/docker.sock");
+        assert!(config.security.jwt_secret.is_none());
+        assert_eq!(config.security.session_timeout, 86400);
+        assert_eq!(config.monitoring.history.interval_seconds, 15);
+        assert_eq!(config.logging.level, "info");
+        assert!(config.logging.file_path.is_none());
+        assert_eq!(config.logging.max_size_bytes, 100 * 1024 * 1024);
+    }
+
+    #[test]
+    fn the_example_config_is_valid() {
+        let config = parse(include_str!("../../deploy/config.yaml")).unwrap();
+        assert_eq!(config.server.port, 8080);
+        assert_eq!(config.monitoring.alerts.cpu_percent, 90.0);
+    }
+
+    #[test]
+    fn invalid_values_name_the_key() {
+        for (yaml, key) in [
+            ("[1, 2]", "mapping at the top level"),
+            ("server: 8080", "server must be a mapping"),
+            ("server:\n  port: 0", "server.port"),
+            ("server:\n  port: 70000", "server.port"),
+            ("server:\n  host: localhost", "server.host"),
+            ("server:\n  ssl:\n    enabled: true", "server.ssl.cert"),
+            ("server:\n  ssl:\n    enabled: yes", "server.ssl.enabled"),
+            ("database:\n  url: postgres://db", "database.url"),
+            ("docker:\n  remote_host: unix:///run/docker.sock", "docker.remote_host"),
+            ("docker:\n  socket_path: ''", "docker.socket_path"),
+            ("docker:\n  build_context_roots: [builds]", "docker.build_context_roots"),
+            ("security:\n  jwt_secret: short", "security.jwt_secret"),
+            ("security:\n  session_timeout: 30", "security.session_timeout"),
+            ("security:\n  refresh_token_lifetime: 60", "security.refresh_token_lifetime"),
+            ("monitoring:\n  stats_interval_seconds: 0", "monitoring.stats_interval_seconds"),
+            ("monitoring:\n  history:\n    interval_seconds: 61", "monitoring.history.interval_seconds"),
+            ("monitoring:\n  history:\n    hour_retention_days: 0", "monitoring.history"),
+            ("monitoring:\n  notification_thresholds:\n    cpu_percent: 120", "cpu_percent"),
+            ("monitoring:\n  alerts:\n    hysteresis_percent: 100", "hysteresis_percent"),
+            ("monitoring:\n  alerts:\n    restart_loop_count: 0", "restart_loop_count"),
+            ("logging:\n  level: '=['", "logging.level"),
+            ("logging:\n  max_size_mb: 0", "logging.max_size_mb"),
+        ] {
+            let message = error(yaml);
+            assert!(message.contains(key), "{:?}: {}", yaml, message);
+        }
+    }
+
+    #[test]
+    fn config_path_comes_from_either_flag_form() {
+        let args = |args: &[&str]| config_path_from_args(args.iter().map(|a| a.to_string()));
+        assert_eq!(args(&["--config", "/etc/a.yaml"]).unwrap(), Some(PathBuf::from("/etc/a.yaml")));
+        assert_eq!(args(&["-v", "--config=b.yaml"]).unwrap(), Some(PathBuf::from("b.yaml")));
+        assert_eq!(args(&[]).unwrap(), None);
+        assert!(args(&["--config"]).is_err());
+    }
+}
@@ -1,22 +1,40 @@
-use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
-use anyhow::Result;
+use sqlx::{sqlite::{SqliteConnectOptions, SqlitePoolOptions}, SqlitePool};
+use anyhow::{Context, Result};
+use std::str::FromStr;
 
 pub struct Database {
     pub pool: SqlitePool,
 }
 
 impl Database {
-    pub async fn new() -> Result<Self> {
-        let db_url = std::env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite:dockium.db".into());
-        
+    pub async fn new(url: &str) -> Result<Self> {
+        let options = SqliteConnectOptions::from_str(url)
+            .with_context(|| format!("Invalid database.url {:?}", url))?
+            .create_if_missing(true);
+
         let pool = SqlitePoolOptions::new()
             .max_connections(5)
-            .connect(&db_url)
-            .await?;
+            .connect_with(options)
+            .await
+            .with_context(|| format!("Failed to open database {}", url))?;
 
         Ok(Self { pool })
     }
 
+    /// A migrated in-memory database for tests. Each connection to
+    /// `:memory:` gets its own database, so the pool holds just one.
+    #[cfg(test)]
+    pub async fn in_memory() -> Self {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        let db = Self { pool };
+        db.run_migrations().await.unwrap();
+        db
+    }
+
     pub async fn run_migrations(&self) -> Result<()> {
         sqlx::query(
             "CREATE TABLE IF NOT EXISTS users (
@@ -30,6 +48,184 @@ impl Database {
         .execute(&self.pool)
         .await?;
 
+        self.add_column_if_missing("users", "disabled", "INTEGER NOT NULL DEFAULT 0").await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS sessions (
+                id TEXT PRIMARY KEY,
+                user_id TEXT NOT NULL,
+                refresh_token_hash TEXT NOT NULL,
+                expires_at INTEGER NOT NULL,
+                revoked INTEGER NOT NULL DEFAULT 0,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            )"
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS audit_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp DATETIME NOT NULL,
+                user_id TEXT,
+                username TEXT,
+                source_ip TEXT,
+                method TEXT NOT NULL,
+                endpoint TEXT NOT NULL,
+                action TEXT NOT NULL,
+                resource_id TEXT,
+                status INTEGER NOT NULL,
+                outcome TEXT NOT NULL
+            )"
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_audit_log_timestamp ON audit_log (timestamp)")
+            .execute(&self.pool)
+            .await?;
+
+        // Raw samples have resolution 0; rollups use the bucket size in
+        // seconds. The scope is the container name, empty for host metrics.
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS metrics (
+                resolution INTEGER NOT NULL,
+                metric TEXT NOT NULL,
+                scope TEXT NOT NULL,
+                timestamp INTEGER NOT NULL,
+                avg REAL NOT NULL,
+                min REAL NOT NULL,
+                max REAL NOT NULL,
+                samples INTEGER NOT NULL,
+                PRIMARY KEY (resolution, metric, scope, timestamp)
+            )"
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS alerts (
+                id TEXT PRIMARY KEY,
+                rule TEXT NOT NULL,
+                subject TEXT NOT NULL,
+                severity TEXT NOT NULL,
+                message TEXT NOT NULL,
+                value REAL,
+                state TEXT NOT NULL,
+                started_at DATETIME NOT NULL,
+                resolved_at DATETIME,
+                acknowledged_by TEXT,
+                acknowledged_at DATETIME
+            )"
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_alerts_started_at ON alerts (started_at)")
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS alert_silences (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                rule TEXT NOT NULL,
+                subject TEXT NOT NULL,
+                until DATETIME NOT NULL,
+                reason TEXT,
+                created_by TEXT,
+                created_at DATETIME NOT NULL
+            )"
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Config is kind-specific JSON, see services::notifiers
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS notification_channels (
+                id TEXT PRIMARY KEY,
+                name TEXT UNIQUE NOT NULL,
+                kind TEXT NOT NULL,
+                config TEXT NOT NULL,
+                enabled INTEGER NOT NULL DEFAULT 1,
+                created_at DATETIME NOT NULL,
+                last_delivered_at DATETIME,
+                last_error TEXT
+            )"
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // `token` is encrypted; `host` is the normalized registry host used
+        // to match image references.
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS registries (
+                id TEXT PRIMARY KEY,
+                url TEXT NOT NULL,
+                host TEXT UNIQUE NOT NULL,
+                username TEXT NOT NULL,
+                token TEXT NOT NULL,
+                created_at DATETIME NOT NULL,
+                updated_at DATETIME NOT NULL
+            )"
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Dry runs are not recorded
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS prune_runs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                started_at DATETIME NOT NULL,
+                finished_at DATETIME NOT NULL,
+                trigger TEXT NOT NULL,
+                username TEXT,
+                targets TEXT NOT NULL,
+                removed INTEGER NOT NULL,
+                failed INTEGER NOT NULL,
+                reclaimed_bytes INTEGER NOT NULL,
+                error TEXT
+            )"
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Latest result per running container, replaced on every check
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS image_update_checks (
+                container_id TEXT PRIMARY KEY,
+                container_name TEXT NOT NULL,
+                image TEXT NOT NULL,
+                local_digest TEXT,
+                remote_digest TEXT,
+                update_available INTEGER NOT NULL,
+                auto_update INTEGER NOT NULL,
+                checked_at DATETIME NOT NULL,
+                error TEXT
+            )"
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS image_updates (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                container_name TEXT NOT NULL,
+                image TEXT NOT NULL,
+                previous_container_id TEXT NOT NULL,
+                container_id TEXT,
+                previous_digest TEXT,
+                digest TEXT,
+                trigger TEXT NOT NULL,
+                username TEXT,
+                started_at DATETIME NOT NULL,
+                finished_at DATETIME NOT NULL,
+                status TEXT NOT NULL,
+                error TEXT
+            )"
+        )
+        .execute(&self.pool)
+        .await?;
+
         sqlx::query(
             "CREATE TABLE IF NOT EXISTS settings (
                 key TEXT PRIMARY KEY,
@@ -41,4 +237,20 @@ impl Database {
 
         Ok(())
     }
+
+    /// SQLite has no `ADD COLUMN IF NOT EXISTS`, so check the schema first.
+    async fn add_column_if_missing(&self, table: &str, column: &str, definition: &str) -> Result<()> {
+        let exists: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM pragma_table_info(?) WHERE name = ?")
+            .bind(table)
+            .bind(column)
+            .fetch_one(&self.pool)
+            .await?;
+
+        if exists == 0 {
+            sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+                .execute(&self.pool)
+                .await?;
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,132 @@
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Log file that is renamed to `<path>.1` once it would grow past
+/// `max_size`, shifting older files up to `<path>.<max_backups>`. With no
+/// backups kept, the file is truncated instead.
+pub struct RotatingFile {
+    path: PathBuf,
+    /// `None` appends without ever rotating.
+    max_size: Option<u64>,
+    max_backups: usize,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    /// Opens the file for appending, creating it and its directory if needed.
+    pub fn open(path: &Path, max_size: Option<u64>, max_backups: usize) -> io::Result<Self> {
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let file = append(path)?;
+        let size = file.metadata()?.len();
+        Ok(Self { path: path.to_path_buf(), max_size, max_backups, file, size })
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.max_backups == 0 {
+            self.file.set_len(0)?;
+        } else {
+            for n in (1..self.max_backups).rev() {
+                let from = backup(&self.path, n);
+                if from.exists() {
+                    fs::rename(&from, backup(&self.path, n + 1))?;
+                }
+            }
+            fs::rename(&self.path, backup(&self.path, 1))?;
+            self.file = append(&self.path)?;
+        }
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // A single oversized write still goes into a fresh file whole
+        if self.max_size.is_some_and(|max| self.size > 0 && self.size + buf.len() as u64 > max) {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn backup(path: &Path, n: usize) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(path: &Path) -> String {
+        fs::read_to_string(path).unwrap_or_default()
+    }
+
+    #[test]
+    fn rotates_by_size_and_keeps_max_backups() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs").join("dockium.log");
+        let mut file = RotatingFile::open(&path, Some(10), 2).unwrap();
+        for line in ["aaaa\n", "bbbb\n", "cccc\n", "dddd\n", "eeee\n", "ffff\n", "gggg\n"] {
+            file.write_all(line.as_bytes()).unwrap();
+        }
+
+        assert_eq!(read(&path), "gggg\n");
+        assert_eq!(read(&backup(&path, 1)), "eeee\nffff\n");
+        assert_eq!(read(&backup(&path, 2)), "cccc\ndddd\n");
+        assert!(!backup(&path, 3).exists());
+    }
+
+    #[test]
+    fn reopening_counts_what_is_already_there() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dockium.log");
+        RotatingFile::open(&path, Some(10), 1).unwrap().write_all(b"aaaaaaaa\n").unwrap();
+
+        let mut file = RotatingFile::open(&path, Some(10), 1).unwrap();
+        file.write_all(b"bbbb\n").unwrap();
+        assert_eq!(read(&path), "bbbb\n");
+        assert_eq!(read(&backup(&path, 1)), "aaaaaaaa\n");
+    }
+
+    #[test]
+    fn truncates_without_backups_and_never_rotates_when_disabled() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("truncated.log");
+        let mut file = RotatingFile::open(&path, Some(10), 0).unwrap();
+        file.write_all(b"aaaaaaaa\n").unwrap();
+        file.write_all(b"bbbb\n").unwrap();
+        assert_eq!(read(&path), "bbbb\n");
+        assert!(!backup(&path, 1).exists());
+
+        let path = dir.path().join("unbounded.log");
+        let mut file = RotatingFile::open(&path, None, 5).unwrap();
+        file.write_all(b"aaaaaaaa\n").unwrap();
+        file.write_all(b"bbbb\n").unwrap();
+        assert_eq!(read(&path), "aaaaaaaa\nbbbb\n");
+    }
+
+    #[test]
+    fn unusable_directories_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let blocker = dir.path().join("not-a-dir");
+        fs::write(&blocker, "").unwrap();
+        assert!(RotatingFile::open(&blocker.join("dockium.log"), Some(10), 1).is_err());
+    }
+}
@@ -1,7 +1,4 @@
-use axum::{
-    routing::{get, post},
-    Router,
-};
+use axum::{middleware, Router};
 use std::net::SocketAddr;
 use tower_http::cors::{Any, CorsLayer};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -9,15 +6,33 @@ use std::sync::Arc;
 use dotenvy::dotenv;
 
 mod api;
+mod config;
 mod db;
+mod log_file;
 mod models;
 mod services;
+mod tls;
 
 use crate::services::docker_service::DockerService;
 use crate::services::system_service::SystemService;
 use crate::services::compose_service::ComposeService;
 use crate::services::settings_service::SettingsService;
+use crate::services::audit_service::AuditService;
+use crate::services::auth_service::AuthService;
+use crate::services::user_service::UserService;
+use crate::services::metrics_service::MetricsService;
+use crate::services::alert_service::AlertService;
+use crate::services::notification_service::NotificationService;
+use crate::services::registry_service::RegistryService;
+use crate::services::build_service::BuildService;
+use crate::services::prune_service::PruneService;
+use crate::services::disk_usage_service::DiskUsageService;
+use crate::services::recreate_service::RecreateService;
+use crate::services::update_service::UpdateService;
+use crate::services::resource_service::ResourceService;
+use crate::config::{Config, LoggingConfig};
 use crate::db::Database;
+use crate::log_file::RotatingFile;
 
 #[derive(Clone)]
 pub struct AppState {
@@ -25,39 +40,119 @@ pub struct AppState {
     pub system: Arc<SystemService>,
     pub compose: Arc<ComposeService>,
     pub settings: Arc<SettingsService>,
+    pub auth: Arc<AuthService>,
+    pub users: Arc<UserService>,
+    pub audit: Arc<AuditService>,
+    pub metrics: Arc<MetricsService>,
+    pub alerts: Arc<AlertService>,
+    pub notifications: Arc<NotificationService>,
+    pub registries: Arc<RegistryService>,
+    pub builds: Arc<BuildService>,
+    pub prune: Arc<PruneService>,
+    pub disk_usage: Arc<DiskUsageService>,
+    pub recreate: Arc<RecreateService>,
+    pub updates: Arc<UpdateService>,
+    pub resources: Arc<ResourceService>,
     pub db: Arc<Database>,
+    pub config: Arc<Config>,
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     dotenv().ok();
 
-    // Initialize logging
-    tracing_subscriber::registry()
-        .with(tracing_subscriber::EnvFilter::new(
-            std::env::var("RUST_LOG").unwrap_or_else(|_| "dockium=debug,tower_http=debug".into()),
-        ))
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+    let config = Arc::new(Config::load()?);
+
+    // Initialize logging. The guard flushes the file writer on shutdown.
+    let _log_guard = init_logging(&config.logging)?;
 
     tracing::info!("Starting Dockium Backend");
+    match &config.source {
+        Some(path) => tracing::info!("Loaded configuration from {}", path.display()),
+        None => tracing::info!("No config file found, using defaults"),
+    }
 
     // Initialize Database
-    let db = Arc::new(Database::new().await?);
+    let db = Arc::new(Database::new(&config.database.url).await?);
     db.run_migrations().await?;
 
     // Initialize Services
-    let docker = Arc::new(DockerService::new()?);
+    let docker = Arc::new(DockerService::new(&config.docker)?);
     let system = Arc::new(SystemService::new());
-    let compose = Arc::new(ComposeService::new());
     let settings = Arc::new(SettingsService::new(db.clone()));
+    let jwt_secret = match &config.security.jwt_secret {
+        Some(secret) => secret.clone(),
+        None => AuthService::load_or_create_secret(&settings).await?,
+    };
+    let registries = Arc::new(RegistryService::new(db.clone(), &jwt_secret));
+    let compose = Arc::new(ComposeService::new(registries.clone()));
+    let builds = Arc::new(BuildService::new(registries.clone(), &config.docker, &config.database.data_dir()));
+    let recreate = Arc::new(RecreateService::new(docker.clone(), registries.clone()));
+    let resources = Arc::new(ResourceService::new(docker.clone(), system.clone()));
+    let auth = Arc::new(AuthService::new(db.clone(), &jwt_secret, &config.security));
+    let users = Arc::new(UserService::new(db.clone()));
+    let audit = Arc::new(AuditService::new(db.clone()));
+    let metrics = Arc::new(MetricsService::new(
+        db.clone(),
+        docker.clone(),
+        system.clone(),
+        config.monitoring.history.clone(),
+    ));
+    let notifications = Arc::new(NotificationService::new(db.clone(), &jwt_secret));
+    if let Err(e) = notifications.seal_plaintext_secrets().await {
+        tracing::warn!("Failed to encrypt stored notification channel secrets: {}", e);
+    }
+    let alerts = Arc::new(AlertService::new(
+        db.clone(),
+        docker.clone(),
+        system.clone(),
+        notifications.clone(),
+        config.monitoring.alerts.clone(),
+    ));
+    let prune = Arc::new(PruneService::new(db.clone(), docker.clone(), settings.clone(), &config.docker));
+    let updates = Arc::new(UpdateService::new(
+        db.clone(),
+        docker.clone(),
+        registries.clone(),
+        settings.clone(),
+        recreate.clone(),
+    ));
+    let disk_usage = Arc::new(DiskUsageService::new(
+        docker.clone(),
+        std::time::Duration::from_secs(config.monitoring.disk_usage_refresh_seconds),
+    ));
+
+    // Background tasks
+    tokio::spawn(docker.clone().watch_events());
+    tokio::spawn(prune.clone().run());
+    tokio::spawn(updates.clone().run());
+    if config.monitoring.history.enabled {
+        tokio::spawn(metrics.clone().run());
+    }
+    if config.monitoring.alerts.enabled {
+        tokio::spawn(alerts.clone().run());
+    }
 
     let state = AppState {
         docker,
         system,
         compose,
         settings,
+        auth,
+        users,
+        audit,
+        metrics,
+        alerts,
+        notifications,
+        registries,
+        builds,
+        prune,
+        disk_usage,
+        recreate,
+        updates,
+        resources,
         db,
+        config: config.clone(),
     };
 
     // Configure CORS
@@ -67,8 +162,8 @@ async fn main() -> anyhow::Result<()> {
         .allow_headers(Any);
 
     // Define Routes
-    let app = Router::new()
-        .nest("/api/auth", api::auth::routes())
+    // Everything except /api/auth requires a valid token
+    let protected = Router::new()
         .nest("/api/containers", api::containers::routes())
         .nest("/api/images", api::images::routes())
         .nest("/api/networks", api::networks::routes())
@@ -76,21 +171,76 @@ async fn main() -> anyhow::Result<()> {
         .nest("/api/system", api::system::routes())
         .nest("/api/compose", api::compose::routes())
         .nest("/api/settings", api::settings::routes())
+        .nest("/api/users", api::users::routes())
+        .nest("/api/audit", api::audit::routes())
+        .nest("/api/metrics", api::metrics::routes())
+        .nest("/api/alerts", api::alerts::routes())
+        .nest("/api/notifications", api::notifications::routes())
+        .nest("/api/events", api::events::routes())
+        .nest("/api/registries", api::registries::routes())
+        .nest("/api/updates", api::updates::routes())
+        .route_layer(middleware::from_fn_with_state(state.clone(), api::middleware::require_auth));
+
+    let app = Router::new()
+        .nest("/api/auth", api::auth::routes())
+        .merge(protected)
+        .route_layer(middleware::from_fn_with_state(state.clone(), api::middleware::record_audit))
         .layer(cors)
         .with_state(state);
 
     // Start Server
-    let host = std::env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string());
-    let port = std::env::var("PORT")
-        .unwrap_or_else(|_| "8080".to_string())
-        .parse::<u16>()
-        .unwrap_or(8080);
-    
-    let addr: SocketAddr = format!("{}:{}", host, port).parse()?;
+    let addr = config.server.addr();
+
+    if let Some(ssl) = &config.server.ssl {
+        let tls = tls::load(ssl).await?;
+
+        if let Some(http_port) = ssl.redirect_http_port {
+            let (host, https_port) = (config.server.host, config.server.port);
+            tokio::spawn(async move {
+                if let Err(e) = tls::serve_redirect(host, http_port, https_port).await {
+                    tracing::error!("HTTP redirect listener failed: {}", e);
+                }
+            });
+        }
+
+        tracing::info!("Listening on {} (HTTPS)", addr);
+        axum_server::bind_rustls(addr, tls)
+            .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+            .await?;
+        return Ok(());
+    }
+
     tracing::info!("Listening on {}", addr);
     
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
 
     Ok(())
 }
+
+/// Logs to stdout, and additionally to `logging.file_path` when configured.
+/// A log file that can't be opened, e.g. under /var/log without root, only
+/// costs the file output rather than failing startup.
+fn init_logging(config: &LoggingConfig) -> anyhow::Result<Option<tracing_appender::non_blocking::WorkerGuard>> {
+    let filter = tracing_subscriber::EnvFilter::try_new(&config.level)?;
+
+    let max_size = config.rotate.then_some(config.max_size_bytes);
+    let (file, file_error) = match &config.file_path {
+        None => (None, None),
+        Some(path) => match RotatingFile::open(path, max_size, config.max_backups) {
+            Ok(file) => (Some(file), None),
+            Err(e) => (None, Some(format!("Can't open log file {}, logging to stdout only: {}", path.display(), e))),
+        },
+    };
+    let (writer, guard) = file.map(tracing_appender::non_blocking).unzip();
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer())
+        .with(writer.map(|writer| tracing_subscriber::fmt::layer().with_ansi(false).with_writer(writer)))
+        .init();
+    if let Some(e) = file_error {
+        tracing::warn!("{}", e);
+    }
+    Ok(guard)
+}
@@ -7,10 +7,130 @@ pub struct User {
     pub role: String,
 }
 
+/// A row of the `users` table as exposed by the user management API.
+#[derive(Serialize)]
+pub struct UserAccount {
+    pub id: String,
+    pub username: String,
+    pub role: String,
+    pub disabled: bool,
+    pub created_at: chrono::NaiveDateTime,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
     pub username: String,
     pub role: String,
+    /// Server-side session the token belongs to; revoked on logout.
+    pub sid: String,
     pub exp: usize,
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Admin,
+    Operator,
+    Viewer,
+}
+
+/// Capabilities checked per route. Each role is granted a fixed set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    /// List and inspect resources, read logs and stats.
+    View,
+    /// Start, stop and restart containers, pull images, compose up/down.
+    Operate,
+    /// Create containers from a raw config and open shells inside them.
+    /// Either can mount the host filesystem or run privileged, so this is
+    /// root on the host and only admins have it.
+    HostAccess,
+    /// Remove containers, images, networks and volumes.
+    Remove,
+    ManageSettings,
+    ManageUsers,
+    ViewAuditLog,
+}
+
+impl Role {
+    pub fn parse(role: &str) -> Option<Self> {
+        match role {
+            "admin" => Some(Role::Admin),
+            "operator" => Some(Role::Operator),
+            "viewer" => Some(Role::Viewer),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Admin => "admin",
+            Role::Operator => "operator",
+            Role::Viewer => "viewer",
+        }
+    }
+
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        match self {
+            Role::Admin => true,
+            Role::Operator => matches!(permission, Permission::View | Permission::Operate),
+            Role::Viewer => permission == Permission::View,
+        }
+    }
+}
+
+impl Permission {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::View => "view",
+            Permission::Operate => "operate",
+            Permission::HostAccess => "host_access",
+            Permission::Remove => "remove",
+            Permission::ManageSettings => "manage_settings",
+            Permission::ManageUsers => "manage_users",
+            Permission::ViewAuditLog => "view_audit_log",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [Permission; 7] = [
+        Permission::View,
+        Permission::Operate,
+        Permission::HostAccess,
+        Permission::Remove,
+        Permission::ManageSettings,
+        Permission::ManageUsers,
+        Permission::ViewAuditLog,
+    ];
+
+    fn granted(role: Role) -> Vec<Permission> {
+        ALL.into_iter().filter(|p| role.has_permission(*p)).collect()
+    }
+
+    #[test]
+    fn each_role_has_a_fixed_set_of_permissions() {
+        assert_eq!(granted(Role::Admin), ALL);
+        assert_eq!(granted(Role::Operator), [Permission::View, Permission::Operate]);
+        assert_eq!(granted(Role::Viewer), [Permission::View]);
+    }
+
+    #[test]
+    fn host_access_is_admin_only() {
+        assert!(Role::Admin.has_permission(Permission::HostAccess));
+        assert!(!Role::Operator.has_permission(Permission::HostAccess));
+        assert!(!Role::Viewer.has_permission(Permission::HostAccess));
+    }
+
+    #[test]
+    fn roles_round_trip_through_their_names() {
+        for role in [Role::Admin, Role::Operator, Role::Viewer] {
+            assert_eq!(Role::parse(role.as_str()), Some(role));
+        }
+        assert_eq!(Role::parse("Admin"), None);
+        assert_eq!(Role::parse("root"), None);
+    }
+}
@@ -0,0 +1,849 @@
+use crate::config::AlertsConfig;
+use crate::db::Database;
+use crate::services::docker_service::DockerService;
+use crate::services::notification_service::NotificationService;
+use crate::services::notifiers::Notification;
+use crate::services::system_service::SystemService;
+use anyhow::Result;
+use bollard::service::{ContainerStateStatusEnum, HealthStatusEnum};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{QueryBuilder, Sqlite};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub const RULE_HOST_CPU: &str = "host_cpu";
+pub const RULE_HOST_MEMORY: &str = "host_memory";
+pub const RULE_HOST_DISK: &str = "host_disk";
+pub const RULE_CONTAINER_EXITED: &str = "container_exited";
+pub const RULE_RESTART_LOOP: &str = "container_restart_loop";
+pub const RULE_UNHEALTHY: &str = "container_unhealthy";
+
+/// Subject of host alerts; container alerts use the container name.
+const HOST_SUBJECT: &str = "host";
+
+/// Exit codes of containers stopped with SIGKILL or SIGTERM, e.g. by
+/// `docker stop`. These count as deliberate unless the container was
+/// OOM-killed.
+const STOP_EXIT_CODES: &[i64] = &[137, 143];
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct Alert {
+    pub id: String,
+    pub rule: String,
+    /// `host` or the container name.
+    pub subject: String,
+    /// `warning` or `critical`.
+    pub severity: String,
+    pub message: String,
+    /// Measured value when the alert fired, for threshold rules.
+    pub value: Option<f64>,
+    /// `firing` or `resolved`.
+    pub state: String,
+    pub started_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub acknowledged_by: Option<String>,
+    pub acknowledged_at: Option<DateTime<Utc>>,
+    /// An active silence covers this alert's rule and subject.
+    pub silenced: bool,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct Silence {
+    pub id: i64,
+    pub rule: String,
+    pub subject: String,
+    pub until: DateTime<Utc>,
+    pub reason: Option<String>,
+    pub created_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, Default)]
+pub struct AlertFilter {
+    pub state: Option<String>,
+    pub rule: Option<String>,
+    pub subject: Option<String>,
+}
+
+/// A rule that currently holds, before its for-duration is applied.
+struct Condition {
+    rule: &'static str,
+    subject: String,
+    severity: &'static str,
+    message: String,
+    value: Option<f64>,
+    for_duration: Duration,
+}
+
+struct Tracker {
+    /// When the condition was first seen holding.
+    since: Instant,
+    /// Set once the condition has held long enough to fire.
+    alert_id: Option<String>,
+}
+
+/// Host usage percentages at one evaluation.
+struct HostUsage {
+    cpu: f64,
+    memory: f64,
+    disk: f64,
+}
+
+/// A container from the listing, with details if it was inspected.
+struct ContainerSample {
+    name: String,
+    /// Docker's state, e.g. `running` or `exited`.
+    state: String,
+    details: Option<ContainerDetails>,
+}
+
+struct ContainerDetails {
+    restart_count: i64,
+    exit_code: i64,
+    oom_killed: bool,
+    unhealthy: bool,
+}
+
+/// Alerts to write after an evaluation.
+#[derive(Default)]
+struct Transitions {
+    /// Conditions that held long enough, with the id their alert gets.
+    fired: Vec<(String, Condition)>,
+    /// Alerts whose condition no longer holds.
+    resolved: Vec<String>,
+}
+
+/// State carried between evaluations.
+#[derive(Default)]
+struct Engine {
+    trackers: HashMap<(String, String), Tracker>,
+    /// Containers that were running at the previous evaluation.
+    running: HashSet<String>,
+    /// Containers that stopped unexpectedly, with the reason. Cleared once
+    /// they run again or are removed.
+    exited: HashMap<String, String>,
+    /// Restart count samples within the restart loop window.
+    restarts: HashMap<String, VecDeque<(Instant, i64)>>,
+}
+
+impl Engine {
+    /// Rebuilds the state of alerts left firing, given as `(id, rule,
+    /// subject, message)`.
+    fn restore(firing: Vec<(String, String, String, String)>, now: Instant) -> Self {
+        let mut engine = Engine::default();
+        for (id, rule, subject, message) in firing {
+            if rule == RULE_CONTAINER_EXITED {
+                engine.exited.insert(subject.clone(), message);
+            }
+            engine.trackers.insert((rule, subject), Tracker { since: now, alert_id: Some(id) });
+        }
+        engine
+    }
+
+    fn host_conditions(&self, config: &AlertsConfig, usage: &HostUsage) -> Vec<Condition> {
+        let checks = [
+            (RULE_HOST_CPU, "CPU", usage.cpu, config.cpu_percent),
+            (RULE_HOST_MEMORY, "memory", usage.memory, config.memory_percent),
+            (RULE_HOST_DISK, "disk", usage.disk, config.disk_percent),
+        ];
+
+        let mut conditions = Vec::new();
+        for (rule, label, value, threshold) in checks {
+            // Once breached, the value has to drop below the hysteresis band
+            // to clear, so usage hovering at the threshold doesn't flap
+            let breached = self.trackers.contains_key(&(rule.to_string(), HOST_SUBJECT.to_string()));
+            let limit = if breached { threshold - config.hysteresis_percent } else { threshold };
+            if value > limit {
+                conditions.push(Condition {
+                    rule,
+                    subject: HOST_SUBJECT.to_string(),
+                    severity: "warning",
+                    message: format!("Host {} usage is {:.1}% (threshold {}%)", label, value, threshold),
+                    value: Some(value),
+                    for_duration: Duration::from_secs(config.for_seconds),
+                });
+            }
+        }
+        conditions
+    }
+
+    /// Whether a listed container needs inspecting: it's running, or was
+    /// seen stopping since the last evaluation.
+    fn should_inspect(&self, name: &str, state: &str) -> bool {
+        match state {
+            "running" | "restarting" => true,
+            // Only containers seen stopping count; old exited containers
+            // don't raise alerts on startup
+            "exited" | "dead" => self.running.contains(name),
+            _ => false,
+        }
+    }
+
+    fn container_conditions(&mut self, config: &AlertsConfig, containers: Vec<ContainerSample>, now: Instant) -> Vec<Condition> {
+        let window = Duration::from_secs(config.restart_loop_window_seconds);
+        let mut present = HashSet::new();
+        let mut running = HashSet::new();
+        let mut conditions = Vec::new();
+        for container in containers {
+            let name = container.name;
+            present.insert(name.clone());
+            let is_running = matches!(container.state.as_str(), "running" | "restarting");
+            if is_running {
+                running.insert(name.clone());
+            }
+            // The container may have been removed since it was listed
+            let Some(details) = container.details else { continue };
+
+            if !is_running {
+                if details.oom_killed {
+                    self.exited.insert(name, "was killed after running out of memory".to_string());
+                } else if details.exit_code != 0 && !STOP_EXIT_CODES.contains(&details.exit_code) {
+                    self.exited.insert(name, format!("exited with code {}", details.exit_code));
+                }
+                continue;
+            }
+
+            let samples = self.restarts.entry(name.clone()).or_default();
+            samples.push_back((now, details.restart_count));
+            while samples.front().is_some_and(|(t, _)| now.duration_since(*t) > window) {
+                samples.pop_front();
+            }
+            let restarts = match (samples.front(), samples.back()) {
+                (Some((_, first)), Some((_, last))) => (last - first).max(0) as u64,
+                _ => 0,
+            };
+            // Stays firing until a full window passes without a restart
+            let looping = self.trackers.contains_key(&(RULE_RESTART_LOOP.to_string(), name.clone()));
+            if restarts >= config.restart_loop_count || (looping && restarts > 0) {
+                conditions.push(Condition {
+                    rule: RULE_RESTART_LOOP,
+                    subject: name.clone(),
+                    severity: "critical",
+                    message: format!(
+                        "Container {} restarted {} times in the last {}s",
+                        name, restarts, config.restart_loop_window_seconds
+                    ),
+                    value: Some(restarts as f64),
+                    for_duration: Duration::ZERO,
+                });
+            }
+
+            if details.unhealthy {
+                conditions.push(Condition {
+                    rule: RULE_UNHEALTHY,
+                    subject: name.clone(),
+                    severity: "warning",
+                    message: format!("Container {} is failing its healthcheck", name),
+                    value: None,
+                    for_duration: Duration::from_secs(config.for_seconds),
+                });
+            }
+        }
+
+        self.exited.retain(|name, _| present.contains(name) && !running.contains(name));
+        self.restarts.retain(|name, _| running.contains(name));
+        self.running = running;
+
+        for (name, reason) in &self.exited {
+            conditions.push(Condition {
+                rule: RULE_CONTAINER_EXITED,
+                subject: name.clone(),
+                severity: "critical",
+                message: format!("Container {} {}", name, reason),
+                value: None,
+                for_duration: Duration::ZERO,
+            });
+        }
+        conditions
+    }
+
+    /// Tracks the conditions holding now. Container alerts are left as they
+    /// are when `containers_evaluated` is false.
+    fn step(&mut self, conditions: Vec<Condition>, containers_evaluated: bool, now: Instant) -> Transitions {
+        let mut transitions = Transitions::default();
+        let mut holding = HashSet::new();
+        for condition in conditions {
+            let key = (condition.rule.to_string(), condition.subject.clone());
+            holding.insert(key.clone());
+            let tracker = self.trackers.entry(key).or_insert(Tracker { since: now, alert_id: None });
+            if tracker.alert_id.is_none() && now.duration_since(tracker.since) >= condition.for_duration {
+                let id = uuid::Uuid::new_v4().to_string();
+                tracker.alert_id = Some(id.clone());
+                transitions.fired.push((id, condition));
+            }
+        }
+
+        let cleared: Vec<(String, String)> = self
+            .trackers
+            .keys()
+            .filter(|key| !holding.contains(*key))
+            .filter(|(rule, _)| containers_evaluated || !rule.starts_with("container_"))
+            .cloned()
+            .collect();
+        for key in cleared {
+            if let Some(Tracker { alert_id: Some(id), .. }) = self.trackers.remove(&key) {
+                transitions.resolved.push(id);
+            }
+        }
+        transitions
+    }
+
+    /// Forgets that the condition fired, so it fires again next time.
+    fn unfire(&mut self, condition: &Condition) {
+        if let Some(tracker) = self.trackers.get_mut(&(condition.rule.to_string(), condition.subject.clone())) {
+            tracker.alert_id = None;
+        }
+    }
+}
+
+pub struct AlertService {
+    db: Arc<Database>,
+    docker: Arc<DockerService>,
+    system: Arc<SystemService>,
+    notifications: Arc<NotificationService>,
+    config: AlertsConfig,
+}
+
+impl AlertService {
+    pub fn new(
+        db: Arc<Database>,
+        docker: Arc<DockerService>,
+        system: Arc<SystemService>,
+        notifications: Arc<NotificationService>,
+        config: AlertsConfig,
+    ) -> Self {
+        Self { db, docker, system, notifications, config }
+    }
+
+    /// Evaluates every rule each `evaluation_interval_seconds`. Runs until
+    /// the process exits.
+    pub async fn run(self: Arc<Self>) {
+        let mut engine = match self.load_firing().await {
+            Ok(engine) => engine,
+            Err(e) => {
+                tracing::error!("Failed to load firing alerts, alerting disabled: {}", e);
+                return;
+            }
+        };
+
+        let mut interval = tokio::time::interval(Duration::from_secs(self.config.evaluation_interval_seconds));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.evaluate(&mut engine).await {
+                tracing::warn!("Failed to evaluate alerts: {}", e);
+            }
+        }
+    }
+
+    /// Picks up alerts left firing by a previous run so they resolve rather
+    /// than being raised a second time.
+    async fn load_firing(&self) -> Result<Engine> {
+        let rows = sqlx::query_as("SELECT id, rule, subject, message FROM alerts WHERE state = 'firing'")
+            .fetch_all(&self.db.pool)
+            .await?;
+        Ok(Engine::restore(rows, Instant::now()))
+    }
+
+    async fn evaluate(&self, engine: &mut Engine) -> Result<()> {
+        let stats = self.system.get_stats();
+        let usage = HostUsage {
+            cpu: stats.cpu_usage as f64,
+            memory: stats.memory_percent(),
+            disk: stats.disk_percent(),
+        };
+        let mut conditions = engine.host_conditions(&self.config, &usage);
+
+        // Without Docker, container alerts keep their last state rather
+        // than all resolving at once
+        let containers_evaluated = match self.sample_containers(engine).await {
+            Ok(containers) => {
+                conditions.extend(engine.container_conditions(&self.config, containers, Instant::now()));
+                true
+            }
+            Err(e) => {
+                tracing::debug!("Skipping container alert rules: {}", e);
+                false
+            }
+        };
+
+        let transitions = engine.step(conditions, containers_evaluated, Instant::now());
+        let mut fired = transitions.fired.into_iter();
+        while let Some((id, condition)) = fired.next() {
+            if let Err(e) = self.fire(&id, &condition).await {
+                // Whatever wasn't written fires at the next evaluation instead
+                for (_, condition) in std::iter::once((id, condition)).chain(fired) {
+                    engine.unfire(&condition);
+                }
+                return Err(e);
+            }
+        }
+        for id in transitions.resolved {
+            self.resolve(&id).await?;
+        }
+        Ok(())
+    }
+
+    /// Lists containers, inspecting those the engine needs details for.
+    async fn sample_containers(&self, engine: &Engine) -> Result<Vec<ContainerSample>> {
+        let containers = self.docker.list_containers(true).await?;
+
+        let mut samples: Vec<ContainerSample> = containers
+            .iter()
+            .filter_map(|container| {
+                let name = container.names.as_ref()?.first()?;
+                Some(ContainerSample {
+                    name: name.trim_start_matches('/').to_string(),
+                    state: container.state.clone().unwrap_or_default(),
+                    details: None,
+                })
+            })
+            .collect();
+
+        let to_inspect: Vec<usize> = (0..samples.len())
+            .filter(|i| engine.should_inspect(&samples[*i].name, &samples[*i].state))
+            .collect();
+        let inspected =
+            futures::future::join_all(to_inspect.iter().map(|i| self.docker.inspect_container(&samples[*i].name))).await;
+
+        for (i, info) in to_inspect.into_iter().zip(inspected) {
+            let Ok(info) = info else { continue };
+            let state = info.state.unwrap_or_default();
+            samples[i].details = Some(ContainerDetails {
+                restart_count: info.restart_count.unwrap_or(0),
+                exit_code: state.exit_code.unwrap_or(0),
+                oom_killed: state.oom_killed == Some(true),
+                unhealthy: state.status == Some(ContainerStateStatusEnum::RUNNING)
+                    && state.health.and_then(|h| h.status) == Some(HealthStatusEnum::UNHEALTHY),
+            });
+        }
+        Ok(samples)
+    }
+
+    async fn fire(&self, id: &str, condition: &Condition) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO alerts (id, rule, subject, severity, message, value, state, started_at)
+             VALUES (?, ?, ?, ?, ?, ?, 'firing', ?)",
+        )
+        .bind(id)
+        .bind(condition.rule)
+        .bind(&condition.subject)
+        .bind(condition.severity)
+        .bind(&condition.message)
+        .bind(condition.value)
+        .bind(Utc::now())
+        .execute(&self.db.pool)
+        .await?;
+
+        tracing::warn!("Alert firing: {}", condition.message);
+        self.send_notification(id, "firing", condition.rule, &condition.subject, condition.severity, &condition.message)
+            .await;
+        Ok(())
+    }
+
+    async fn resolve(&self, id: &str) -> Result<()> {
+        sqlx::query("UPDATE alerts SET state = 'resolved', resolved_at = ? WHERE id = ?")
+            .bind(Utc::now())
+            .bind(id)
+            .execute(&self.db.pool)
+            .await?;
+
+        let (rule, subject, severity, message): (String, String, String, String) =
+            sqlx::query_as("SELECT rule, subject, severity, message FROM alerts WHERE id = ?")
+                .bind(id)
+                .fetch_one(&self.db.pool)
+                .await?;
+
+        tracing::info!("Alert resolved: {}", message);
+        self.send_notification(id, "resolved", &rule, &subject, &severity, &message).await;
+        Ok(())
+    }
+
+    /// Hands the alert to the notification channels unless it's silenced.
+    async fn send_notification(&self, id: &str, event: &str, rule: &str, subject: &str, severity: &str, message: &str) {
+        match self.is_silenced(rule, subject).await {
+            Ok(false) => {}
+            Ok(true) => return,
+            Err(e) => {
+                tracing::warn!("Failed to check alert silences: {}", e);
+                return;
+            }
+        }
+
+        let title = match event {
+            "resolved" => format!("Resolved: {}", message),
+            _ => format!("[{}] {}", severity.to_uppercase(), message),
+        };
+        self.notifications.notify(Notification {
+            event: event.to_string(),
+            severity: severity.to_string(),
+            title,
+            message: message.to_string(),
+            alert_id: Some(id.to_string()),
+            rule: Some(rule.to_string()),
+            subject: Some(subject.to_string()),
+            timestamp: Utc::now(),
+        });
+    }
+
+    async fn is_silenced(&self, rule: &str, subject: &str) -> Result<bool> {
+        let silenced = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM alert_silences WHERE rule = ? AND subject = ? AND until > ?)",
+        )
+        .bind(rule)
+        .bind(subject)
+        .bind(Utc::now())
+        .fetch_one(&self.db.pool)
+        .await?;
+        Ok(silenced)
+    }
+
+    /// Returns one page of matching alerts, newest first, and the total
+    /// number of matches.
+    pub async fn list(&self, filter: &AlertFilter, limit: i64, offset: i64) -> Result<(Vec<Alert>, i64)> {
+        let mut count = QueryBuilder::new("SELECT COUNT(*) FROM alerts a");
+        push_filter(&mut count, filter);
+        let total: i64 = count.build_query_scalar().fetch_one(&self.db.pool).await?;
+
+        let mut select = select_alerts();
+        push_filter(&mut select, filter);
+        select.push(" ORDER BY a.started_at DESC LIMIT ").push_bind(limit);
+        select.push(" OFFSET ").push_bind(offset);
+        let alerts = select.build_query_as().fetch_all(&self.db.pool).await?;
+
+        Ok((alerts, total))
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<Alert>> {
+        let mut select = select_alerts();
+        select.push(" WHERE a.id = ").push_bind(id.to_string());
+        Ok(select.build_query_as().fetch_optional(&self.db.pool).await?)
+    }
+
+    pub async fn acknowledge(&self, id: &str, username: &str) -> Result<Option<Alert>> {
+        sqlx::query("UPDATE alerts SET acknowledged_by = ?, acknowledged_at = ? WHERE id = ?")
+            .bind(username)
+            .bind(Utc::now())
+            .bind(id)
+            .execute(&self.db.pool)
+            .await?;
+        self.get(id).await
+    }
+
+    /// Silences the alert's rule and subject, covering this alert and any
+    /// that fire for the same thing before the silence ends.
+    pub async fn silence(&self, id: &str, duration: chrono::Duration, reason: Option<&str>, username: &str) -> Result<Option<Silence>> {
+        let Some(alert) = self.get(id).await? else {
+            return Ok(None);
+        };
+        let now = Utc::now();
+
+        sqlx::query("DELETE FROM alert_silences WHERE until <= ?")
+            .bind(now)
+            .execute(&self.db.pool)
+            .await?;
+
+        let silence = sqlx::query_as(
+            "INSERT INTO alert_silences (rule, subject, until, reason, created_by, created_at)
+             VALUES (?, ?, ?, ?, ?, ?) RETURNING *",
+        )
+        .bind(&alert.rule)
+        .bind(&alert.subject)
+        .bind(now + duration)
+        .bind(reason)
+        .bind(username)
+        .bind(now)
+        .fetch_one(&self.db.pool)
+        .await?;
+        Ok(Some(silence))
+    }
+
+    /// Ends every silence covering the alert. Returns `None` if the alert
+    /// doesn't exist.
+    pub async fn unsilence(&self, id: &str) -> Result<Option<Alert>> {
+        let Some(alert) = self.get(id).await? else {
+            return Ok(None);
+        };
+        sqlx::query("DELETE FROM alert_silences WHERE rule = ? AND subject = ?")
+            .bind(&alert.rule)
+            .bind(&alert.subject)
+            .execute(&self.db.pool)
+            .await?;
+        self.get(id).await
+    }
+
+    pub async fn active_silences(&self) -> Result<Vec<Silence>> {
+        let silences = sqlx::query_as("SELECT * FROM alert_silences WHERE until > ? ORDER BY until")
+            .bind(Utc::now())
+            .fetch_all(&self.db.pool)
+            .await?;
+        Ok(silences)
+    }
+}
+
+fn select_alerts() -> QueryBuilder<'static, Sqlite> {
+    let mut select = QueryBuilder::new(
+        "SELECT a.*, EXISTS(SELECT 1 FROM alert_silences s WHERE s.rule = a.rule AND s.subject = a.subject AND s.until > ",
+    );
+    select.push_bind(Utc::now()).push(") AS silenced FROM alerts a");
+    select
+}
+
+fn push_filter(builder: &mut QueryBuilder<'_, Sqlite>, filter: &AlertFilter) {
+    let mut clause = " WHERE ";
+    let mut next = |builder: &mut QueryBuilder<'_, Sqlite>| {
+        builder.push(clause);
+        clause = " AND ";
+    };
+
+    if let Some(state) = &filter.state {
+        next(builder);
+        builder.push("a.state = ").push_bind(state.clone());
+    }
+    if let Some(rule) = &filter.rule {
+        next(builder);
+        builder.push("a.rule = ").push_bind(rule.clone());
+    }
+    if let Some(subject) = &filter.subject {
+        next(builder);
+        builder.push("a.subject = ").push_bind(subject.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DockerConfig;
+
+    fn config() -> AlertsConfig {
+        AlertsConfig {
+            enabled: true,
+            evaluation_interval_seconds: 15,
+            for_seconds: 60,
+            hysteresis_percent: 5.0,
+            cpu_percent: 90.0,
+            memory_percent: 90.0,
+            disk_percent: 90.0,
+            restart_loop_count: 3,
+            restart_loop_window_seconds: 300,
+        }
+    }
+
+    fn cpu(cpu: f64) -> HostUsage {
+        HostUsage { cpu, memory: 10.0, disk: 10.0 }
+    }
+
+    fn running(name: &str, restart_count: i64) -> ContainerSample {
+        ContainerSample {
+            name: name.to_string(),
+            state: "running".to_string(),
+            details: Some(ContainerDetails { restart_count, exit_code: 0, oom_killed: false, unhealthy: false }),
+        }
+    }
+
+    fn exited(name: &str, exit_code: i64, oom_killed: bool) -> ContainerSample {
+        ContainerSample {
+            name: name.to_string(),
+            state: "exited".to_string(),
+            details: Some(ContainerDetails { restart_count: 0, exit_code, oom_killed, unhealthy: false }),
+        }
+    }
+
+    fn fired(transitions: &Transitions) -> Vec<(&str, &str)> {
+        transitions.fired.iter().map(|(_, c)| (c.rule, c.subject.as_str())).collect()
+    }
+
+    /// One evaluation with host usage only, `secs` after `start`.
+    fn host_step(engine: &mut Engine, start: Instant, secs: u64, usage: f64) -> Transitions {
+        let conditions = engine.host_conditions(&config(), &cpu(usage));
+        engine.step(conditions, false, start + Duration::from_secs(secs))
+    }
+
+    /// One evaluation with containers only, `secs` after `start`.
+    fn container_step(engine: &mut Engine, start: Instant, secs: u64, containers: Vec<ContainerSample>) -> Transitions {
+        let now = start + Duration::from_secs(secs);
+        let containers = containers
+            .into_iter()
+            .map(|mut c| {
+                if !engine.should_inspect(&c.name, &c.state) {
+                    c.details = None;
+                }
+                c
+            })
+            .collect();
+        let conditions = engine.container_conditions(&config(), containers, now);
+        engine.step(conditions, true, now)
+    }
+
+    #[test]
+    fn threshold_alerts_fire_once_held_for_the_duration() {
+        let start = Instant::now();
+        let mut engine = Engine::default();
+
+        assert!(host_step(&mut engine, start, 0, 95.0).fired.is_empty());
+        assert!(host_step(&mut engine, start, 45, 95.0).fired.is_empty());
+        let transitions = host_step(&mut engine, start, 60, 96.0);
+        assert_eq!(fired(&transitions), [(RULE_HOST_CPU, HOST_SUBJECT)]);
+        assert_eq!(transitions.fired[0].1.value, Some(96.0));
+        assert!(host_step(&mut engine, start, 75, 96.0).fired.is_empty());
+
+        // A breach that clears early starts over
+        let mut engine = Engine::default();
+        host_step(&mut engine, start, 0, 95.0);
+        let transitions = host_step(&mut engine, start, 30, 50.0);
+        assert!(transitions.fired.is_empty() && transitions.resolved.is_empty());
+        assert!(host_step(&mut engine, start, 60, 95.0).fired.is_empty());
+        assert_eq!(host_step(&mut engine, start, 120, 95.0).fired.len(), 1);
+    }
+
+    #[test]
+    fn threshold_alerts_resolve_only_below_the_hysteresis_band() {
+        let start = Instant::now();
+        let mut engine = Engine::default();
+        host_step(&mut engine, start, 0, 95.0);
+        let id = host_step(&mut engine, start, 60, 95.0).fired[0].0.clone();
+
+        // Below the threshold but inside the band
+        assert!(host_step(&mut engine, start, 75, 88.0).resolved.is_empty());
+        assert!(host_step(&mut engine, start, 90, 85.5).resolved.is_empty());
+        assert_eq!(host_step(&mut engine, start, 105, 84.0).resolved, [id]);
+
+        // Back inside the band without a breach doesn't count
+        assert!(engine.host_conditions(&config(), &cpu(88.0)).is_empty());
+    }
+
+    #[test]
+    fn restart_loops_count_restarts_within_the_window() {
+        let start = Instant::now();
+        let mut engine = Engine::default();
+        for (secs, restarts) in [(0, 0), (60, 1), (120, 2)] {
+            assert!(container_step(&mut engine, start, secs, vec![running("web", restarts)]).fired.is_empty());
+        }
+        let transitions = container_step(&mut engine, start, 180, vec![running("web", 3)]);
+        assert_eq!(fired(&transitions), [(RULE_RESTART_LOOP, "web")]);
+        assert_eq!(transitions.fired[0].1.value, Some(3.0));
+        let id = transitions.fired[0].0.clone();
+
+        // Fewer restarts than the count keep it firing until the window
+        // holds none
+        for secs in [300, 420] {
+            assert!(container_step(&mut engine, start, secs, vec![running("web", 3)]).resolved.is_empty());
+        }
+        assert_eq!(container_step(&mut engine, start, 500, vec![running("web", 3)]).resolved, [id]);
+
+        // The same number of restarts spread wider than the window
+        let mut engine = Engine::default();
+        for (secs, restarts) in [(0, 0), (200, 1), (400, 2), (600, 3), (800, 4)] {
+            assert!(container_step(&mut engine, start, secs, vec![running("db", restarts)]).fired.is_empty());
+        }
+    }
+
+    #[test]
+    fn unexpected_exits_fire_until_the_container_runs_again() {
+        let start = Instant::now();
+        let mut engine = Engine::default();
+
+        // Already stopped at startup
+        container_step(&mut engine, start, 0, vec![exited("old", 1, false), running("web", 0), running("db", 0), running("cache", 0)]);
+        let transitions = container_step(
+            &mut engine,
+            start,
+            15,
+            vec![exited("old", 1, false), exited("web", 2, false), exited("db", 137, false), exited("cache", 137, true)],
+        );
+        let mut alerts: Vec<(&str, &str)> = fired(&transitions);
+        alerts.sort();
+        assert_eq!(alerts, [(RULE_CONTAINER_EXITED, "cache"), (RULE_CONTAINER_EXITED, "web")]);
+        let messages: HashMap<&str, &str> =
+            transitions.fired.iter().map(|(_, c)| (c.subject.as_str(), c.message.as_str())).collect();
+        assert_eq!(messages["web"], "Container web exited with code 2");
+        assert_eq!(messages["cache"], "Container cache was killed after running out of memory");
+
+        // Still exited, and no longer inspected
+        let transitions = container_step(&mut engine, start, 30, vec![exited("web", 2, false), exited("cache", 137, true)]);
+        assert!(transitions.fired.is_empty() && transitions.resolved.is_empty());
+
+        let transitions = container_step(&mut engine, start, 45, vec![running("web", 1)]);
+        assert_eq!(transitions.resolved.len(), 2);
+        assert!(engine.exited.is_empty());
+    }
+
+    #[test]
+    fn restored_alerts_resolve_instead_of_firing_again() {
+        let start = Instant::now();
+        let firing = |id: &str, rule: &str, subject: &str, message: &str| {
+            (id.to_string(), rule.to_string(), subject.to_string(), message.to_string())
+        };
+        let mut engine = Engine::restore(
+            vec![
+                firing("a1", RULE_HOST_CPU, HOST_SUBJECT, "Host CPU usage is 95.0% (threshold 90%)"),
+                firing("a2", RULE_CONTAINER_EXITED, "web", "exited with code 1"),
+                firing("a3", RULE_CONTAINER_EXITED, "gone", "exited with code 1"),
+            ],
+            start,
+        );
+
+        // Still breached and inside the band: nothing changes
+        let transitions = host_step(&mut engine, start, 15, 88.0);
+        assert!(transitions.fired.is_empty() && transitions.resolved.is_empty());
+
+        // Container alerts stay as they are until Docker answers
+        assert_eq!(host_step(&mut engine, start, 30, 50.0).resolved, ["a1"]);
+
+        let transitions = container_step(&mut engine, start, 45, vec![exited("web", 1, false)]);
+        assert!(transitions.fired.is_empty());
+        assert_eq!(transitions.resolved, ["a3"]);
+    }
+
+    async fn service() -> AlertService {
+        let docker = DockerConfig {
+            socket_path: String::new(),
+            remote_host: Some("http://127.0.0.1:1".to_string()),
+            build_context_roots: Vec::new(),
+            max_build_context_bytes: 1,
+        };
+        let db = Arc::new(Database::in_memory().await);
+        AlertService::new(
+            db.clone(),
+            Arc::new(DockerService::new(&docker).unwrap()),
+            Arc::new(SystemService::new()),
+            Arc::new(NotificationService::new(db, "0123456789abcdef0123456789abcdef")),
+            config(),
+        )
+    }
+
+    #[tokio::test]
+    async fn silences_cover_the_rule_and_subject_until_they_end() {
+        let service = service().await;
+        let start = Instant::now();
+        let mut engine = Engine::default();
+        container_step(&mut engine, start, 0, vec![running("web", 0), running("db", 0)]);
+        let transitions = container_step(&mut engine, start, 15, vec![exited("web", 1, false), exited("db", 1, false)]);
+        for (id, condition) in &transitions.fired {
+            service.fire(id, condition).await.unwrap();
+        }
+        let id = |subject: &str| transitions.fired.iter().find(|(_, c)| c.subject == subject).unwrap().0.clone();
+
+        let silence = service.silence(&id("web"), chrono::Duration::hours(1), Some("deploying"), "admin").await.unwrap().unwrap();
+        assert_eq!((silence.rule.as_str(), silence.subject.as_str()), (RULE_CONTAINER_EXITED, "web"));
+        assert!(service.get(&id("web")).await.unwrap().unwrap().silenced);
+        assert!(!service.get(&id("db")).await.unwrap().unwrap().silenced);
+        assert!(service.is_silenced(RULE_CONTAINER_EXITED, "web").await.unwrap());
+        assert!(!service.is_silenced(RULE_RESTART_LOOP, "web").await.unwrap());
+
+        assert!(!service.unsilence(&id("web")).await.unwrap().unwrap().silenced);
+        assert!(service.active_silences().await.unwrap().is_empty());
+
+        // Expired silences don't count and are cleared by the next one
+        service.silence(&id("db"), chrono::Duration::seconds(-1), None, "admin").await.unwrap();
+        assert!(!service.is_silenced(RULE_CONTAINER_EXITED, "db").await.unwrap());
+        service.silence(&id("web"), chrono::Duration::hours(1), None, "admin").await.unwrap();
+        assert_eq!(service.active_silences().await.unwrap().len(), 1);
+        let stored: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM alert_silences").fetch_one(&service.db.pool).await.unwrap();
+        assert_eq!(stored, 1);
+
+        assert!(service.silence("missing", chrono::Duration::hours(1), None, "admin").await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,205 @@
+use crate::db::Database;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{QueryBuilder, Sqlite};
+use std::sync::Arc;
+
+/// Upper bound on rows returned by a single export.
+pub const MAX_EXPORT_ROWS: i64 = 100_000;
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub user_id: Option<String>,
+    pub username: Option<String>,
+    pub source_ip: Option<String>,
+    pub method: String,
+    pub endpoint: String,
+    /// `<resource>.<verb>`, e.g. `containers.remove` or `auth.login`.
+    pub action: String,
+    pub resource_id: Option<String>,
+    pub status: i64,
+    /// `success`, `denied` (401/403) or `failure`.
+    pub outcome: String,
+}
+
+/// A row to be recorded; the id and timestamp are assigned on insert.
+pub struct NewAuditEntry {
+    pub user_id: Option<String>,
+    pub username: Option<String>,
+    pub source_ip: Option<String>,
+    pub method: String,
+    pub endpoint: String,
+    pub action: String,
+    pub resource_id: Option<String>,
+    pub status: u16,
+}
+
+#[derive(Deserialize, Default)]
+pub struct AuditFilter {
+    pub username: Option<String>,
+    /// Matches the action exactly or as a prefix, so `containers` matches
+    /// every container action.
+    pub action: Option<String>,
+    pub resource_id: Option<String>,
+    pub outcome: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+pub struct AuditService {
+    db: Arc<Database>,
+}
+
+impl AuditService {
+    pub fn new(db: Arc<Database>) -> Self {
+        Self { db }
+    }
+
+    pub async fn record(&self, entry: NewAuditEntry) -> Result<()> {
+        let outcome = match entry.status {
+            401 | 403 => "denied",
+            s if s < 400 => "success",
+            _ => "failure",
+        };
+
+        sqlx::query(
+            "INSERT INTO audit_log (timestamp, user_id, username, source_ip, method, endpoint, action, resource_id, status, outcome)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(Utc::now())
+        .bind(&entry.user_id)
+        .bind(&entry.username)
+        .bind(&entry.source_ip)
+        .bind(&entry.method)
+        .bind(&entry.endpoint)
+        .bind(&entry.action)
+        .bind(&entry.resource_id)
+        .bind(entry.status as i64)
+        .bind(outcome)
+        .execute(&self.db.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns one page of matching entries, newest first, and the total
+    /// number of matches.
+    pub async fn query(&self, filter: &AuditFilter, limit: i64, offset: i64) -> Result<(Vec<AuditEntry>, i64)> {
+        let mut count = QueryBuilder::new("SELECT COUNT(*) FROM audit_log");
+        push_filter(&mut count, filter);
+        let total: i64 = count.build_query_scalar().fetch_one(&self.db.pool).await?;
+
+        let mut select = QueryBuilder::new("SELECT * FROM audit_log");
+        push_filter(&mut select, filter);
+        select.push(" ORDER BY id DESC LIMIT ").push_bind(limit);
+        select.push(" OFFSET ").push_bind(offset);
+        let entries = select.build_query_as().fetch_all(&self.db.pool).await?;
+
+        Ok((entries, total))
+    }
+}
+
+fn push_filter(builder: &mut QueryBuilder<'_, Sqlite>, filter: &AuditFilter) {
+    let mut clause = " WHERE ";
+    let mut next = |builder: &mut QueryBuilder<'_, Sqlite>| {
+        builder.push(clause);
+        clause = " AND ";
+    };
+
+    if let Some(username) = &filter.username {
+        next(builder);
+        builder.push("username = ").push_bind(username.clone());
+    }
+    if let Some(action) = &filter.action {
+        next(builder);
+        builder
+            .push("(action = ")
+            .push_bind(action.clone())
+            .push(" OR action LIKE ")
+            .push_bind(format!("{}.%", escape_like(action)))
+            .push(" ESCAPE '\\')");
+    }
+    if let Some(resource_id) = &filter.resource_id {
+        next(builder);
+        builder.push("resource_id = ").push_bind(resource_id.clone());
+    }
+    if let Some(outcome) = &filter.outcome {
+        next(builder);
+        builder.push("outcome = ").push_bind(outcome.clone());
+    }
+    if let Some(since) = filter.since {
+        next(builder);
+        builder.push("timestamp >= ").push_bind(since);
+    }
+    if let Some(until) = filter.until {
+        next(builder);
+        builder.push("timestamp <= ").push_bind(until);
+    }
+}
+
+/// Escapes `LIKE` wildcards for use with `ESCAPE '\'`. The escape
+/// character goes first, so the escapes added after it aren't doubled.
+fn escape_like(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn service_with_actions(actions: &[&str]) -> AuditService {
+        let service = AuditService::new(Arc::new(Database::in_memory().await));
+        for action in actions {
+            service
+                .record(NewAuditEntry {
+                    user_id: None,
+                    username: None,
+                    source_ip: None,
+                    method: "POST".into(),
+                    endpoint: "/api/test".into(),
+                    action: action.to_string(),
+                    resource_id: None,
+                    status: 200,
+                })
+                .await
+                .unwrap();
+        }
+        service
+    }
+
+    async fn matching(service: &AuditService, action: &str) -> Vec<String> {
+        let filter = AuditFilter {
+            action: Some(action.to_string()),
+            ..Default::default()
+        };
+        let (entries, _) = service.query(&filter, 100, 0).await.unwrap();
+        let mut actions: Vec<String> = entries.into_iter().map(|e| e.action).collect();
+        actions.sort();
+        actions
+    }
+
+    #[test]
+    fn escape_like_escapes_the_escape_character_first() {
+        assert_eq!(escape_like(r"a\b"), r"a\\b");
+        assert_eq!(escape_like("50%_off"), r"50\%\_off");
+        assert_eq!(escape_like(r"\%"), r"\\\%");
+    }
+
+    #[tokio::test]
+    async fn action_filter_matches_exactly_or_as_prefix() {
+        let service = service_with_actions(&["containers.remove", "containers.start", "containers", "images.pull"]).await;
+        assert_eq!(matching(&service, "containers").await, ["containers", "containers.remove", "containers.start"]);
+        assert_eq!(matching(&service, "containers.start").await, ["containers.start"]);
+    }
+
+    #[tokio::test]
+    async fn action_filter_treats_wildcards_literally() {
+        let service = service_with_actions(&["a_b.x", "axb.x", r"a\b.x", "ab.x", "50%.x", "500.x"]).await;
+        assert_eq!(matching(&service, "a_b").await, ["a_b.x"]);
+        assert_eq!(matching(&service, r"a\b").await, [r"a\b.x"]);
+        assert_eq!(matching(&service, "50%").await, ["50%.x"]);
+    }
+}
@@ -0,0 +1,310 @@
+use crate::config::SecurityConfig;
+use crate::db::Database;
+use crate::models::{Claims, User};
+use crate::services::settings_service::SettingsService;
+use anyhow::Result;
+use chrono::Utc;
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
+
+/// Settings key holding the generated signing secret when none is configured.
+const JWT_SECRET_SETTING: &str = "internal.jwt_secret";
+
+/// Tokens handed out on login. The refresh token identifies a server-side
+/// session; revoking the session invalidates both tokens.
+pub struct TokenPair {
+    pub token: String,
+    pub refresh_token: String,
+    /// Access token lifetime in seconds.
+    pub expires_in: u64,
+}
+
+pub struct AuthService {
+    db: Arc<Database>,
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    session_timeout: u64,
+    refresh_token_lifetime: u64,
+}
+
+impl AuthService {
+    pub fn new(db: Arc<Database>, secret: &str, config: &SecurityConfig) -> Self {
+        Self {
+            db,
+            encoding_key: EncodingKey::from_secret(secret.as_bytes()),
+            decoding_key: DecodingKey::from_secret(secret.as_bytes()),
+            session_timeout: config.session_timeout,
+            refresh_token_lifetime: config.refresh_token_lifetime,
+        }
+    }
+
+    /// Returns the persisted signing secret, generating one on first boot.
+    pub async fn load_or_create_secret(settings: &SettingsService) -> Result<String> {
+        if let Some(secret) = settings.get_setting(JWT_SECRET_SETTING).await? {
+            return Ok(secret);
+        }
+        let secret = random_hex(64);
+        settings.set_setting(JWT_SECRET_SETTING, &secret).await?;
+        tracing::info!("Generated a new JWT signing secret");
+        Ok(secret)
+    }
+
+    /// Creates a session for the user and issues its first access token.
+    pub async fn start_session(&self, user: &User) -> Result<TokenPair> {
+        let session_id = uuid::Uuid::new_v4().to_string();
+        let secret = random_hex(32);
+        let now = Utc::now().timestamp();
+
+        // Opportunistic cleanup so the table doesn't grow without bound
+        sqlx::query("DELETE FROM sessions WHERE expires_at < ?")
+            .bind(now)
+            .execute(&self.db.pool)
+            .await?;
+
+        sqlx::query("INSERT INTO sessions (id, user_id, refresh_token_hash, expires_at) VALUES (?, ?, ?, ?)")
+            .bind(&session_id)
+            .bind(&user.id)
+            .bind(hash_token(&secret))
+            .bind(now + self.refresh_token_lifetime as i64)
+            .execute(&self.db.pool)
+            .await?;
+
+        Ok(TokenPair {
+            token: self.issue_token(user, &session_id)?,
+            refresh_token: format!("{}.{}", session_id, secret),
+            expires_in: self.session_timeout,
+        })
+    }
+
+    /// Exchanges a refresh token for a new access and refresh token and
+    /// extends the session. The old refresh token stops working. Returns
+    /// `None` for unknown, expired or revoked sessions.
+    pub async fn refresh(&self, refresh_token: &str) -> Result<Option<(User, TokenPair)>> {
+        let Some((session_id, secret)) = refresh_token.split_once('.') else {
+            return Ok(None);
+        };
+        let now = Utc::now().timestamp();
+
+        let row: Option<(String, String, String, String)> = sqlx::query_as(
+            "SELECT s.refresh_token_hash, u.id, u.username, u.role
+             FROM sessions s JOIN users u ON u.id = s.user_id
+             WHERE s.id = ? AND s.revoked = 0 AND s.expires_at >= ? AND u.disabled = 0",
+        )
+        .bind(session_id)
+        .bind(now)
+        .fetch_optional(&self.db.pool)
+        .await?;
+
+        let Some((stored_hash, id, username, role)) = row else {
+            return Ok(None);
+        };
+        if !bool::from(stored_hash.as_bytes().ct_eq(hash_token(secret).as_bytes())) {
+            return Ok(None);
+        }
+
+        // Matching on the old hash means only one of two concurrent
+        // refreshes with the same token succeeds
+        let next_secret = random_hex(32);
+        let rotated = sqlx::query(
+            "UPDATE sessions SET refresh_token_hash = ?, expires_at = ? WHERE id = ? AND refresh_token_hash = ?",
+        )
+        .bind(hash_token(&next_secret))
+        .bind(now + self.refresh_token_lifetime as i64)
+        .bind(session_id)
+        .bind(&stored_hash)
+        .execute(&self.db.pool)
+        .await?;
+        if rotated.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        let user = User { id, username, role };
+        let tokens = TokenPair {
+            token: self.issue_token(&user, session_id)?,
+            refresh_token: format!("{}.{}", session_id, next_secret),
+            expires_in: self.session_timeout,
+        };
+        Ok(Some((user, tokens)))
+    }
+
+    pub async fn revoke_session(&self, session_id: &str) -> Result<()> {
+        sqlx::query("UPDATE sessions SET revoked = 1 WHERE id = ?")
+            .bind(session_id)
+            .execute(&self.db.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub fn session_timeout(&self) -> u64 {
+        self.session_timeout
+    }
+
+    /// Validates the signature and expiry of a token and returns its claims.
+    pub fn verify_token(&self, token: &str) -> Result<Claims> {
+        let data = decode::<Claims>(token, &self.decoding_key, &Validation::default())?;
+        Ok(data.claims)
+    }
+
+    /// Returns the current state of the user behind verified claims, or
+    /// `None` if their session was revoked or has expired, or the account
+    /// disabled. Role changes take effect without waiting for the token to
+    /// expire.
+    pub async fn session_user(&self, claims: &Claims) -> Result<Option<User>> {
+        let row: Option<(String, String, String)> = sqlx::query_as(
+            "SELECT u.id, u.username, u.role
+             FROM sessions s JOIN users u ON u.id = s.user_id
+             WHERE s.id = ? AND s.user_id = ? AND s.revoked = 0 AND s.expires_at >= ? AND u.disabled = 0",
+        )
+        .bind(&claims.sid)
+        .bind(&claims.sub)
+        .bind(Utc::now().timestamp())
+        .fetch_optional(&self.db.pool)
+        .await?;
+
+        Ok(row.map(|(id, username, role)| User { id, username, role }))
+    }
+
+    fn issue_token(&self, user: &User, session_id: &str) -> Result<String> {
+        let expiration = Utc::now().timestamp() as usize + self.session_timeout as usize;
+
+        let claims = Claims {
+            sub: user.id.clone(),
+            username: user.username.clone(),
+            role: user.role.clone(),
+            sid: session_id.to_string(),
+            exp: expiration,
+        };
+
+        Ok(encode(&Header::default(), &claims, &self.encoding_key)?)
+    }
+}
+
+fn random_hex(bytes: usize) -> String {
+    let mut buf = vec![0u8; bytes];
+    rand::rngs::OsRng.fill_bytes(&mut buf);
+    buf.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Refresh tokens are stored hashed so a leaked database can't be replayed.
+fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIFETIME: u64 = 3600;
+
+    async fn service() -> (AuthService, User) {
+        let db = Arc::new(Database::in_memory().await);
+        sqlx::query("INSERT INTO users (id, username, password_hash, role) VALUES ('u1', 'alice', '', 'operator')")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let config = SecurityConfig {
+            jwt_secret: None,
+            admin_setup_enabled: false,
+            session_timeout: 900,
+            refresh_token_lifetime: LIFETIME,
+        };
+        let service = AuthService::new(db, "0123456789abcdef0123456789abcdef", &config);
+        let user = User { id: "u1".to_string(), username: "alice".to_string(), role: "operator".to_string() };
+        (service, user)
+    }
+
+    async fn execute(service: &AuthService, sql: &str) {
+        sqlx::query(sql).execute(&service.db.pool).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn refreshing_rotates_the_refresh_token() {
+        let (service, user) = service().await;
+        let first = service.start_session(&user).await.unwrap();
+
+        let (refreshed_user, second) = service.refresh(&first.refresh_token).await.unwrap().unwrap();
+        assert_eq!(refreshed_user.id, "u1");
+        assert_ne!(second.refresh_token, first.refresh_token);
+        assert_eq!(second.expires_in, 900);
+        let claims = service.verify_token(&second.token).unwrap();
+        assert_eq!(claims.sid, first.refresh_token.split_once('.').unwrap().0);
+
+        // The old token is used up, the new one works once
+        assert!(service.refresh(&first.refresh_token).await.unwrap().is_none());
+        let (_, third) = service.refresh(&second.refresh_token).await.unwrap().unwrap();
+        assert!(service.refresh(&second.refresh_token).await.unwrap().is_none());
+        assert!(service.refresh(&third.refresh_token).await.unwrap().is_some());
+
+        assert!(service.refresh("not-a-token").await.unwrap().is_none());
+        let (sid, _) = first.refresh_token.split_once('.').unwrap();
+        assert!(service.refresh(&format!("{}.{}", sid, "0".repeat(64))).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn logging_out_revokes_both_tokens() {
+        let (service, user) = service().await;
+        let tokens = service.start_session(&user).await.unwrap();
+        let other = service.start_session(&user).await.unwrap();
+        let claims = service.verify_token(&tokens.token).unwrap();
+        assert!(service.session_user(&claims).await.unwrap().is_some());
+
+        service.revoke_session(&claims.sid).await.unwrap();
+        assert!(service.session_user(&claims).await.unwrap().is_none());
+        assert!(service.refresh(&tokens.refresh_token).await.unwrap().is_none());
+
+        // Other sessions of the same user are unaffected
+        let other_claims = service.verify_token(&other.token).unwrap();
+        assert!(service.session_user(&other_claims).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn sessions_expire_after_the_refresh_token_lifetime() {
+        let (service, user) = service().await;
+        let tokens = service.start_session(&user).await.unwrap();
+        let claims = service.verify_token(&tokens.token).unwrap();
+
+        let expires_at: i64 = sqlx::query_scalar("SELECT expires_at FROM sessions")
+            .fetch_one(&service.db.pool)
+            .await
+            .unwrap();
+        let now = Utc::now().timestamp();
+        assert!((now + LIFETIME as i64 - expires_at).abs() <= 1, "{} vs {}", expires_at, now);
+
+        // A session not refreshed within the lifetime
+        execute(&service, &format!("UPDATE sessions SET expires_at = {}", now - 1)).await;
+        assert!(service.session_user(&claims).await.unwrap().is_none());
+        assert!(service.refresh(&tokens.refresh_token).await.unwrap().is_none());
+
+        // Expired sessions are cleaned up when the next one starts
+        service.start_session(&user).await.unwrap();
+        let remaining: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM sessions")
+            .fetch_one(&service.db.pool)
+            .await
+            .unwrap();
+        assert_eq!(remaining, 1);
+    }
+
+    #[tokio::test]
+    async fn session_user_reflects_the_current_account() {
+        let (service, user) = service().await;
+        let tokens = service.start_session(&user).await.unwrap();
+        let claims = service.verify_token(&tokens.token).unwrap();
+
+        // Role changes apply without a new token
+        execute(&service, "UPDATE users SET role = 'viewer' WHERE id = 'u1'").await;
+        assert_eq!(service.session_user(&claims).await.unwrap().unwrap().role, "viewer");
+        assert_eq!(claims.role, "operator");
+
+        execute(&service, "UPDATE users SET disabled = 1 WHERE id = 'u1'").await;
+        assert!(service.session_user(&claims).await.unwrap().is_none());
+        assert!(service.refresh(&tokens.refresh_token).await.unwrap().is_none());
+
+        // Claims must name the session's own user
+        execute(&service, "UPDATE users SET disabled = 0 WHERE id = 'u1'").await;
+        let forged = Claims { sub: "u2".to_string(), ..claims };
+        assert!(service.session_user(&forged).await.unwrap().is_none());
+    }
+}
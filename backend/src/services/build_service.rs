@@ -0,0 +1,402 @@
+use crate::config::DockerConfig;
+use crate::services::registry_service::RegistryService;
+use anyhow::Context;
+use bollard::auth::DockerCredentials;
+use bollard::image::BuildImageOptions;
+use futures::{Stream, StreamExt};
+use hyper::body::Bytes;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::io::AsyncWriteExt;
+
+/// Uploaded contexts that were never built are removed after this long.
+const UPLOAD_TTL: Duration = Duration::from_secs(3600);
+
+#[derive(Debug)]
+pub enum BuildError {
+    NotFound,
+    InvalidInput(String),
+    /// The context exceeds `docker.max_build_context_mb`; holds the limit.
+    TooLarge(u64),
+    Internal(anyhow::Error),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::NotFound => write!(f, "Build context not found; upload it again"),
+            BuildError::InvalidInput(msg) => write!(f, "{}", msg),
+            BuildError::TooLarge(limit) => write!(f, "Build context is larger than {} MB", limit / 1024 / 1024),
+            BuildError::Internal(e) => write!(f, "{:#}", e),
+        }
+    }
+}
+
+impl From<anyhow::Error> for BuildError {
+    fn from(e: anyhow::Error) -> Self {
+        BuildError::Internal(e)
+    }
+}
+
+impl From<std::io::Error> for BuildError {
+    fn from(e: std::io::Error) -> Self {
+        BuildError::Internal(e.into())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, BuildError>;
+
+#[derive(Serialize)]
+pub struct UploadedContext {
+    /// Pass as `context_id` in a [`BuildRequest`]. Valid for one build.
+    pub id: String,
+    pub size: u64,
+}
+
+#[derive(Deserialize)]
+pub struct BuildRequest {
+    /// A context uploaded beforehand.
+    pub context_id: Option<String>,
+    /// A host directory under one of `docker.build_context_roots`.
+    pub path: Option<String>,
+    /// Relative to the context root. Defaults to `Dockerfile`.
+    pub dockerfile: Option<String>,
+    #[serde(default)]
+    pub build_args: HashMap<String, String>,
+    /// Stage to stop at in a multi-stage Dockerfile.
+    pub target: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub no_cache: bool,
+    /// Always pull newer versions of base images.
+    #[serde(default)]
+    pub pull: bool,
+}
+
+/// A validated build, ready for `DockerService::build_image`.
+pub struct PreparedBuild {
+    pub options: BuildImageOptions<String>,
+    pub credentials: HashMap<String, DockerCredentials>,
+    pub context: Vec<u8>,
+    /// Docker takes a single tag per build; these are added afterwards.
+    pub extra_tags: Vec<String>,
+}
+
+pub struct BuildService {
+    registries: Arc<RegistryService>,
+    roots: Vec<PathBuf>,
+    max_bytes: u64,
+    uploads: PathBuf,
+}
+
+impl BuildService {
+    /// Uploads are kept under `data_dir` rather than the shared temp
+    /// directory, where another user could create the directory first.
+    pub fn new(registries: Arc<RegistryService>, config: &DockerConfig, data_dir: &Path) -> Self {
+        Self {
+            registries,
+            roots: config.build_context_roots.clone(),
+            max_bytes: config.max_build_context_bytes,
+            uploads: data_dir.join("build-contexts"),
+        }
+    }
+
+    /// Writes an uploaded tar context to a temporary file as it arrives.
+    pub async fn save_context<E: fmt::Display>(&self, body: impl Stream<Item = std::result::Result<Bytes, E>>) -> Result<UploadedContext> {
+        let mut dir = tokio::fs::DirBuilder::new();
+        dir.recursive(true);
+        #[cfg(unix)]
+        dir.mode(0o700);
+        dir.create(&self.uploads).await?;
+        self.remove_stale_uploads().await;
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let path = self.upload_path(&id);
+        let result = self.write_upload(&path, body).await;
+        if result.is_err() {
+            let _ = tokio::fs::remove_file(&path).await;
+        }
+        Ok(UploadedContext { id, size: result? })
+    }
+
+    async fn write_upload<E: fmt::Display>(&self, path: &Path, body: impl Stream<Item = std::result::Result<Bytes, E>>) -> Result<u64> {
+        let mut file = tokio::fs::File::create(path).await?;
+        let mut size = 0u64;
+        futures::pin_mut!(body);
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk.map_err(|e| BuildError::InvalidInput(format!("Upload failed: {}", e)))?;
+            size += chunk.len() as u64;
+            if size > self.max_bytes {
+                return Err(BuildError::TooLarge(self.max_bytes));
+            }
+            file.write_all(&chunk).await?;
+        }
+        if size == 0 {
+            return Err(BuildError::InvalidInput("Build context must not be empty".into()));
+        }
+        file.flush().await?;
+        Ok(size)
+    }
+
+    async fn remove_stale_uploads(&self) {
+        let Ok(mut entries) = tokio::fs::read_dir(&self.uploads).await else {
+            return;
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let stale = entry
+                .metadata()
+                .await
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                .is_some_and(|age| age > UPLOAD_TTL);
+            if stale {
+                let _ = tokio::fs::remove_file(entry.path()).await;
+            }
+        }
+    }
+
+    fn upload_path(&self, id: &str) -> PathBuf {
+        self.uploads.join(format!("{}.tar", id))
+    }
+
+    /// Validates the request and loads its context. Uploaded contexts are
+    /// consumed.
+    pub async fn prepare(&self, request: BuildRequest) -> Result<PreparedBuild> {
+        let dockerfile = request.dockerfile.as_deref().map(str::trim).filter(|d| !d.is_empty()).unwrap_or("Dockerfile");
+        if Path::new(dockerfile).is_absolute() || dockerfile.split(['/', '\\']).any(|part| part == "..") {
+            return Err(BuildError::InvalidInput(format!(
+                "Invalid dockerfile {:?}: expected a path inside the build context",
+                dockerfile
+            )));
+        }
+        let mut tags = Vec::with_capacity(request.tags.len());
+        for tag in &request.tags {
+            let tag = tag.trim();
+            if tag.is_empty() || tag.contains(char::is_whitespace) {
+                return Err(BuildError::InvalidInput(format!("Invalid tag {:?}", tag)));
+            }
+            tags.push(tag.to_string());
+        }
+
+        let context = match (request.context_id, request.path) {
+            (Some(id), None) => self.take_upload(&id).await?,
+            (None, Some(path)) => self.tar_host_directory(&path).await?,
+            _ => return Err(BuildError::InvalidInput("Set exactly one of context_id or path".into())),
+        };
+
+        let credentials = self
+            .registries
+            .all_credentials()
+            .await
+            .map_err(|e| BuildError::Internal(anyhow::anyhow!("{}", e)))?;
+
+        let mut tags = tags.into_iter();
+        let options = BuildImageOptions {
+            dockerfile: dockerfile.to_string(),
+            t: tags.next().unwrap_or_default(),
+            buildargs: request.build_args,
+            target: request.target.unwrap_or_default(),
+            nocache: request.no_cache,
+            pull: request.pull,
+            rm: true,
+            ..Default::default()
+        };
+        Ok(PreparedBuild { options, credentials, context, extra_tags: tags.collect() })
+    }
+
+    async fn take_upload(&self, id: &str) -> Result<Vec<u8>> {
+        // Only accept ids we hand out, so the path can't escape the directory
+        let id = uuid::Uuid::parse_str(id).map_err(|_| BuildError::NotFound)?;
+        let path = self.upload_path(&id.to_string());
+        let context = match tokio::fs::read(&path).await {
+            Ok(context) => context,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(BuildError::NotFound),
+            Err(e) => return Err(e.into()),
+        };
+        let _ = tokio::fs::remove_file(&path).await;
+        Ok(context)
+    }
+
+    async fn tar_host_directory(&self, path: &str) -> Result<Vec<u8>> {
+        if self.roots.is_empty() {
+            return Err(BuildError::InvalidInput(
+                "Building from host directories is disabled; set docker.build_context_roots".into(),
+            ));
+        }
+        let dir = tokio::fs::canonicalize(path)
+            .await
+            .map_err(|_| BuildError::InvalidInput(format!("Directory {:?} does not exist", path)))?;
+        if !dir.is_dir() {
+            return Err(BuildError::InvalidInput(format!("{:?} is not a directory", path)));
+        }
+        let allowed = self
+            .roots
+            .iter()
+            .filter_map(|root| std::fs::canonicalize(root).ok())
+            .any(|root| dir.starts_with(root));
+        if !allowed {
+            return Err(BuildError::InvalidInput(format!(
+                "Directory {:?} is outside docker.build_context_roots",
+                path
+            )));
+        }
+
+        let max_bytes = self.max_bytes;
+        tokio::task::spawn_blocking(move || archive::directory(&dir, max_bytes))
+            .await
+            .map_err(|e| BuildError::Internal(e.into()))?
+    }
+}
+
+/// Tars a host directory as a build context.
+mod archive {
+    use super::{BuildError, Context, Path, Result};
+    use std::fs;
+
+    /// Archives the directory's contents without following symlinks.
+    /// `.dockerignore` is left for Docker to read but not applied here.
+    pub fn directory(root: &Path, max_bytes: u64) -> Result<Vec<u8>> {
+        let mut builder = tar::Builder::new(Vec::new());
+        builder.follow_symlinks(false);
+        append_dir(&mut builder, root, Path::new(""), max_bytes)?;
+        Ok(builder.into_inner().context("Failed to finish build context")?)
+    }
+
+    fn append_dir(builder: &mut tar::Builder<Vec<u8>>, dir: &Path, prefix: &Path, max_bytes: u64) -> Result<()> {
+        let mut entries = fs::read_dir(dir)
+            .with_context(|| format!("Failed to read {}", dir.display()))?
+            .collect::<std::io::Result<Vec<_>>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+
+        for entry in entries {
+            let name = prefix.join(entry.file_name());
+            // Doesn't follow symlinks; sockets and other special files are skipped
+            let metadata = entry.metadata()?;
+            if !(metadata.is_dir() || metadata.is_file() || metadata.is_symlink()) {
+                continue;
+            }
+            if metadata.is_file() && builder.get_ref().len() as u64 + metadata.len() > max_bytes {
+                return Err(BuildError::TooLarge(max_bytes));
+            }
+            builder
+                .append_path_with_name(entry.path(), &name)
+                .with_context(|| format!("Failed to add {}", entry.path().display()))?;
+            if metadata.is_dir() {
+                append_dir(builder, &entry.path(), &name, max_bytes)?;
+            }
+            if builder.get_ref().len() as u64 > max_bytes {
+                return Err(BuildError::TooLarge(max_bytes));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use std::io::Read;
+
+    fn entries(context: &[u8]) -> Vec<(String, tar::EntryType, Vec<u8>, Option<String>)> {
+        let mut archive = tar::Archive::new(context);
+        archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let path = entry.path().unwrap().to_string_lossy().into_owned();
+                let link = entry.link_name().unwrap().map(|l| l.to_string_lossy().into_owned());
+                let kind = entry.header().entry_type();
+                let mut data = Vec::new();
+                entry.read_to_end(&mut data).unwrap();
+                (path, kind, data, link)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn directory_round_trips_through_a_tar_reader() {
+        let root = tempfile::tempdir().unwrap();
+        let long = "a".repeat(120);
+        fs_write(root.path(), "Dockerfile", b"FROM scratch\n");
+        fs_write(root.path(), "src/main.rs", b"fn main() {}\n");
+        fs_write(root.path(), &format!("src/{}", long), b"long");
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("/etc/passwd", root.path().join("passwd")).unwrap();
+
+        let context = archive::directory(root.path(), 1024 * 1024).unwrap();
+        let entries = entries(&context);
+        let names: Vec<&str> = entries.iter().map(|(name, ..)| name.as_str()).collect();
+
+        let expected_long = format!("src/{}", long);
+        let mut expected = vec!["Dockerfile", "passwd", "src", expected_long.as_str(), "src/main.rs"];
+        if cfg!(not(unix)) {
+            expected.retain(|name| *name != "passwd");
+        }
+        assert_eq!(names, expected);
+        for (name, kind, data, link) in &entries {
+            match name.as_str() {
+                "Dockerfile" => assert_eq!(data, b"FROM scratch\n"),
+                "src/main.rs" => assert_eq!(data, b"fn main() {}\n"),
+                "src" => assert!(kind.is_dir()),
+                "passwd" => {
+                    // The link itself is archived, never the file it points at
+                    assert!(kind.is_symlink());
+                    assert_eq!(link.as_deref(), Some("/etc/passwd"));
+                    assert!(data.is_empty());
+                }
+                _ => assert_eq!(data, b"long"),
+            }
+        }
+    }
+
+    #[test]
+    fn directory_stops_at_the_size_limit() {
+        let root = tempfile::tempdir().unwrap();
+        fs_write(root.path(), "big", &[0; 8192]);
+        assert!(matches!(archive::directory(root.path(), 4096), Err(BuildError::TooLarge(4096))));
+        assert!(archive::directory(root.path(), 64 * 1024).is_ok());
+    }
+
+    #[tokio::test]
+    async fn uploads_are_kept_in_a_private_directory() {
+        let data = tempfile::tempdir().unwrap();
+        let registries = Arc::new(RegistryService::new(
+            Arc::new(Database::in_memory().await),
+            "0123456789abcdef0123456789abcdef",
+        ));
+        let config = DockerConfig {
+            socket_path: "/var/run/docker.sock".into(),
+            remote_host: None,
+            build_context_roots: Vec::new(),
+            max_build_context_bytes: 1024,
+        };
+        let builds = BuildService::new(registries, &config, data.path());
+
+        let chunks = futures::stream::iter([Ok::<_, std::io::Error>(Bytes::from_static(b"context"))]);
+        let upload = builds.save_context(chunks).await.unwrap();
+        assert_eq!(upload.size, 7);
+
+        let dir = data.path().join("build-contexts");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&dir).unwrap().permissions().mode() & 0o777, 0o700);
+        }
+        assert_eq!(builds.take_upload(&upload.id).await.unwrap(), b"context");
+        assert!(matches!(builds.take_upload(&upload.id).await, Err(BuildError::NotFound)));
+    }
+
+    fn fs_write(root: &Path, name: &str, data: &[u8]) {
+        let path = root.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, data).unwrap();
+    }
+}
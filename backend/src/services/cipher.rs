@@ -0,0 +1,62 @@
+//! Encryption of credentials stored in the database.
+
+use anyhow::{anyhow, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::hkdf;
+
+/// AES-256-GCM with a key derived from the server secret. Changing the
+/// secret makes stored values unreadable, so they have to be entered again.
+pub struct Cipher {
+    key: LessSafeKey,
+}
+
+impl Cipher {
+    /// `purpose` separates the keys of different stores, so a value can't
+    /// be moved from one to another.
+    pub fn new(secret: &str, purpose: &[u8]) -> Self {
+        let prk = hkdf::Salt::new(hkdf::HKDF_SHA256, b"dockium").extract(secret.as_bytes());
+        let info = [purpose];
+        let okm = prk
+            .expand(&info, &AES_256_GCM)
+            .expect("AES-256 key length is a valid HKDF output length");
+        Self { key: LessSafeKey::new(UnboundKey::from(okm)) }
+    }
+
+    /// Encrypts `plaintext` bound to `id`, as base64 of the nonce followed
+    /// by the ciphertext and tag.
+    pub fn seal(&self, id: &str, plaintext: &str) -> String {
+        let nonce: [u8; NONCE_LEN] = rand::random();
+        let mut data = plaintext.as_bytes().to_vec();
+        self.key
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(id.as_bytes()), &mut data)
+            .expect("plaintext fits in a single AES-GCM message");
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend(data);
+        BASE64.encode(sealed)
+    }
+
+    /// `what` names the value in the error, e.g. "registry token".
+    pub fn open(&self, id: &str, sealed: &str, what: &str) -> Result<String> {
+        let unreadable = || {
+            anyhow!(
+                "Stored {} can't be decrypted; the server secret may have changed, so enter it again",
+                what
+            )
+        };
+        let sealed = BASE64.decode(sealed).map_err(|_| unreadable())?;
+        if sealed.len() < NONCE_LEN {
+            return Err(unreadable());
+        }
+        let (nonce, data) = sealed.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| unreadable())?;
+        let mut data = data.to_vec();
+        let plaintext = self
+            .key
+            .open_in_place(nonce, Aad::from(id.as_bytes()), &mut data)
+            .map_err(|_| unreadable())?;
+        String::from_utf8(plaintext.to_vec()).map_err(|_| unreadable())
+    }
+}
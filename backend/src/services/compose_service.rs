@@ -1,7 +1,10 @@
-use std::process::Stdio;
 use tokio::process::Command;
-use anyhow::{Result, anyhow};
+use anyhow::{Context, Result, anyhow};
 use serde::Serialize;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use crate::services::registry_service::RegistryService;
 
 #[derive(Serialize)]
 pub struct ComposeProject {
@@ -10,11 +13,13 @@ pub struct ComposeProject {
     pub config_path: String,
 }
 
-pub struct ComposeService {}
+pub struct ComposeService {
+    registries: Arc<RegistryService>,
+}
 
 impl ComposeService {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(registries: Arc<RegistryService>) -> Self {
+        Self { registries }
     }
 
     pub async fn list_projects(&self) -> Result<Vec<ComposeProject>> {
@@ -39,11 +44,13 @@ impl ComposeService {
         Ok(result)
     }
 
+    /// Starts the project. Images are pulled with the stored registry
+    /// credentials.
     pub async fn up(&self, project_path: &str) -> Result<()> {
-        let output = Command::new("docker")
-            .args(["compose", "-f", project_path, "up", "-d"])
-            .output()
-            .await?;
+        let mut command = Command::new("docker");
+        command.args(["compose", "-f", project_path, "up", "-d"]);
+        let _config = self.with_registry_config(&mut command).await?;
+        let output = command.output().await?;
 
         if !output.status.success() {
             return Err(anyhow!("Failed to up compose project: {}", String::from_utf8_lossy(&output.stderr)));
@@ -62,4 +69,53 @@ impl ComposeService {
         }
         Ok(())
     }
+
+    /// Points the CLI at a temporary config directory holding the stored
+    /// registry credentials. The directory is removed when the returned
+    /// guard is dropped.
+    async fn with_registry_config(&self, command: &mut Command) -> Result<Option<TempConfigDir>> {
+        let Some(config) = self.registries.docker_config().await.map_err(|e| anyhow!("{}", e))? else {
+            return Ok(None);
+        };
+
+        let dir = TempConfigDir(std::env::temp_dir().join(format!("dockium-docker-{}", uuid::Uuid::new_v4())));
+        // The config holds plaintext credentials, so both are created
+        // private rather than tightened after the bytes are on disk
+        let mut builder = std::fs::DirBuilder::new();
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+            builder.mode(0o700);
+            options.mode(0o600);
+        }
+        builder.create(&dir.0).context("Failed to create Docker config directory")?;
+        let mut file = options.open(dir.0.join("config.json")).context("Failed to create Docker config")?;
+        file.write_all(&serde_json::to_vec(&config)?).context("Failed to write Docker config")?;
+        drop(file);
+
+        #[cfg(unix)]
+        {
+            // Keep finding a compose plugin installed for the current user
+            if let Some(home) = std::env::var_os("HOME") {
+                let plugins = PathBuf::from(home).join(".docker/cli-plugins");
+                if plugins.is_dir() {
+                    let _ = std::os::unix::fs::symlink(plugins, dir.0.join("cli-plugins"));
+                }
+            }
+        }
+
+        command.env("DOCKER_CONFIG", &dir.0);
+        Ok(Some(dir))
+    }
+}
+
+/// Removes the directory on drop.
+struct TempConfigDir(PathBuf);
+
+impl Drop for TempConfigDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
 }
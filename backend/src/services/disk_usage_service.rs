@@ -0,0 +1,368 @@
+use crate::services::docker_service::DockerService;
+use anyhow::Result;
+use bollard::service::SystemDataUsageResponse;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// Disk space used by Docker, as `docker system df -v` reports it. Items
+/// are sorted largest first.
+#[derive(Serialize)]
+pub struct DiskUsage {
+    /// When the engine was asked; responses are served from cache until
+    /// the refresh interval has passed.
+    pub generated_at: DateTime<Utc>,
+    pub images: UsageGroup<ImageUsage>,
+    pub containers: UsageGroup<ContainerUsage>,
+    pub volumes: UsageGroup<VolumeUsage>,
+    pub build_cache: UsageGroup<BuildCacheUsage>,
+    pub total_size: u64,
+    pub reclaimable: u64,
+}
+
+#[derive(Serialize)]
+pub struct UsageGroup<T> {
+    pub items: Vec<T>,
+    pub total_size: u64,
+    /// Bytes that would be freed by removing everything not in use.
+    pub reclaimable: u64,
+}
+
+#[derive(Serialize)]
+pub struct ImageUsage {
+    pub id: String,
+    pub tags: Vec<String>,
+    /// Unix seconds.
+    pub created: i64,
+    pub size: u64,
+    /// Bytes in layers also used by other images. `None` when the engine
+    /// did not compute it.
+    pub shared_size: Option<u64>,
+    /// Bytes only this image uses, freed when it is removed.
+    pub unique_size: u64,
+    pub containers: u64,
+}
+
+#[derive(Serialize)]
+pub struct ContainerUsage {
+    pub id: String,
+    pub name: Option<String>,
+    pub image: Option<String>,
+    pub state: Option<String>,
+    /// Size of the writable layer.
+    pub size: u64,
+}
+
+#[derive(Serialize)]
+pub struct VolumeUsage {
+    pub name: String,
+    pub driver: String,
+    /// `None` for drivers that can't report usage.
+    pub size: Option<u64>,
+    /// Containers mounting the volume.
+    pub ref_count: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct BuildCacheUsage {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub typ: Option<String>,
+    pub description: Option<String>,
+    pub size: u64,
+    pub in_use: bool,
+    pub shared: bool,
+    pub last_used_at: Option<String>,
+}
+
+pub struct DiskUsageService {
+    docker: Arc<DockerService>,
+    refresh_interval: Duration,
+    /// Held while refreshing, so concurrent requests wait for one engine
+    /// call rather than each making their own.
+    cache: Mutex<Option<(Instant, Arc<DiskUsage>)>>,
+}
+
+impl DiskUsageService {
+    pub fn new(docker: Arc<DockerService>, refresh_interval: Duration) -> Self {
+        Self {
+            docker,
+            refresh_interval,
+            cache: Mutex::new(None),
+        }
+    }
+
+    /// Returns the cached usage, asking the engine again when it is older
+    /// than the refresh interval or `refresh` is set.
+    pub async fn get(&self, refresh: bool) -> Result<Arc<DiskUsage>> {
+        let mut cache = self.cache.lock().await;
+        if let Some((fetched, usage)) = cache.as_ref() {
+            if !refresh && fetched.elapsed() < self.refresh_interval {
+                return Ok(usage.clone());
+            }
+        }
+
+        let usage = Arc::new(summarize(self.docker.data_usage().await?));
+        *cache = Some((Instant::now(), usage.clone()));
+        Ok(usage)
+    }
+
+    /// Drops the cached usage after something changed it, e.g. a prune.
+    pub async fn invalidate(&self) {
+        *self.cache.lock().await = None;
+    }
+}
+
+fn summarize(usage: SystemDataUsageResponse) -> DiskUsage {
+    let bytes = |n: i64| n.max(0) as u64;
+
+    let mut images: Vec<ImageUsage> = usage
+        .images
+        .unwrap_or_default()
+        .into_iter()
+        .map(|image| {
+            // -1 when not computed
+            let shared_size = (image.shared_size >= 0).then_some(image.shared_size as u64);
+            ImageUsage {
+                unique_size: bytes(image.size).saturating_sub(shared_size.unwrap_or(0)),
+                id: image.id,
+                tags: image.repo_tags.into_iter().filter(|t| t != "<none>:<none>").collect(),
+                created: image.created,
+                size: bytes(image.size),
+                shared_size,
+                containers: bytes(image.containers),
+            }
+        })
+        .collect();
+    images.sort_by_key(|i| Reverse(i.size));
+    // Shared layers are counted once in the layer total, so the sum of
+    // image sizes would overstate it.
+    let images_total = usage
+        .layers_size
+        .map(bytes)
+        .unwrap_or_else(|| images.iter().map(|i| i.unique_size).sum());
+    // Like `docker system df`, an image in use only keeps its unique layers
+    // from being reclaimed; shared ones are counted against the total once
+    let images_used: u64 = images.iter().filter(|i| i.containers > 0).map(|i| i.unique_size).sum();
+    let images = UsageGroup {
+        reclaimable: images_total.saturating_sub(images_used),
+        total_size: images_total,
+        items: images,
+    };
+
+    let mut containers: Vec<ContainerUsage> = usage
+        .containers
+        .unwrap_or_default()
+        .into_iter()
+        .map(|container| ContainerUsage {
+            id: container.id.unwrap_or_default(),
+            name: container
+                .names
+                .and_then(|n| n.into_iter().next())
+                .map(|n| n.trim_start_matches('/').to_string()),
+            image: container.image,
+            state: container.state,
+            size: container.size_rw.map(bytes).unwrap_or(0),
+        })
+        .collect();
+    containers.sort_by_key(|i| Reverse(i.size));
+    let containers = UsageGroup {
+        total_size: containers.iter().map(|c| c.size).sum(),
+        reclaimable: containers
+            .iter()
+            .filter(|c| c.state.as_deref() != Some("running"))
+            .map(|c| c.size)
+            .sum(),
+        items: containers,
+    };
+
+    let mut volumes: Vec<VolumeUsage> = usage
+        .volumes
+        .unwrap_or_default()
+        .into_iter()
+        .map(|volume| VolumeUsage {
+            size: volume.usage_data.as_ref().filter(|u| u.size >= 0).map(|u| u.size as u64),
+            ref_count: volume.usage_data.as_ref().filter(|u| u.ref_count >= 0).map(|u| u.ref_count as u64),
+            name: volume.name,
+            driver: volume.driver,
+        })
+        .collect();
+    volumes.sort_by_key(|i| Reverse(i.size));
+    let volumes = UsageGroup {
+        total_size: volumes.iter().filter_map(|v| v.size).sum(),
+        reclaimable: volumes
+            .iter()
+            .filter(|v| v.ref_count == Some(0))
+            .filter_map(|v| v.size)
+            .sum(),
+        items: volumes,
+    };
+
+    let mut build_cache: Vec<BuildCacheUsage> = usage
+        .build_cache
+        .unwrap_or_default()
+        .into_iter()
+        .map(|record| BuildCacheUsage {
+            id: record.id.unwrap_or_default(),
+            typ: record.typ.map(|t| t.to_string()),
+            description: record.description,
+            size: record.size.map(bytes).unwrap_or(0),
+            in_use: record.in_use.unwrap_or(false),
+            shared: record.shared.unwrap_or(false),
+            last_used_at: record.last_used_at,
+        })
+        .collect();
+    build_cache.sort_by_key(|i| Reverse(i.size));
+    let build_cache = UsageGroup {
+        // Shared records are also counted by the records using them
+        total_size: build_cache.iter().filter(|r| !r.shared).map(|r| r.size).sum(),
+        reclaimable: build_cache
+            .iter()
+            .filter(|r| !r.in_use && !r.shared)
+            .map(|r| r.size)
+            .sum(),
+        items: build_cache,
+    };
+
+    DiskUsage {
+        generated_at: Utc::now(),
+        total_size: images.total_size + containers.total_size + volumes.total_size + build_cache.total_size,
+        reclaimable: images.reclaimable + containers.reclaimable + volumes.reclaimable + build_cache.reclaimable,
+        images,
+        containers,
+        volumes,
+        build_cache,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bollard::service::{BuildCache, ContainerSummary, ImageSummary, Volume, VolumeUsageData};
+
+    fn image(id: &str, size: i64, shared_size: i64, containers: i64) -> ImageSummary {
+        ImageSummary {
+            id: id.to_string(),
+            repo_tags: vec![format!("{}:latest", id), "<none>:<none>".to_string()],
+            size,
+            shared_size,
+            containers,
+            ..Default::default()
+        }
+    }
+
+    fn cache(id: &str, size: i64, in_use: bool, shared: bool) -> BuildCache {
+        BuildCache {
+            id: Some(id.to_string()),
+            size: Some(size),
+            in_use: Some(in_use),
+            shared: Some(shared),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn images_in_use_only_hold_back_their_unique_layers() {
+        let usage = summarize(SystemDataUsageResponse {
+            layers_size: Some(700),
+            images: Some(vec![
+                // 200 shared with `worker`, in use
+                image("app", 500, 200, 1),
+                image("worker", 300, 200, 0),
+                // Shared size not computed
+                image("tool", 100, -1, 2),
+            ]),
+            ..Default::default()
+        });
+
+        let images = &usage.images;
+        let ids: Vec<&str> = images.items.iter().map(|i| i.id.as_str()).collect();
+        assert_eq!(ids, ["app", "worker", "tool"]);
+        assert_eq!(images.items[0].tags, ["app:latest"]);
+        assert_eq!((images.items[0].shared_size, images.items[0].unique_size), (Some(200), 300));
+        assert_eq!((images.items[2].shared_size, images.items[2].unique_size), (None, 100));
+
+        // The layer total counts shared layers once; `app` and `tool` keep
+        // 300 + 100 of it in use
+        assert_eq!(images.total_size, 700);
+        assert_eq!(images.reclaimable, 300);
+    }
+
+    #[test]
+    fn image_total_falls_back_to_unique_sizes() {
+        let usage = summarize(SystemDataUsageResponse {
+            images: Some(vec![image("app", 500, 200, 0), image("worker", 300, 200, 0)]),
+            ..Default::default()
+        });
+        assert_eq!(usage.images.total_size, 400);
+        assert_eq!(usage.images.reclaimable, 400);
+    }
+
+    #[test]
+    fn shared_and_in_use_build_cache_is_not_reclaimable() {
+        let usage = summarize(SystemDataUsageResponse {
+            build_cache: Some(vec![
+                cache("unused", 100, false, false),
+                cache("in-use", 200, true, false),
+                cache("shared", 400, false, true),
+            ]),
+            ..Default::default()
+        });
+        let build_cache = &usage.build_cache;
+        assert_eq!(build_cache.items.len(), 3);
+        assert_eq!(build_cache.total_size, 300);
+        assert_eq!(build_cache.reclaimable, 100);
+    }
+
+    #[test]
+    fn totals_add_up_every_group() {
+        let usage = summarize(SystemDataUsageResponse {
+            layers_size: Some(500),
+            images: Some(vec![image("app", 500, 0, 1)]),
+            containers: Some(vec![
+                ContainerSummary {
+                    id: Some("c1".to_string()),
+                    names: Some(vec!["/web".to_string()]),
+                    state: Some("running".to_string()),
+                    size_rw: Some(10),
+                    ..Default::default()
+                },
+                ContainerSummary {
+                    id: Some("c2".to_string()),
+                    state: Some("exited".to_string()),
+                    size_rw: Some(20),
+                    ..Default::default()
+                },
+            ]),
+            volumes: Some(vec![
+                Volume {
+                    name: "data".to_string(),
+                    usage_data: Some(VolumeUsageData { size: 1000, ref_count: 1 }),
+                    ..Default::default()
+                },
+                Volume {
+                    name: "old".to_string(),
+                    usage_data: Some(VolumeUsageData { size: 50, ref_count: 0 }),
+                    ..Default::default()
+                },
+                // Usage not reported by the driver
+                Volume {
+                    name: "remote".to_string(),
+                    usage_data: Some(VolumeUsageData { size: -1, ref_count: -1 }),
+                    ..Default::default()
+                },
+            ]),
+            build_cache: Some(vec![cache("unused", 5, false, false)]),
+        });
+
+        assert_eq!(usage.containers.items[1].name.as_deref(), Some("web"));
+        assert_eq!((usage.containers.total_size, usage.containers.reclaimable), (30, 20));
+        assert_eq!((usage.volumes.total_size, usage.volumes.reclaimable), (1050, 50));
+        assert_eq!(usage.volumes.items[2].size, None);
+        assert_eq!(usage.total_size, 500 + 30 + 1050 + 5);
+        assert_eq!(usage.reclaimable, 20 + 50 + 5);
+    }
+}
@@ -1,21 +1,229 @@
-use bollard::Docker;
-use bollard::container::{ListContainersOptions, Config, CreateContainerOptions, StartContainerOptions, LogOutput};
-use bollard::image::{ListImagesOptions, RemoveImageOptions};
-use bollard::network::{ListNetworksOptions, CreateNetworkOptions};
+use bollard::{Docker, API_DEFAULT_VERSION};
+use bollard::container::{ListContainersOptions, Config, CreateContainerOptions, RenameContainerOptions, StartContainerOptions, UpdateContainerOptions, LogOutput, LogsOptions, MemoryStatsStats, Stats, StatsOptions};
+use bollard::auth::DockerCredentials;
+use bollard::image::{BuildImageOptions, CreateImageOptions, ImportImageOptions, ListImagesOptions, PushImageOptions, RemoveImageOptions, TagImageOptions};
+use bollard::network::{ConnectNetworkOptions, ListNetworksOptions, CreateNetworkOptions};
 use bollard::volume::{ListVolumesOptions, CreateVolumeOptions};
+use bollard::exec::{CreateExecOptions, ResizeExecOptions, StartExecOptions, StartExecResults};
+use bollard::system::EventsOptions;
+use bollard::service::{EndpointSettings, EventMessage, SystemDataUsageResponse};
 use futures::StreamExt;
+use hyper::body::Bytes;
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
 use std::collections::HashMap;
-use anyhow::Result;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWrite;
+use tokio::sync::broadcast;
+use crate::config::DockerConfig;
+
+/// Seconds before a request to the Docker engine times out.
+const DOCKER_TIMEOUT: u64 = 120;
+
+/// Events buffered per subscriber before slow subscribers start missing some.
+const EVENTS_CAPACITY: usize = 1024;
+const EVENTS_MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// An event from the Docker engine, flattened for clients.
+#[derive(Serialize, Clone)]
+pub struct DockerEvent {
+    /// `container`, `image`, `network`, `volume`, `daemon`, ...
+    #[serde(rename = "type")]
+    pub typ: String,
+    /// e.g. `start`, `die`, `pull` or `exec_start: /bin/sh`.
+    pub action: String,
+    pub id: Option<String>,
+    /// Actor attributes; includes `name` and, for containers, their labels.
+    pub attributes: HashMap<String, String>,
+    /// Unix seconds.
+    pub time: i64,
+    #[serde(skip)]
+    time_nano: i64,
+}
+
+impl From<EventMessage> for DockerEvent {
+    fn from(message: EventMessage) -> Self {
+        let actor = message.actor.unwrap_or_default();
+        Self {
+            typ: message.typ.map(|t| t.to_string()).unwrap_or_default(),
+            action: message.action.unwrap_or_default(),
+            id: actor.id,
+            attributes: actor.attributes.unwrap_or_default(),
+            time: message.time.unwrap_or_default(),
+            time_nano: message.time_nano.unwrap_or_default(),
+        }
+    }
+}
+
+/// Resource usage of one container, computed the same way as `docker stats`.
+#[derive(Serialize, Clone)]
+pub struct ContainerStats {
+    pub id: String,
+    pub name: String,
+    /// Percentage of a single CPU, so it can exceed 100 on multi-core hosts.
+    pub cpu_percent: f64,
+    /// Usage excluding the page cache.
+    pub memory_usage: u64,
+    pub memory_limit: u64,
+    pub memory_percent: f64,
+    pub network_rx: u64,
+    pub network_tx: u64,
+    pub block_read: u64,
+    pub block_write: u64,
+    pub pids: u64,
+}
+
+impl From<Stats> for ContainerStats {
+    fn from(stats: Stats) -> Self {
+        let cpu_delta = stats.cpu_stats.cpu_usage.total_usage as f64
+            - stats.precpu_stats.cpu_usage.total_usage as f64;
+        let system_delta = stats.cpu_stats.system_cpu_usage.unwrap_or(0) as f64
+            - stats.precpu_stats.system_cpu_usage.unwrap_or(0) as f64;
+        let online_cpus = stats.cpu_stats.online_cpus.unwrap_or_else(|| {
+            stats.cpu_stats.cpu_usage.percpu_usage.as_ref().map_or(1, |c| c.len() as u64)
+        });
+        let cpu_percent = if cpu_delta > 0.0 && system_delta > 0.0 {
+            cpu_delta / system_delta * online_cpus as f64 * 100.0
+        } else {
+            0.0
+        };
+
+        let cache = match stats.memory_stats.stats {
+            Some(MemoryStatsStats::V1(v1)) => v1.total_inactive_file,
+            Some(MemoryStatsStats::V2(v2)) => v2.inactive_file,
+            None => 0,
+        };
+        let memory_usage = stats.memory_stats.usage.unwrap_or(0).saturating_sub(cache);
+        let memory_limit = stats.memory_stats.limit.unwrap_or(0);
+        let memory_percent = if memory_limit > 0 {
+            memory_usage as f64 / memory_limit as f64 * 100.0
+        } else {
+            0.0
+        };
+
+        let networks = stats.networks.unwrap_or_default();
+        let network_rx = networks.values().map(|n| n.rx_bytes).sum();
+        let network_tx = networks.values().map(|n| n.tx_bytes).sum();
+
+        let io = stats.blkio_stats.io_service_bytes_recursive.unwrap_or_default();
+        let block_total = |op: &str| {
+            io.iter()
+                .filter(|e| e.op.eq_ignore_ascii_case(op))
+                .map(|e| e.value)
+                .sum()
+        };
+
+        Self {
+            name: stats.name.trim_start_matches('/').to_string(),
+            id: stats.id,
+            cpu_percent,
+            memory_usage,
+            memory_limit,
+            memory_percent,
+            network_rx,
+            network_tx,
+            block_read: block_total("read"),
+            block_write: block_total("write"),
+            pids: stats.pids_stats.current.unwrap_or(0),
+        }
+    }
+}
+
+/// One progress line of an image pull or push. Pull lines for a layer carry
+/// its id, and byte counts while the layer transfers or extracts.
+#[derive(Serialize, Clone)]
+pub struct ImageProgress {
+    pub id: Option<String>,
+    /// e.g. `Pulling fs layer`, `Downloading` or `Pushed`.
+    pub status: String,
+    pub current: Option<i64>,
+    pub total: Option<i64>,
+}
+
+/// Output of an image build.
+pub enum BuildOutput {
+    /// A chunk of build log text, usually one line.
+    Log(String),
+    /// Progress of a base image pull.
+    Progress(ImageProgress),
+    /// The id of the built image, sent once the build succeeds.
+    ImageId(String),
+}
+
+/// Result of loading an image archive.
+#[derive(Serialize, Default)]
+pub struct LoadedImages {
+    /// `repo:tag` references that were loaded.
+    pub tags: Vec<String>,
+    /// Ids of loaded images that had no tags in the archive.
+    pub image_ids: Vec<String>,
+}
 
 pub struct DockerService {
     client: Docker,
+    events: broadcast::Sender<DockerEvent>,
 }
 
 impl DockerService {
-    pub fn new() -> Result<Self> {
-        // Connect with defaults (handles unix/windows automatically)
-        let client = Docker::connect_with_defaults()?;
-        Ok(Self { client })
+    pub fn new(config: &DockerConfig) -> Result<Self> {
+        let client = match &config.remote_host {
+            Some(host) => Docker::connect_with_http(host, DOCKER_TIMEOUT, API_DEFAULT_VERSION)
+                .with_context(|| format!("Failed to connect to Docker at {}", host))?,
+            None => Docker::connect_with_socket(&config.socket_path, DOCKER_TIMEOUT, API_DEFAULT_VERSION)
+                .with_context(|| format!("Failed to connect to Docker socket {}", config.socket_path))?,
+        };
+        let (events, _) = broadcast::channel(EVENTS_CAPACITY);
+        Ok(Self { client, events })
+    }
+
+    // --- Event Methods ---
+
+    /// Receives every engine event seen by [`watch_events`](Self::watch_events)
+    /// from now on.
+    pub fn subscribe_events(&self) -> broadcast::Receiver<DockerEvent> {
+        self.events.subscribe()
+    }
+
+    /// Follows the engine's event stream and fans it out to subscribers,
+    /// reconnecting with backoff. After a reconnect, events missed in the
+    /// meantime are replayed. Runs until the process exits.
+    pub async fn watch_events(self: Arc<Self>) {
+        let mut backoff = Duration::from_secs(1);
+        let mut last_nano: i64 = 0;
+
+        loop {
+            let options = (last_nano > 0).then(|| EventsOptions::<String> {
+                since: Some((last_nano / 1_000_000_000).to_string()),
+                until: None,
+                filters: HashMap::new(),
+            });
+            let mut stream = self.client.events(options);
+
+            while let Some(message) = stream.next().await {
+                let message = match message {
+                    Ok(message) => message,
+                    Err(e) => {
+                        tracing::warn!("Docker event stream failed: {}", e);
+                        break;
+                    }
+                };
+                backoff = Duration::from_secs(1);
+
+                let event = DockerEvent::from(message);
+                // Replayed events that were already sent before the reconnect
+                if event.time_nano <= last_nano {
+                    continue;
+                }
+                last_nano = event.time_nano;
+                // Fails only when nobody is listening
+                let _ = self.events.send(event);
+            }
+
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(EVENTS_MAX_BACKOFF);
+        }
     }
 
     // --- Container Methods ---
@@ -49,15 +257,13 @@ impl DockerService {
         Ok(())
     }
 
-    pub async fn get_container_logs(&self, id: &str) -> Result<impl futures::Stream<Item = Result<LogOutput, bollard::errors::Error>>> {
-        let options = Some(bollard::container::LogsOptions {
-            stdout: true,
-            stderr: true,
-            follow: true,
-            tail: "100".to_string(),
-            ..Default::default()
-        });
-        Ok(self.client.logs(id, options))
+    pub async fn get_container_logs(&self, id: &str, options: LogsOptions<String>) -> Result<impl futures::Stream<Item = Result<LogOutput, bollard::errors::Error>>> {
+        Ok(self.client.logs(id, Some(options)))
+    }
+
+    pub async fn rename_container(&self, id: &str, name: &str) -> Result<()> {
+        self.client.rename_container(id, RenameContainerOptions { name }).await?;
+        Ok(())
     }
 
     pub async fn inspect_container(&self, id: &str) -> Result<bollard::service::ContainerInspectResponse> {
@@ -65,6 +271,13 @@ impl DockerService {
         Ok(container)
     }
 
+    /// Changes resource limits and the restart policy in place, without
+    /// restarting the container.
+    pub async fn update_container(&self, id: &str, options: UpdateContainerOptions<String>) -> Result<()> {
+        self.client.update_container(id, options).await?;
+        Ok(())
+    }
+
     pub async fn create_container(&self, name: &str, config: Config<String>) -> Result<bollard::service::ContainerCreateResponse> {
         let options = Some(CreateContainerOptions {
             name: name.to_string(),
@@ -74,6 +287,90 @@ impl DockerService {
         Ok(response)
     }
 
+    // --- Stats Methods ---
+
+    /// Takes a single stats sample. Docker waits for a second sample so the
+    /// CPU percentage is meaningful, which makes this take about a second.
+    pub async fn container_stats(&self, id: &str) -> Result<ContainerStats> {
+        let options = Some(StatsOptions { stream: false, one_shot: false });
+        let stats = self
+            .client
+            .stats(id, options)
+            .next()
+            .await
+            .ok_or_else(|| anyhow!("No stats returned for container {}", id))??;
+        Ok(stats.into())
+    }
+
+    /// Streams stats samples, one per second, until the container stops.
+    pub fn stream_container_stats(&self, id: &str) -> impl futures::Stream<Item = Result<ContainerStats, bollard::errors::Error>> {
+        let options = Some(StatsOptions { stream: true, one_shot: false });
+        self.client.stats(id, options).map(|stats| stats.map(ContainerStats::from))
+    }
+
+    /// Samples every running container concurrently. Containers that stop
+    /// while being sampled are left out.
+    pub async fn running_container_stats(&self) -> Result<Vec<ContainerStats>> {
+        let containers = self.list_containers(false).await?;
+        let samples = futures::future::join_all(
+            containers
+                .iter()
+                .filter_map(|c| c.id.as_deref())
+                .map(|id| self.container_stats(id)),
+        )
+        .await;
+
+        Ok(samples.into_iter().filter_map(Result::ok).collect())
+    }
+
+    // --- Exec Methods ---
+
+    /// Creates an interactive exec instance with a TTY and returns its id.
+    pub async fn create_exec(&self, id: &str, cmd: Vec<String>, user: Option<String>, working_dir: Option<String>) -> Result<String> {
+        let options = CreateExecOptions {
+            attach_stdin: Some(true),
+            attach_stdout: Some(true),
+            attach_stderr: Some(true),
+            tty: Some(true),
+            env: Some(vec!["TERM=xterm-256color".to_string()]),
+            cmd: Some(cmd),
+            user,
+            working_dir,
+            ..Default::default()
+        };
+        let response = self.client.create_exec(id, options).await?;
+        Ok(response.id)
+    }
+
+    /// Starts an exec instance attached, returning its output stream and stdin.
+    pub async fn start_exec(&self, exec_id: &str) -> Result<(
+        impl futures::Stream<Item = Result<LogOutput, bollard::errors::Error>>,
+        Pin<Box<dyn AsyncWrite + Send>>,
+    )> {
+        let options = Some(StartExecOptions {
+            detach: false,
+            tty: true,
+            output_capacity: None,
+        });
+        match self.client.start_exec(exec_id, options).await? {
+            StartExecResults::Attached { output, input } => Ok((output, input)),
+            StartExecResults::Detached => Err(anyhow!("Exec instance started detached")),
+        }
+    }
+
+    pub async fn resize_exec(&self, exec_id: &str, rows: u16, cols: u16) -> Result<()> {
+        self.client
+            .resize_exec(exec_id, ResizeExecOptions { height: rows, width: cols })
+            .await?;
+        Ok(())
+    }
+
+    /// Exit code of a finished exec instance, `None` while it is still running.
+    pub async fn exec_exit_code(&self, exec_id: &str) -> Result<Option<i64>> {
+        let inspect = self.client.inspect_exec(exec_id).await?;
+        Ok(inspect.exit_code)
+    }
+
     // --- Image Methods ---
 
     pub async fn list_images(&self) -> Result<Vec<bollard::service::ImageSummary>> {
@@ -81,26 +378,167 @@ impl DockerService {
         Ok(images)
     }
 
-    pub async fn pull_image(&self, image: &str) -> Result<()> {
-        let mut stream = self.client.create_image(
-            Some(bollard::image::CreateImageOptions {
-                from_image: image.to_string(),
-                ..Default::default()
-            }),
-            None,
-            None,
-        );
+    /// Pulls an image, yielding Docker's progress lines as they arrive.
+    /// Dropping the stream aborts the pull.
+    pub fn pull_image_stream(
+        &self,
+        image: &str,
+        credentials: Option<DockerCredentials>,
+    ) -> impl futures::Stream<Item = Result<ImageProgress>> {
+        let options = CreateImageOptions {
+            from_image: with_default_tag(image),
+            ..Default::default()
+        };
+        self.client.create_image(Some(options), None, credentials).map(|item| {
+            let info = item?;
+            if let Some(error) = info.error {
+                return Err(anyhow!(error));
+            }
+            let detail = info.progress_detail.unwrap_or_default();
+            Ok(ImageProgress {
+                id: info.id,
+                status: info.status.unwrap_or_default(),
+                current: detail.current,
+                total: detail.total,
+            })
+        })
+    }
+
+    pub async fn pull_image(&self, image: &str, credentials: Option<DockerCredentials>) -> Result<()> {
+        let stream = self.pull_image_stream(image, credentials);
+        futures::pin_mut!(stream);
         while let Some(item) = stream.next().await {
             item?;
         }
         Ok(())
     }
 
+    pub async fn inspect_image(&self, id: &str) -> Result<bollard::service::ImageInspect> {
+        let image = self.client.inspect_image(id).await?;
+        Ok(image)
+    }
+
+    /// The image's layers, newest first.
+    pub async fn image_history(&self, id: &str) -> Result<Vec<bollard::service::HistoryResponseItem>> {
+        let history = self.client.image_history(id).await?;
+        Ok(history)
+    }
+
+    pub async fn tag_image(&self, id: &str, repo: &str, tag: &str) -> Result<()> {
+        let options = TagImageOptions { repo, tag };
+        self.client.tag_image(id, Some(options)).await?;
+        Ok(())
+    }
+
+    /// Pushes one tag of an image, yielding progress lines like
+    /// [`Self::pull_image_stream`]. Dropping the stream aborts the push.
+    pub fn push_image_stream(
+        &self,
+        image: &str,
+        credentials: Option<DockerCredentials>,
+    ) -> impl futures::Stream<Item = Result<ImageProgress>> {
+        let (repo, tag) = split_tag(image);
+        let options = PushImageOptions { tag: tag.to_string() };
+        self.client.push_image(repo, Some(options), credentials).map(|item| {
+            let info = item?;
+            if let Some(error) = info.error {
+                return Err(anyhow!(error));
+            }
+            let detail = info.progress_detail.unwrap_or_default();
+            Ok(ImageProgress {
+                id: None,
+                status: info.status.unwrap_or_default(),
+                current: detail.current,
+                total: detail.total,
+            })
+        })
+    }
+
+    /// Builds an image from a tar context, streaming its output. Dropping
+    /// the stream cancels the build.
+    pub fn build_image(
+        &self,
+        options: BuildImageOptions<String>,
+        credentials: HashMap<String, DockerCredentials>,
+        context: Vec<u8>,
+    ) -> impl futures::Stream<Item = Result<BuildOutput>> + '_ {
+        self.client
+            .build_image(options, Some(credentials), Some(context.into()))
+            .map(|item| {
+                let info = item?;
+                if let Some(error) = info.error {
+                    return Err(anyhow!(error));
+                }
+                if let Some(id) = info.aux.and_then(|aux| aux.id) {
+                    return Ok(Some(BuildOutput::ImageId(id)));
+                }
+                if let Some(log) = info.stream {
+                    return Ok(Some(BuildOutput::Log(log)));
+                }
+                Ok(info.status.map(|status| {
+                    let detail = info.progress_detail.unwrap_or_default();
+                    BuildOutput::Progress(ImageProgress {
+                        id: info.id,
+                        status,
+                        current: detail.current,
+                        total: detail.total,
+                    })
+                }))
+            })
+            .filter_map(|item| async move { item.transpose() })
+    }
+
+    /// Streams a tar archive of the images, like `docker save`.
+    pub fn export_images(&self, names: &[&str]) -> impl futures::Stream<Item = Result<Bytes, bollard::errors::Error>> {
+        self.client.export_images(names)
+    }
+
+    /// Loads images from a tar archive, like `docker load`, forwarding the
+    /// archive to Docker as it arrives.
+    pub async fn import_images(&self, archive: impl futures::Stream<Item = Bytes> + Send + 'static) -> Result<LoadedImages> {
+        let options = ImportImageOptions { quiet: true };
+        let mut output = self.client.import_image_stream(options, archive, None);
+        let mut loaded = LoadedImages::default();
+        while let Some(item) = output.next().await {
+            let info = item?;
+            if let Some(error) = info.error {
+                return Err(anyhow!(error));
+            }
+            for line in info.stream.as_deref().unwrap_or_default().lines() {
+                if let Some(id) = line.strip_prefix("Loaded image ID: ") {
+                    loaded.image_ids.push(id.trim().to_string());
+                } else if let Some(tag) = line.strip_prefix("Loaded image: ") {
+                    loaded.tags.push(tag.trim().to_string());
+                }
+            }
+        }
+        Ok(loaded)
+    }
+
     pub async fn remove_image(&self, id: &str) -> Result<()> {
         self.client.remove_image(id, None::<RemoveImageOptions>, None).await?;
         Ok(())
     }
 
+    /// Removes an image and all of its tags. Callers must make sure no
+    /// container uses it.
+    pub async fn remove_unused_image(&self, id: &str) -> Result<()> {
+        let options = RemoveImageOptions {
+            force: true,
+            ..Default::default()
+        };
+        self.client.remove_image(id, Some(options), None).await?;
+        Ok(())
+    }
+
+    // --- System Methods ---
+
+    /// Disk usage of images, containers, volumes and build cache. Slow on
+    /// hosts with many containers, since each writable layer is measured.
+    pub async fn data_usage(&self) -> Result<SystemDataUsageResponse> {
+        Ok(self.client.df().await?)
+    }
+
     // --- Network Methods ---
     pub async fn list_networks(&self) -> Result<Vec<bollard::service::Network>> {
         let networks = self.client.list_networks(Some(ListNetworksOptions::<String>::default())).await?;
@@ -112,6 +550,15 @@ impl DockerService {
         Ok(response)
     }
 
+    pub async fn connect_network(&self, network: &str, container: &str, endpoint: EndpointSettings) -> Result<()> {
+        let options = ConnectNetworkOptions {
+            container,
+            endpoint_config: endpoint,
+        };
+        self.client.connect_network(network, options).await?;
+        Ok(())
+    }
+
     pub async fn remove_network(&self, id: &str) -> Result<()> {
         self.client.remove_network(id).await?;
         Ok(())
@@ -133,3 +580,24 @@ impl DockerService {
         Ok(())
     }
 }
+
+/// Appends `:latest` to untagged references; with an empty tag Docker would
+/// pull every tag of the repository.
+fn with_default_tag(image: &str) -> String {
+    let name = image.rsplit('/').next().unwrap_or(image);
+    if name.contains(':') || name.contains('@') {
+        image.to_string()
+    } else {
+        format!("{}:latest", image)
+    }
+}
+
+/// Splits a reference into repository and tag, defaulting to `latest`. A
+/// colon before the last slash belongs to a registry port.
+pub fn split_tag(image: &str) -> (&str, &str) {
+    let name_start = image.rfind('/').map_or(0, |i| i + 1);
+    match image[name_start..].rfind(':') {
+        Some(i) => (&image[..name_start + i], &image[name_start + i + 1..]),
+        None => (image, "latest"),
+    }
+}
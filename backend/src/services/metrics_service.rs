@@ -0,0 +1,471 @@
+use crate::config::HistoryConfig;
+use crate::db::Database;
+use crate::services::docker_service::DockerService;
+use crate::services::system_service::SystemService;
+use anyhow::Result;
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::QueryBuilder;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Host metrics, stored with an empty scope.
+pub const HOST_METRICS: &[&str] = &[
+    "host.cpu_percent",
+    "host.memory_percent",
+    "host.memory_used",
+    "host.disk_percent",
+    "host.network_rx",
+    "host.network_tx",
+];
+
+/// Per-container metrics, scoped by container name so history survives
+/// the container being recreated.
+pub const CONTAINER_METRICS: &[&str] = &[
+    "container.cpu_percent",
+    "container.memory_usage",
+    "container.memory_percent",
+    "container.network_rx",
+    "container.network_tx",
+    "container.block_read",
+    "container.block_write",
+    "container.pids",
+];
+
+/// Rows inserted per statement, well below SQLite's bind parameter limit.
+const INSERT_BATCH: usize = 500;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Resolution {
+    Raw,
+    Minute,
+    Hour,
+}
+
+impl Resolution {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "raw" => Some(Resolution::Raw),
+            "1m" => Some(Resolution::Minute),
+            "1h" => Some(Resolution::Hour),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Resolution::Raw => "raw",
+            Resolution::Minute => "1m",
+            Resolution::Hour => "1h",
+        }
+    }
+
+    /// Bucket size in seconds, stored in the `resolution` column.
+    fn seconds(&self) -> i64 {
+        match self {
+            Resolution::Raw => 0,
+            Resolution::Minute => 60,
+            Resolution::Hour => 3600,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct MetricPoint {
+    /// Unix seconds; the start of the bucket for rolled-up data.
+    pub timestamp: i64,
+    pub avg: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+#[derive(Serialize)]
+pub struct MetricSeries {
+    /// Container name, absent for host metrics.
+    pub container: Option<String>,
+    pub points: Vec<MetricPoint>,
+}
+
+#[derive(Serialize)]
+pub struct MetricQuery {
+    pub metric: String,
+    pub resolution: &'static str,
+    pub from: i64,
+    pub to: i64,
+    pub series: Vec<MetricSeries>,
+}
+
+/// Cumulative counters from the previous sample, used to turn network and
+/// block IO totals into per-second rates.
+#[derive(Default)]
+struct Counters {
+    timestamp: i64,
+    host: (u64, u64),
+    containers: HashMap<String, [u64; 4]>,
+}
+
+pub struct MetricsService {
+    db: Arc<Database>,
+    docker: Arc<DockerService>,
+    system: Arc<SystemService>,
+    config: HistoryConfig,
+}
+
+impl MetricsService {
+    pub fn new(db: Arc<Database>, docker: Arc<DockerService>, system: Arc<SystemService>, config: HistoryConfig) -> Self {
+        Self { db, docker, system, config }
+    }
+
+    /// Samples every `interval_seconds` and rolls up and prunes once a minute.
+    /// Runs until the process exits.
+    pub async fn run(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(Duration::from_secs(self.config.interval_seconds));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut counters = Counters::default();
+        let mut last_rollup: Option<i64> = None;
+
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.collect(&mut counters).await {
+                tracing::warn!("Failed to collect metrics: {}", e);
+            }
+
+            let now = Utc::now().timestamp();
+            let minute = now - now % 60;
+            if last_rollup == Some(minute) {
+                continue;
+            }
+            // The first pass catches up on anything missed while stopped
+            if let Err(e) = self.rollup(now, last_rollup.is_none()).await {
+                tracing::warn!("Failed to roll up metrics: {}", e);
+            }
+            last_rollup = Some(minute);
+        }
+    }
+
+    async fn collect(&self, counters: &mut Counters) -> Result<()> {
+        let now = Utc::now().timestamp();
+        let elapsed = now - counters.timestamp;
+        let rate = |current: u64, previous: u64| {
+            current.saturating_sub(previous) as f64 / elapsed.max(1) as f64
+        };
+        let mut samples: Vec<(&'static str, String, f64)> = Vec::new();
+
+        let host = self.system.get_stats();
+        samples.push(("host.cpu_percent", String::new(), host.cpu_usage as f64));
+        samples.push(("host.memory_percent", String::new(), host.memory_percent()));
+        samples.push(("host.memory_used", String::new(), host.memory_used as f64));
+        samples.push(("host.disk_percent", String::new(), host.disk_percent()));
+        let (rx, tx) = host.network_totals();
+        if counters.timestamp > 0 {
+            samples.push(("host.network_rx", String::new(), rate(rx, counters.host.0)));
+            samples.push(("host.network_tx", String::new(), rate(tx, counters.host.1)));
+        }
+        counters.host = (rx, tx);
+
+        // A Docker outage shouldn't stop host metrics from being recorded
+        let containers = match self.docker.running_container_stats().await {
+            Ok(containers) => containers,
+            Err(e) => {
+                tracing::debug!("Skipping container metrics: {}", e);
+                Vec::new()
+            }
+        };
+
+        let mut current = HashMap::new();
+        for c in containers {
+            samples.push(("container.cpu_percent", c.name.clone(), c.cpu_percent));
+            samples.push(("container.memory_usage", c.name.clone(), c.memory_usage as f64));
+            samples.push(("container.memory_percent", c.name.clone(), c.memory_percent));
+            samples.push(("container.pids", c.name.clone(), c.pids as f64));
+
+            let totals = [c.network_rx, c.network_tx, c.block_read, c.block_write];
+            // Counters reset when a container restarts; skip that interval
+            if let Some(previous) = counters.containers.get(&c.name).filter(|p| totals.iter().zip(p.iter()).all(|(t, p)| t >= p)) {
+                samples.push(("container.network_rx", c.name.clone(), rate(totals[0], previous[0])));
+                samples.push(("container.network_tx", c.name.clone(), rate(totals[1], previous[1])));
+                samples.push(("container.block_read", c.name.clone(), rate(totals[2], previous[2])));
+                samples.push(("container.block_write", c.name.clone(), rate(totals[3], previous[3])));
+            }
+            current.insert(c.name, totals);
+        }
+        counters.containers = current;
+        counters.timestamp = now;
+
+        let mut tx = self.db.pool.begin().await?;
+        for batch in samples.chunks(INSERT_BATCH) {
+            let mut insert = QueryBuilder::new(
+                "INSERT OR REPLACE INTO metrics (resolution, metric, scope, timestamp, avg, min, max, samples) ",
+            );
+            insert.push_values(batch, |mut row, (metric, scope, value)| {
+                row.push_bind(Resolution::Raw.seconds())
+                    .push_bind(*metric)
+                    .push_bind(scope.as_str())
+                    .push_bind(now)
+                    .push_bind(value)
+                    .push_bind(value)
+                    .push_bind(value)
+                    .push_bind(1_i64);
+            });
+            insert.build().execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn rollup(&self, now: i64, catch_up: bool) -> Result<()> {
+        let raw_retention = self.config.raw_retention_hours as i64 * 3600;
+        let minute_retention = self.config.minute_retention_days as i64 * 86400;
+        let hour_retention = self.config.hour_retention_days as i64 * 86400;
+
+        let (raw_lookback, minute_lookback) = if catch_up {
+            (raw_retention, minute_retention)
+        } else {
+            (2 * 60, 2 * 3600)
+        };
+        self.rollup_into(Resolution::Raw, Resolution::Minute, now, raw_lookback).await?;
+        self.rollup_into(Resolution::Minute, Resolution::Hour, now, minute_lookback).await?;
+
+        for (resolution, retention) in [
+            (Resolution::Raw, raw_retention),
+            (Resolution::Minute, minute_retention),
+            (Resolution::Hour, hour_retention),
+        ] {
+            sqlx::query("DELETE FROM metrics WHERE resolution = ? AND timestamp < ?")
+                .bind(resolution.seconds())
+                .bind(now - retention)
+                .execute(&self.db.pool)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Recomputes the completed buckets of `to` within the lookback window.
+    /// The bucket in progress is left alone until it has ended.
+    async fn rollup_into(&self, from: Resolution, to: Resolution, now: i64, lookback: i64) -> Result<()> {
+        let size = to.seconds();
+        let end = now - now % size;
+        let start = end - lookback;
+
+        sqlx::query(
+            "INSERT OR REPLACE INTO metrics (resolution, metric, scope, timestamp, avg, min, max, samples)
+             SELECT ?, metric, scope, timestamp - timestamp % ?, SUM(avg * samples) / SUM(samples), MIN(min), MAX(max), SUM(samples)
+             FROM metrics
+             WHERE resolution = ? AND timestamp >= ? AND timestamp < ?
+             GROUP BY metric, scope, timestamp - timestamp % ?",
+        )
+        .bind(size)
+        .bind(size)
+        .bind(from.seconds())
+        .bind(start)
+        .bind(end)
+        .bind(size)
+        .execute(&self.db.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Picks the finest resolution that still holds data for `from` and
+    /// keeps the number of points reasonable for a chart.
+    pub fn auto_resolution(&self, from: i64, to: i64) -> Resolution {
+        self.resolution_at(from, to, Utc::now().timestamp())
+    }
+
+    fn resolution_at(&self, from: i64, to: i64, now: i64) -> Resolution {
+        let span = to - from;
+        let raw_since = now - self.config.raw_retention_hours as i64 * 3600;
+        let minute_since = now - self.config.minute_retention_days as i64 * 86400;
+
+        if span <= 6 * 3600 && from >= raw_since {
+            Resolution::Raw
+        } else if span <= 7 * 86400 && from >= minute_since {
+            Resolution::Minute
+        } else {
+            Resolution::Hour
+        }
+    }
+
+    /// Returns one series per scope. Container metrics cover every
+    /// container unless one is named.
+    pub async fn query(
+        &self,
+        metric: &str,
+        container: Option<&str>,
+        from: i64,
+        to: i64,
+        resolution: Resolution,
+    ) -> Result<MetricQuery> {
+        let host = HOST_METRICS.contains(&metric);
+
+        let mut select = QueryBuilder::new("SELECT scope, timestamp, avg, min, max FROM metrics WHERE resolution = ");
+        select.push_bind(resolution.seconds());
+        select.push(" AND metric = ").push_bind(metric.to_string());
+        select.push(" AND timestamp >= ").push_bind(from);
+        select.push(" AND timestamp <= ").push_bind(to);
+        if let Some(container) = container.filter(|_| !host) {
+            select.push(" AND scope = ").push_bind(container.trim_start_matches('/').to_string());
+        }
+        select.push(" ORDER BY scope, timestamp");
+
+        let rows: Vec<(String, i64, f64, f64, f64)> = select.build_query_as().fetch_all(&self.db.pool).await?;
+
+        let mut series: Vec<MetricSeries> = Vec::new();
+        for (scope, timestamp, avg, min, max) in rows {
+            let container = (!host).then_some(scope);
+            if series.last().is_none_or(|s| s.container != container) {
+                series.push(MetricSeries { container, points: Vec::new() });
+            }
+            if let Some(s) = series.last_mut() {
+                s.points.push(MetricPoint { timestamp, avg, min, max });
+            }
+        }
+
+        Ok(MetricQuery {
+            metric: metric.to_string(),
+            resolution: resolution.as_str(),
+            from,
+            to,
+            series,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DockerConfig;
+
+    /// A whole hour, so minute and hour buckets line up with it.
+    const NOW: i64 = 1_700_000_000 - 1_700_000_000 % 3600;
+
+    async fn service(raw_retention_hours: u64, minute_retention_days: u64, hour_retention_days: u64) -> MetricsService {
+        let docker = DockerConfig {
+            socket_path: String::new(),
+            remote_host: Some("http://127.0.0.1:1".to_string()),
+            build_context_roots: Vec::new(),
+            max_build_context_bytes: 1,
+        };
+        MetricsService::new(
+            Arc::new(Database::in_memory().await),
+            Arc::new(DockerService::new(&docker).unwrap()),
+            Arc::new(SystemService::new()),
+            HistoryConfig {
+                enabled: true,
+                interval_seconds: 15,
+                raw_retention_hours,
+                minute_retention_days,
+                hour_retention_days,
+            },
+        )
+    }
+
+    async fn insert(service: &MetricsService, resolution: Resolution, scope: &str, timestamp: i64, value: f64) {
+        sqlx::query(
+            "INSERT INTO metrics (resolution, metric, scope, timestamp, avg, min, max, samples) VALUES (?, ?, ?, ?, ?, ?, ?, 1)",
+        )
+        .bind(resolution.seconds())
+        .bind("container.cpu_percent")
+        .bind(scope)
+        .bind(timestamp)
+        .bind(value)
+        .bind(value)
+        .bind(value)
+        .execute(&service.db.pool)
+        .await
+        .unwrap();
+    }
+
+    /// `(timestamp, avg, min, max, samples)` rows at one resolution.
+    async fn rows(service: &MetricsService, resolution: Resolution) -> Vec<(i64, f64, f64, f64, i64)> {
+        sqlx::query_as("SELECT timestamp, avg, min, max, samples FROM metrics WHERE resolution = ? ORDER BY scope, timestamp")
+            .bind(resolution.seconds())
+            .fetch_all(&service.db.pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn rollup_averages_completed_buckets() {
+        let service = service(24, 7, 90).await;
+        let hour = NOW - 3600;
+        for (offset, value) in [(0, 1.0), (15, 2.0), (30, 6.0), (60, 10.0)] {
+            insert(&service, Resolution::Raw, "web", hour + offset, value).await;
+        }
+        // Still in progress at NOW + 30, so not rolled up yet
+        insert(&service, Resolution::Raw, "web", NOW + 15, 50.0).await;
+
+        service.rollup(NOW + 30, true).await.unwrap();
+        assert_eq!(
+            rows(&service, Resolution::Minute).await,
+            [(hour, 3.0, 1.0, 6.0, 3), (hour + 60, 10.0, 10.0, 10.0, 1)]
+        );
+        // Weighted by samples, not by minute
+        assert_eq!(rows(&service, Resolution::Hour).await, [(hour, 4.75, 1.0, 10.0, 4)]);
+
+        // Running again replaces the buckets instead of adding to them
+        service.rollup(NOW + 45, false).await.unwrap();
+        service.rollup(NOW + 45, true).await.unwrap();
+        assert_eq!(rows(&service, Resolution::Minute).await.len(), 2);
+        assert_eq!(rows(&service, Resolution::Hour).await, [(hour, 4.75, 1.0, 10.0, 4)]);
+        assert_eq!(rows(&service, Resolution::Raw).await.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn retention_deletes_only_expired_rows() {
+        let service = service(1, 1, 2).await;
+        for (resolution, retention) in [
+            (Resolution::Raw, 3600),
+            (Resolution::Minute, 86400),
+            (Resolution::Hour, 2 * 86400),
+        ] {
+            insert(&service, resolution, "web", NOW - retention - 60, 1.0).await;
+            insert(&service, resolution, "web", NOW - retention, 2.0).await;
+        }
+
+        // Without catching up, nothing this old is rolled up again
+        service.rollup(NOW, false).await.unwrap();
+        assert_eq!(rows(&service, Resolution::Raw).await, [(NOW - 3600, 2.0, 2.0, 2.0, 1)]);
+        assert_eq!(rows(&service, Resolution::Minute).await, [(NOW - 86400, 2.0, 2.0, 2.0, 1)]);
+        assert_eq!(rows(&service, Resolution::Hour).await, [(NOW - 2 * 86400, 2.0, 2.0, 2.0, 1)]);
+    }
+
+    #[tokio::test]
+    async fn ranges_use_the_finest_resolution_still_retained() {
+        let service = service(24, 7, 90).await;
+        let at = |from: i64, to: i64| service.resolution_at(from, to, NOW);
+
+        assert_eq!(at(NOW - 3600, NOW), Resolution::Raw);
+        assert_eq!(at(NOW - 6 * 3600, NOW), Resolution::Raw);
+        // Too many points for raw data
+        assert_eq!(at(NOW - 12 * 3600, NOW), Resolution::Minute);
+        // Raw data is gone, even for a short range
+        assert_eq!(at(NOW - 30 * 3600, NOW - 29 * 3600), Resolution::Minute);
+        assert_eq!(at(NOW - 7 * 86400, NOW), Resolution::Minute);
+        assert_eq!(at(NOW - 30 * 86400, NOW), Resolution::Hour);
+        assert_eq!(at(NOW - 8 * 86400, NOW - 7 * 86400 - 3600), Resolution::Hour);
+    }
+
+    #[tokio::test]
+    async fn query_reads_one_resolution_and_splits_series_by_container() {
+        let service = service(24, 7, 90).await;
+        insert(&service, Resolution::Raw, "web", NOW - 30, 1.0).await;
+        insert(&service, Resolution::Raw, "db", NOW - 30, 2.0).await;
+        insert(&service, Resolution::Raw, "db", NOW - 15, 3.0).await;
+        insert(&service, Resolution::Raw, "db", NOW - 7200, 4.0).await;
+        insert(&service, Resolution::Minute, "db", NOW - 60, 5.0).await;
+
+        let result = service.query("container.cpu_percent", None, NOW - 3600, NOW, Resolution::Raw).await.unwrap();
+        assert_eq!(result.resolution, "raw");
+        let series: Vec<(Option<&str>, Vec<f64>)> = result
+            .series
+            .iter()
+            .map(|s| (s.container.as_deref(), s.points.iter().map(|p| p.avg).collect()))
+            .collect();
+        assert_eq!(series, [(Some("db"), vec![2.0, 3.0]), (Some("web"), vec![1.0])]);
+
+        let result = service.query("container.cpu_percent", Some("/db"), NOW - 3600, NOW, Resolution::Minute).await.unwrap();
+        assert_eq!(result.series.len(), 1);
+        assert_eq!(result.series[0].points[0].avg, 5.0);
+    }
+}
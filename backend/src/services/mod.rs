@@ -2,3 +2,19 @@ pub mod docker_service;
 pub mod system_service;
 pub mod compose_service;
 pub mod settings_service;
+pub mod audit_service;
+pub mod auth_service;
+pub mod user_service;
+pub mod metrics_service;
+pub mod alert_service;
+pub mod notification_service;
+pub mod notifiers;
+pub mod net;
+pub mod cipher;
+pub mod registry_service;
+pub mod build_service;
+pub mod prune_service;
+pub mod disk_usage_service;
+pub mod recreate_service;
+pub mod update_service;
+pub mod resource_service;
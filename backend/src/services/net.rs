@@ -0,0 +1,258 @@
+//! Minimal HTTP and TLS client plumbing shared by the notifiers and the
+//! registry client.
+
+use anyhow::{anyhow, bail, Context, Result};
+use axum::http::{header, HeaderMap, Method, Request, StatusCode, Uri};
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper_util::rt::TokioIo;
+use rustls::pki_types::{Der, ServerName, TrustAnchor};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A plain or TLS stream.
+pub trait Connection: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Connection for T {}
+
+pub async fn connect(host: &str, port: u16) -> Result<TcpStream> {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect((host, port)))
+        .await
+        .map_err(|_| anyhow!("Timed out connecting to {}:{}", host, port))?
+        .with_context(|| format!("Failed to connect to {}:{}", host, port))
+}
+
+/// Wraps a connected stream in TLS, verifying the server against the
+/// bundled Mozilla root certificates.
+pub async fn tls<S: Connection + 'static>(host: &str, stream: S) -> Result<Box<dyn Connection>> {
+    let mut roots = rustls::RootCertStore::empty();
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| TrustAnchor {
+        subject: Der::from_slice(ta.subject),
+        subject_public_key_info: Der::from_slice(ta.spki),
+        name_constraints: ta.name_constraints.map(Der::from_slice),
+    }));
+
+    let config = rustls::ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+
+    let name = ServerName::try_from(host.trim_start_matches('[').trim_end_matches(']').to_string())
+        .map_err(|_| anyhow!("Invalid TLS server name {:?}", host))?;
+    let stream = TlsConnector::from(Arc::new(config))
+        .connect(name, stream)
+        .await
+        .with_context(|| format!("TLS handshake with {} failed", host))?;
+    Ok(Box::new(stream))
+}
+
+pub struct Response {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl Response {
+    /// The start of the body, for error messages.
+    pub fn snippet(&self) -> String {
+        String::from_utf8_lossy(&self.body).chars().take(200).collect()
+    }
+}
+
+/// Sends a request over a fresh connection and reads the whole response.
+pub async fn send(method: Method, url: &str, headers: &[(&str, String)], body: Vec<u8>) -> Result<Response> {
+    let uri: Uri = url.parse().with_context(|| format!("Invalid URL {:?}", url))?;
+    let host = uri.host().ok_or_else(|| anyhow!("URL {:?} has no host", url))?.to_string();
+    let https = match uri.scheme_str() {
+        Some("https") => true,
+        Some("http") => false,
+        _ => bail!("URL must start with http:// or https://"),
+    };
+    let port = uri.port_u16().unwrap_or(if https { 443 } else { 80 });
+
+    let tcp = connect(&host, port).await?;
+    let stream: Box<dyn Connection> = if https { tls(&host, tcp).await? } else { Box::new(tcp) };
+
+    let (mut sender, conn) = hyper::client::conn::http1::handshake(TokioIo::new(stream)).await?;
+    tokio::spawn(async move {
+        let _ = conn.await;
+    });
+
+    let authority = uri.authority().map(|a| a.as_str()).unwrap_or(&host).to_string();
+    let path = uri.path_and_query().map(|pq| pq.as_str()).unwrap_or("/");
+    let mut request = Request::builder()
+        .method(method)
+        .uri(path)
+        .header(header::HOST, authority)
+        .header(header::USER_AGENT, concat!("Dockium/", env!("CARGO_PKG_VERSION")));
+    for (name, value) in headers {
+        request = request.header(*name, value);
+    }
+    let request = request.body(Full::new(Bytes::from(body)))?;
+
+    tokio::time::timeout(REQUEST_TIMEOUT, async {
+        let response = sender.send_request(request).await?;
+        let (parts, body) = response.into_parts();
+        let body = body.collect().await?.to_bytes();
+        Ok::<_, anyhow::Error>(Response { status: parts.status, headers: parts.headers, body })
+    })
+    .await
+    .map_err(|_| anyhow!("Timed out waiting for {}", host))?
+}
+
+/// POSTs a JSON body and fails unless the response status is 2xx.
+pub async fn post_json(url: &str, headers: &[(&str, String)], body: Vec<u8>) -> Result<()> {
+    let mut headers = headers.to_vec();
+    headers.push((header::CONTENT_TYPE.as_str(), "application/json".into()));
+    let response = send(Method::POST, url, &headers, body).await?;
+    if !response.status.is_success() {
+        let host = url.parse::<Uri>().ok().and_then(|uri| uri.host().map(str::to_string)).unwrap_or_default();
+        bail!("{} responded with {}: {}", host, response.status, response.snippet());
+    }
+    Ok(())
+}
+
+/// A local HTTP server standing in for webhook receivers and registries.
+#[cfg(test)]
+pub mod mock {
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+
+    /// A request as it arrived on the wire.
+    pub struct Received {
+        /// Request line and headers, without the blank line.
+        pub head: String,
+        pub body: Vec<u8>,
+    }
+
+    impl Received {
+        pub fn request_line(&self) -> &str {
+            self.head.lines().next().unwrap_or_default()
+        }
+
+        pub fn header(&self, name: &str) -> Option<&str> {
+            self.head.lines().skip(1).find_map(|line| {
+                let (key, value) = line.split_once(':')?;
+                key.eq_ignore_ascii_case(name).then(|| value.trim())
+            })
+        }
+    }
+
+    /// Builds a raw response with a `Content-Length` and `Connection: close`.
+    pub fn response(status: &str, headers: &[(&str, &str)], body: &str) -> Vec<u8> {
+        let mut response = format!("HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n", status, body.len());
+        for (name, value) in headers {
+            response.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        response.push_str("\r\n");
+        response.push_str(body);
+        response.into_bytes()
+    }
+
+    /// Answers one request per connection with whatever `handler` returns,
+    /// and reports each request received.
+    pub async fn serve<F>(handler: F) -> (SocketAddr, mpsc::UnboundedReceiver<Received>)
+    where
+        F: Fn(&Received) -> Vec<u8> + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = mpsc::unbounded_channel();
+        let handler = Arc::new(handler);
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let (tx, handler) = (tx.clone(), handler.clone());
+                tokio::spawn(async move {
+                    let mut stream = BufReader::new(stream);
+                    let mut head = String::new();
+                    loop {
+                        let mut line = String::new();
+                        if stream.read_line(&mut line).await.unwrap_or(0) == 0 {
+                            return;
+                        }
+                        if line == "\r\n" {
+                            break;
+                        }
+                        head.push_str(&line);
+                    }
+                    let mut request = Received { head: head.trim_end().to_string(), body: Vec::new() };
+                    let length = request.header("content-length").and_then(|l| l.parse().ok()).unwrap_or(0);
+                    request.body = vec![0; length];
+                    stream.read_exact(&mut request.body).await.unwrap();
+
+                    let response = handler(&request);
+                    let _ = tx.send(request);
+                    let _ = stream.get_mut().write_all(&response).await;
+                    let _ = stream.get_mut().shutdown().await;
+                });
+            }
+        });
+
+        (addr, rx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock::{self, response};
+    use super::*;
+
+    #[tokio::test]
+    async fn send_writes_the_request_and_reads_the_response() {
+        let (addr, mut received) = mock::serve(|_| response("201 Created", &[("X-Reply", "yes")], "created")).await;
+
+        let url = format!("http://{}/hooks/abc?x=1", addr);
+        let response = send(Method::PUT, &url, &[("X-Custom", "value".into())], b"payload".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(response.status, StatusCode::CREATED);
+        assert_eq!(response.headers["x-reply"], "yes");
+        assert_eq!(&response.body[..], b"created");
+
+        let request = received.recv().await.unwrap();
+        assert_eq!(request.request_line(), "PUT /hooks/abc?x=1 HTTP/1.1");
+        assert_eq!(request.header("host"), Some(addr.to_string().as_str()));
+        assert_eq!(request.header("x-custom"), Some("value"));
+        assert!(request.header("user-agent").unwrap().starts_with("Dockium/"));
+        assert_eq!(request.header("content-length"), Some("7"));
+        assert_eq!(request.body, b"payload");
+    }
+
+    #[tokio::test]
+    async fn post_json_sets_the_content_type() {
+        let (addr, mut received) = mock::serve(|_| response("204 No Content", &[], "")).await;
+
+        post_json(&format!("http://{}/", addr), &[], br#"{"a":1}"#.to_vec()).await.unwrap();
+
+        let request = received.recv().await.unwrap();
+        assert_eq!(request.request_line(), "POST / HTTP/1.1");
+        assert_eq!(request.header("content-type"), Some("application/json"));
+        assert_eq!(request.body, br#"{"a":1}"#);
+    }
+
+    #[tokio::test]
+    async fn post_json_fails_on_error_status_with_the_body() {
+        let (addr, _received) = mock::serve(|_| response("400 Bad Request", &[], "invalid_payload")).await;
+
+        let error = post_json(&format!("http://{}/", addr), &[], b"{}".to_vec()).await.unwrap_err();
+        let message = error.to_string();
+        assert!(message.contains("400 Bad Request"), "{}", message);
+        assert!(message.contains("invalid_payload"), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn send_rejects_other_schemes() {
+        let result = send(Method::GET, "ftp://127.0.0.1/", &[], Vec::new()).await;
+        assert!(result.is_err_and(|e| e.to_string().contains("http:// or https://")));
+    }
+}
@@ -0,0 +1,455 @@
+use crate::db::Database;
+use crate::services::cipher::Cipher;
+use crate::services::notifiers::{self, Notification, Notifier};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Shown in place of stored secrets; sending it back keeps the stored value.
+pub const REDACTED: &str = "********";
+
+/// Marks secret fields that are stored encrypted. Channels saved before
+/// encryption was added are converted on startup.
+const SEALED_PREFIX: &str = "sealed:";
+
+/// Delivery attempts per notification and channel, including the first.
+const MAX_ATTEMPTS: u32 = 5;
+/// Wait before the first retry, doubled after each failure.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(2);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+pub enum ChannelError {
+    NotFound,
+    NameTaken,
+    InvalidInput(String),
+    /// The channel rejected or couldn't be reached by a test notification.
+    Delivery(String),
+    Internal(anyhow::Error),
+}
+
+impl fmt::Display for ChannelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChannelError::NotFound => write!(f, "Notification channel not found"),
+            ChannelError::NameTaken => write!(f, "A channel with this name already exists"),
+            ChannelError::InvalidInput(msg) => write!(f, "{}", msg),
+            ChannelError::Delivery(msg) => write!(f, "Delivery failed: {}", msg),
+            ChannelError::Internal(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<sqlx::Error> for ChannelError {
+    fn from(e: sqlx::Error) -> Self {
+        ChannelError::Internal(e.into())
+    }
+}
+
+impl From<serde_json::Error> for ChannelError {
+    fn from(e: serde_json::Error) -> Self {
+        ChannelError::Internal(e.into())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, ChannelError>;
+
+#[derive(Serialize)]
+pub struct Channel {
+    pub id: String,
+    pub name: String,
+    /// One of [`notifiers::KINDS`].
+    pub kind: String,
+    /// Kind-specific settings with secrets redacted.
+    pub config: serde_json::Value,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+    pub last_delivered_at: Option<DateTime<Utc>>,
+    /// Error from the most recent delivery that gave up, cleared on success.
+    pub last_error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ChannelInput {
+    pub name: String,
+    pub kind: String,
+    pub config: serde_json::Value,
+    pub enabled: Option<bool>,
+}
+
+#[derive(sqlx::FromRow)]
+struct ChannelRow {
+    id: String,
+    name: String,
+    kind: String,
+    config: String,
+    enabled: bool,
+    created_at: DateTime<Utc>,
+    last_delivered_at: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+}
+
+impl ChannelRow {
+    fn into_channel(self) -> Channel {
+        let mut config: Value = serde_json::from_str(&self.config).unwrap_or_default();
+        if let Some(fields) = config.as_object_mut() {
+            for key in notifiers::secret_fields(&self.kind) {
+                if let Some(value) = fields.get_mut(*key).filter(|v| !v.is_null()) {
+                    *value = REDACTED.into();
+                }
+            }
+        }
+        Channel {
+            id: self.id,
+            name: self.name,
+            kind: self.kind,
+            config,
+            enabled: self.enabled,
+            created_at: self.created_at,
+            last_delivered_at: self.last_delivered_at,
+            last_error: self.last_error,
+        }
+    }
+}
+
+pub struct NotificationService {
+    db: Arc<Database>,
+    cipher: Cipher,
+}
+
+impl NotificationService {
+    pub fn new(db: Arc<Database>, secret: &str) -> Self {
+        Self { db, cipher: Cipher::new(secret, b"notification-channels") }
+    }
+
+    /// Encrypts the secrets of channels saved while they were stored in
+    /// plain text.
+    pub async fn seal_plaintext_secrets(&self) -> Result<()> {
+        let rows: Vec<ChannelRow> = sqlx::query_as("SELECT * FROM notification_channels")
+            .fetch_all(&self.db.pool)
+            .await?;
+        for row in rows {
+            let stored: Value = serde_json::from_str(&row.config)?;
+            let plaintext = notifiers::secret_fields(&row.kind)
+                .iter()
+                .any(|key| matches!(stored.get(*key), Some(Value::String(v)) if !v.starts_with(SEALED_PREFIX)));
+            if !plaintext {
+                continue;
+            }
+            let config = self.seal_config(&row.id, &row.kind, self.open_config(&row.id, &row.kind, &row.config)?);
+            sqlx::query("UPDATE notification_channels SET config = ? WHERE id = ?")
+                .bind(serde_json::to_string(&config)?)
+                .bind(&row.id)
+                .execute(&self.db.pool)
+                .await?;
+        }
+        Ok(())
+    }
+
+    pub async fn list(&self) -> Result<Vec<Channel>> {
+        let rows: Vec<ChannelRow> = sqlx::query_as("SELECT * FROM notification_channels ORDER BY name")
+            .fetch_all(&self.db.pool)
+            .await?;
+        Ok(rows.into_iter().map(ChannelRow::into_channel).collect())
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Channel> {
+        Ok(self.row(id).await?.into_channel())
+    }
+
+    async fn row(&self, id: &str) -> Result<ChannelRow> {
+        let row: Option<ChannelRow> = sqlx::query_as("SELECT * FROM notification_channels WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.db.pool)
+            .await?;
+        row.ok_or(ChannelError::NotFound)
+    }
+
+    pub async fn create(&self, input: ChannelInput) -> Result<Channel> {
+        let name = validate(&input)?;
+        let id = uuid::Uuid::new_v4().to_string();
+
+        let result = sqlx::query(
+            "INSERT INTO notification_channels (id, name, kind, config, enabled, created_at) VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&id)
+        .bind(name)
+        .bind(&input.kind)
+        .bind(serde_json::to_string(&self.seal_config(&id, &input.kind, input.config.clone()))?)
+        .bind(input.enabled.unwrap_or(true))
+        .bind(Utc::now())
+        .execute(&self.db.pool)
+        .await;
+
+        match result {
+            Ok(_) => self.get(&id).await,
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Err(ChannelError::NameTaken),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Replaces the channel's settings. Secret fields sent back as
+    /// [`REDACTED`] keep their stored value.
+    pub async fn update(&self, id: &str, mut input: ChannelInput) -> Result<Channel> {
+        let existing = self.row(id).await?;
+        let stored = self.open_config(id, &existing.kind, &existing.config)?;
+        if let Some(fields) = input.config.as_object_mut() {
+            for key in notifiers::secret_fields(&input.kind) {
+                if fields.get(*key).and_then(|v| v.as_str()) == Some(REDACTED) {
+                    fields.insert(key.to_string(), stored.get(*key).cloned().unwrap_or_default());
+                }
+            }
+        }
+        let name = validate(&input)?;
+
+        let result = sqlx::query("UPDATE notification_channels SET name = ?, kind = ?, config = ?, enabled = ? WHERE id = ?")
+            .bind(name)
+            .bind(&input.kind)
+            .bind(serde_json::to_string(&self.seal_config(id, &input.kind, input.config.clone()))?)
+            .bind(input.enabled.unwrap_or(existing.enabled))
+            .bind(id)
+            .execute(&self.db.pool)
+            .await;
+
+        match result {
+            Ok(_) => self.get(id).await,
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Err(ChannelError::NameTaken),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn delete(&self, id: &str) -> Result<()> {
+        let result = sqlx::query("DELETE FROM notification_channels WHERE id = ?")
+            .bind(id)
+            .execute(&self.db.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(ChannelError::NotFound);
+        }
+        Ok(())
+    }
+
+    /// Sends a test notification with a single attempt, so the result can
+    /// be reported straight back.
+    pub async fn send_test(&self, id: &str, username: &str) -> Result<()> {
+        let row = self.row(id).await?;
+        let notifier = self.notifier(&row)?;
+        let notification = Notification {
+            event: "test".into(),
+            severity: "info".into(),
+            title: "Dockium test notification".into(),
+            message: format!("Test notification for channel {:?}, sent by {}.", row.name, username),
+            alert_id: None,
+            rule: None,
+            subject: None,
+            timestamp: Utc::now(),
+        };
+
+        notifier
+            .send(&notification)
+            .await
+            .map_err(|e| ChannelError::Delivery(format!("{:#}", e)))?;
+        self.record_delivery(id, None).await;
+        Ok(())
+    }
+
+    /// Delivers to every enabled channel in the background, retrying each
+    /// with exponential backoff.
+    pub fn notify(self: &Arc<Self>, notification: Notification) {
+        let service = self.clone();
+        tokio::spawn(async move {
+            let rows: Vec<ChannelRow> = match sqlx::query_as("SELECT * FROM notification_channels WHERE enabled = 1")
+                .fetch_all(&service.db.pool)
+                .await
+            {
+                Ok(rows) => rows,
+                Err(e) => {
+                    tracing::error!("Failed to load notification channels: {}", e);
+                    return;
+                }
+            };
+
+            let notification = Arc::new(notification);
+            for row in rows {
+                let notifier = match service.notifier(&row) {
+                    Ok(notifier) => notifier,
+                    Err(e) => {
+                        tracing::warn!("Skipping notification channel {}: {}", row.name, e);
+                        continue;
+                    }
+                };
+                tokio::spawn(service.clone().deliver(row.id, row.name, notifier, notification.clone()));
+            }
+        });
+    }
+
+    async fn deliver(self: Arc<Self>, id: String, name: String, notifier: Box<dyn Notifier>, notification: Arc<Notification>) {
+        let mut backoff = INITIAL_BACKOFF;
+        for attempt in 1..=MAX_ATTEMPTS {
+            match notifier.send(&notification).await {
+                Ok(()) => {
+                    self.record_delivery(&id, None).await;
+                    return;
+                }
+                Err(e) if attempt == MAX_ATTEMPTS => {
+                    tracing::error!("Giving up on notification channel {} after {} attempts: {:#}", name, attempt, e);
+                    self.record_delivery(&id, Some(format!("{:#}", e))).await;
+                }
+                Err(e) => {
+                    tracing::warn!("Notification channel {} failed (attempt {}), retrying in {:?}: {:#}", name, attempt, backoff, e);
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+            }
+        }
+    }
+
+    fn notifier(&self, row: &ChannelRow) -> Result<Box<dyn Notifier>> {
+        let config = self.open_config(&row.id, &row.kind, &row.config)?;
+        notifiers::from_config(&row.kind, &config).map_err(ChannelError::InvalidInput)
+    }
+
+    /// Encrypts the kind's secret fields for storage, bound to the channel.
+    fn seal_config(&self, id: &str, kind: &str, mut config: Value) -> Value {
+        if let Some(fields) = config.as_object_mut() {
+            for key in notifiers::secret_fields(kind) {
+                if let Some(Value::String(value)) = fields.get_mut(*key) {
+                    *value = format!("{}{}", SEALED_PREFIX, self.cipher.seal(id, value));
+                }
+            }
+        }
+        config
+    }
+
+    /// Parses a stored config and decrypts its secret fields.
+    fn open_config(&self, id: &str, kind: &str, config: &str) -> Result<Value> {
+        let mut config: Value = serde_json::from_str(config)?;
+        if let Some(fields) = config.as_object_mut() {
+            for key in notifiers::secret_fields(kind) {
+                if let Some(Value::String(value)) = fields.get_mut(*key) {
+                    if let Some(sealed) = value.strip_prefix(SEALED_PREFIX) {
+                        *value = self
+                            .cipher
+                            .open(id, sealed, "notification channel secret")
+                            .map_err(ChannelError::Internal)?;
+                    }
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    async fn record_delivery(&self, id: &str, error: Option<String>) {
+        let result = match &error {
+            None => sqlx::query("UPDATE notification_channels SET last_delivered_at = ?, last_error = NULL WHERE id = ?")
+                .bind(Utc::now())
+                .bind(id)
+                .execute(&self.db.pool)
+                .await,
+            Some(error) => sqlx::query("UPDATE notification_channels SET last_error = ? WHERE id = ?")
+                .bind(error)
+                .bind(id)
+                .execute(&self.db.pool)
+                .await,
+        };
+        if let Err(e) = result {
+            tracing::warn!("Failed to record notification delivery: {}", e);
+        }
+    }
+}
+
+/// Checks the name and builds the notifier once to validate the config.
+fn validate(input: &ChannelInput) -> Result<&str> {
+    let name = input.name.trim();
+    if name.is_empty() {
+        return Err(ChannelError::InvalidInput("Name must not be empty".into()));
+    }
+    notifiers::from_config(&input.kind, &input.config).map_err(ChannelError::InvalidInput)?;
+    Ok(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SLACK_URL: &str = "https://hooks.slack.com/services/T000/B000/XXXXXXXX";
+
+    async fn service() -> NotificationService {
+        NotificationService::new(Arc::new(Database::in_memory().await), "0123456789abcdef0123456789abcdef")
+    }
+
+    fn slack(url: &str) -> ChannelInput {
+        ChannelInput {
+            name: "ops".into(),
+            kind: "slack".into(),
+            config: json!({ "url": url }),
+            enabled: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn webhook_urls_are_redacted_and_encrypted_at_rest() {
+        let service = service().await;
+        let channel = service.create(slack(SLACK_URL)).await.unwrap();
+        assert_eq!(channel.config["url"], REDACTED);
+
+        let row = service.row(&channel.id).await.unwrap();
+        assert!(!row.config.contains("hooks.slack.com"));
+        let stored: Value = serde_json::from_str(&row.config).unwrap();
+        assert!(stored["url"].as_str().unwrap().starts_with(SEALED_PREFIX));
+
+        let opened = service.open_config(&row.id, &row.kind, &row.config).unwrap();
+        assert_eq!(opened["url"], SLACK_URL);
+    }
+
+    #[tokio::test]
+    async fn update_keeps_redacted_secrets_and_replaces_new_ones() {
+        let service = service().await;
+        let channel = service.create(slack(SLACK_URL)).await.unwrap();
+
+        service.update(&channel.id, slack(REDACTED)).await.unwrap();
+        let row = service.row(&channel.id).await.unwrap();
+        assert_eq!(service.open_config(&row.id, &row.kind, &row.config).unwrap()["url"], SLACK_URL);
+
+        let replaced = "https://hooks.slack.com/services/T000/B000/YYYYYYYY";
+        service.update(&channel.id, slack(replaced)).await.unwrap();
+        let row = service.row(&channel.id).await.unwrap();
+        assert_eq!(service.open_config(&row.id, &row.kind, &row.config).unwrap()["url"], replaced);
+    }
+
+    #[tokio::test]
+    async fn secrets_are_bound_to_their_channel() {
+        let service = service().await;
+        let first = service.create(slack(SLACK_URL)).await.unwrap();
+        let row = service.row(&first.id).await.unwrap();
+        assert!(service.open_config("another-channel", &row.kind, &row.config).is_err());
+    }
+
+    #[tokio::test]
+    async fn plaintext_secrets_are_sealed_on_startup() {
+        let service = service().await;
+        let config = json!({ "host": "smtp.example.com", "password": "hunter22", "from": "a@example.com", "to": ["b@example.com"] });
+        sqlx::query(
+            "INSERT INTO notification_channels (id, name, kind, config, enabled, created_at) VALUES ('legacy', 'mail', 'email', ?, 1, ?)",
+        )
+        .bind(config.to_string())
+        .bind(Utc::now())
+        .execute(&service.db.pool)
+        .await
+        .unwrap();
+
+        service.seal_plaintext_secrets().await.unwrap();
+        let row = service.row("legacy").await.unwrap();
+        assert!(!row.config.contains("hunter22"));
+        let opened = service.open_config(&row.id, &row.kind, &row.config).unwrap();
+        assert_eq!(opened, config);
+
+        // Already sealed values are left alone
+        service.seal_plaintext_secrets().await.unwrap();
+        assert_eq!(service.row("legacy").await.unwrap().config, row.config);
+    }
+}
@@ -0,0 +1,70 @@
+use super::{validate_url, Notification, Notifier};
+use crate::services::net;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(Deserialize)]
+pub struct ChatConfig {
+    /// Incoming webhook URL.
+    pub url: String,
+}
+
+#[derive(Clone, Copy)]
+pub enum ChatFormat {
+    Slack,
+    Discord,
+    /// Microsoft Teams connector `MessageCard`.
+    Teams,
+}
+
+pub struct ChatNotifier {
+    config: ChatConfig,
+    format: ChatFormat,
+}
+
+impl ChatNotifier {
+    pub fn new(config: ChatConfig, format: ChatFormat) -> Result<Self, String> {
+        validate_url(&config.url)?;
+        Ok(Self { config, format })
+    }
+
+    fn payload(&self, n: &Notification) -> serde_json::Value {
+        let color = n.color();
+        match self.format {
+            ChatFormat::Slack => json!({
+                "text": n.title,
+                "attachments": [{
+                    "color": format!("#{:06x}", color),
+                    "text": n.message,
+                    "ts": n.timestamp.timestamp(),
+                }],
+            }),
+            ChatFormat::Discord => json!({
+                "embeds": [{
+                    "title": n.title,
+                    "description": n.message,
+                    "color": color,
+                    "timestamp": n.timestamp.to_rfc3339(),
+                }],
+            }),
+            ChatFormat::Teams => json!({
+                "@type": "MessageCard",
+                "@context": "http://schema.org/extensions",
+                "themeColor": format!("{:06x}", color),
+                "summary": n.title,
+                "title": n.title,
+                "text": n.message,
+            }),
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for ChatNotifier {
+    async fn send(&self, notification: &Notification) -> Result<()> {
+        let body = serde_json::to_vec(&self.payload(notification))?;
+        net::post_json(&self.config.url, &[], body).await
+    }
+}
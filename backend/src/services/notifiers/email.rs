@@ -0,0 +1,420 @@
+use crate::services::net::{self, Connection};
+use super::{Notification, Notifier};
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+/// Upper bound on a whole SMTP conversation.
+const SMTP_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// Plain connection upgraded with STARTTLS; the upgrade is required.
+    #[default]
+    Starttls,
+    /// TLS from the first byte, usually port 465.
+    Tls,
+    /// No encryption. Only for relays on a trusted network.
+    None,
+}
+
+#[derive(Deserialize)]
+pub struct EmailConfig {
+    pub host: String,
+    /// Defaults to 587, 465 or 25 depending on `security`.
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+pub struct EmailNotifier {
+    config: EmailConfig,
+}
+
+impl EmailNotifier {
+    pub fn new(config: EmailConfig) -> Result<Self, String> {
+        if config.host.trim().is_empty() {
+            return Err("host must not be empty".into());
+        }
+        if config.to.is_empty() {
+            return Err("to must list at least one recipient".into());
+        }
+        for address in std::iter::once(&config.from).chain(&config.to) {
+            validate_address(address)?;
+        }
+        if config.username.is_some() != config.password.is_some() {
+            return Err("username and password must be set together".into());
+        }
+        Ok(Self { config })
+    }
+
+    fn port(&self) -> u16 {
+        self.config.port.unwrap_or(match self.config.security {
+            SmtpSecurity::Starttls => 587,
+            SmtpSecurity::Tls => 465,
+            SmtpSecurity::None => 25,
+        })
+    }
+
+    async fn deliver(&self, notification: &Notification) -> Result<()> {
+        let host = self.config.host.as_str();
+        let tcp = net::connect(host, self.port()).await?;
+        let stream: Box<dyn Connection> = match self.config.security {
+            SmtpSecurity::Tls => net::tls(host, tcp).await?,
+            _ => Box::new(tcp),
+        };
+
+        let mut smtp = Smtp::new(stream);
+        smtp.reply(&[220]).await?;
+        let mut capabilities = smtp.command("EHLO dockium", &[250]).await?;
+
+        if self.config.security == SmtpSecurity::Starttls {
+            if !capabilities.iter().any(|c| c.eq_ignore_ascii_case("STARTTLS")) {
+                bail!("{} does not support STARTTLS", host);
+            }
+            smtp.command("STARTTLS", &[220]).await?;
+            smtp = Smtp::new(net::tls(host, smtp.into_inner()).await?);
+            capabilities = smtp.command("EHLO dockium", &[250]).await?;
+        }
+
+        if let (Some(username), Some(password)) = (&self.config.username, &self.config.password) {
+            let plain = capabilities
+                .iter()
+                .any(|c| c.to_ascii_uppercase().starts_with("AUTH") && c.to_ascii_uppercase().contains("PLAIN"));
+            if plain {
+                let credentials = BASE64.encode(format!("\0{}\0{}", username, password));
+                smtp.command(&format!("AUTH PLAIN {}", credentials), &[235]).await?;
+            } else {
+                smtp.command("AUTH LOGIN", &[334]).await?;
+                smtp.command(&BASE64.encode(username), &[334]).await?;
+                smtp.command(&BASE64.encode(password), &[235]).await?;
+            }
+        }
+
+        smtp.command(&format!("MAIL FROM:<{}>", self.config.from), &[250]).await?;
+        for to in &self.config.to {
+            smtp.command(&format!("RCPT TO:<{}>", to), &[250, 251]).await?;
+        }
+        smtp.command("DATA", &[354]).await?;
+        smtp.command(&format!("{}\r\n.", self.message(notification)), &[250]).await?;
+        let _ = smtp.command("QUIT", &[221]).await;
+        Ok(())
+    }
+
+    /// Builds the message. The body is base64 encoded so no line needs
+    /// dot-stuffing and non-ASCII text survives any relay.
+    fn message(&self, n: &Notification) -> String {
+        let body = format!(
+            "{}\r\n\r\nTime: {}\r\n",
+            n.message.replace('\n', "\r\n"),
+            n.timestamp.to_rfc2822()
+        );
+        let encoded = BASE64.encode(body.as_bytes());
+        let lines: Vec<&str> = encoded
+            .as_bytes()
+            .chunks(76)
+            .map(|chunk| std::str::from_utf8(chunk).unwrap_or_default())
+            .collect();
+
+        format!(
+            "Date: {}\r\nFrom: {}\r\nTo: {}\r\nSubject: {}\r\nMessage-ID: <{}@dockium>\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: base64\r\n\r\n{}",
+            chrono::Utc::now().to_rfc2822(),
+            self.config.from,
+            self.config.to.join(", "),
+            encode_header(&n.title),
+            uuid::Uuid::new_v4(),
+            lines.join("\r\n"),
+        )
+    }
+}
+
+#[async_trait]
+impl Notifier for EmailNotifier {
+    async fn send(&self, notification: &Notification) -> Result<()> {
+        tokio::time::timeout(SMTP_TIMEOUT, self.deliver(notification))
+            .await
+            .map_err(|_| anyhow!("Timed out talking to {}", self.config.host))?
+    }
+}
+
+/// A line-oriented SMTP session.
+struct Smtp {
+    stream: BufReader<Box<dyn Connection>>,
+}
+
+impl Smtp {
+    fn new(stream: Box<dyn Connection>) -> Self {
+        Self { stream: BufReader::new(stream) }
+    }
+
+    fn into_inner(self) -> Box<dyn Connection> {
+        self.stream.into_inner()
+    }
+
+    /// Sends one command and returns the reply's text lines.
+    async fn command(&mut self, line: &str, expected: &[u16]) -> Result<Vec<String>> {
+        self.stream.get_mut().write_all(format!("{}\r\n", line).as_bytes()).await?;
+        self.stream.get_mut().flush().await?;
+        self.reply(expected).await
+    }
+
+    /// Reads a possibly multi-line reply and checks its status code.
+    async fn reply(&mut self, expected: &[u16]) -> Result<Vec<String>> {
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            if self.stream.read_line(&mut line).await? == 0 {
+                bail!("SMTP server closed the connection");
+            }
+            let line = line.trim_end();
+            let code: u16 = line
+                .get(..3)
+                .and_then(|c| c.parse().ok())
+                .ok_or_else(|| anyhow!("Malformed SMTP reply {:?}", line))?;
+            lines.push(line.get(4..).unwrap_or_default().to_string());
+
+            // "250-" continues the reply, "250 " ends it
+            if line.as_bytes().get(3) != Some(&b'-') {
+                if !expected.contains(&code) {
+                    bail!("SMTP server replied {}", line);
+                }
+                return Ok(lines);
+            }
+        }
+    }
+}
+
+fn validate_address(address: &str) -> Result<(), String> {
+    let valid = address.contains('@') && !address.contains(['<', '>', '\r', '\n', ',', ' ']);
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid email address {:?}", address))
+    }
+}
+
+/// Encodes a header value per RFC 2047 when it isn't plain ASCII, and
+/// strips line breaks so it can't inject headers.
+fn encode_header(value: &str) -> String {
+    let value = value.replace(['\r', '\n'], " ");
+    if value.is_ascii() {
+        value
+    } else {
+        format!("=?UTF-8?B?{}?=", BASE64.encode(value.as_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// What the mock server saw: each command line and the DATA payload as
+    /// sent, before dot-unstuffing.
+    #[derive(Default)]
+    struct Session {
+        commands: Vec<String>,
+        data: Vec<String>,
+    }
+
+    /// A scripted SMTP server for one session. `ehlo` lists the extensions
+    /// advertised; recipients starting with `reject` get a 550.
+    async fn smtp_server(ehlo: &'static [&'static str]) -> (u16, tokio::task::JoinHandle<Session>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let handle = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufReader::new(stream);
+            let mut session = Session::default();
+            let mut login_step = 0;
+            stream.get_mut().write_all(b"220 mock ESMTP\r\n").await.unwrap();
+
+            loop {
+                let mut line = String::new();
+                if stream.read_line(&mut line).await.unwrap() == 0 {
+                    break;
+                }
+                let line = line.trim_end_matches("\r\n").to_string();
+                session.commands.push(line.clone());
+
+                let reply = if login_step > 0 {
+                    login_step += 1;
+                    if login_step == 2 { "334 UGFzc3dvcmQ6".to_string() } else { "235 ok".to_string() }
+                } else if line.starts_with("EHLO") {
+                    let mut reply = String::from("250-mock");
+                    for extension in ehlo {
+                        reply.push_str(&format!("\r\n250-{}", extension));
+                    }
+                    reply + "\r\n250 SIZE 1000000"
+                } else if line.starts_with("AUTH PLAIN") {
+                    "235 ok".to_string()
+                } else if line == "AUTH LOGIN" {
+                    login_step = 1;
+                    "334 VXNlcm5hbWU6".to_string()
+                } else if line.starts_with("MAIL FROM") {
+                    "250 ok".to_string()
+                } else if line.starts_with("RCPT TO:<reject") {
+                    "550 no such user".to_string()
+                } else if line.starts_with("RCPT TO") {
+                    "250 ok".to_string()
+                } else if line == "DATA" {
+                    stream.get_mut().write_all(b"354 go ahead\r\n").await.unwrap();
+                    loop {
+                        let mut data = String::new();
+                        stream.read_line(&mut data).await.unwrap();
+                        let data = data.trim_end_matches("\r\n").to_string();
+                        if data == "." {
+                            break;
+                        }
+                        session.data.push(data);
+                    }
+                    "250 queued".to_string()
+                } else if line == "QUIT" {
+                    stream.get_mut().write_all(b"221 bye\r\n").await.unwrap();
+                    break;
+                } else {
+                    "500 unknown command".to_string()
+                };
+                if login_step == 3 {
+                    login_step = 0;
+                }
+                stream.get_mut().write_all(format!("{}\r\n", reply).as_bytes()).await.unwrap();
+            }
+            session
+        });
+
+        (port, handle)
+    }
+
+    fn notifier(port: u16, security: SmtpSecurity, to: &[&str], credentials: bool) -> EmailNotifier {
+        EmailNotifier::new(EmailConfig {
+            host: "127.0.0.1".into(),
+            port: Some(port),
+            security,
+            username: credentials.then(|| "alice".into()),
+            password: credentials.then(|| "p@ss word".into()),
+            from: "dockium@example.com".into(),
+            to: to.iter().map(|s| s.to_string()).collect(),
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn sends_the_command_sequence_with_auth_plain() {
+        let (port, server) = smtp_server(&["AUTH LOGIN PLAIN"]).await;
+        let notifier = notifier(port, SmtpSecurity::None, &["ops@example.com", "oncall@example.com"], true);
+
+        notifier.send(&Notification::sample("Disk full", "/var is at 99%")).await.unwrap();
+
+        let session = server.await.unwrap();
+        let credentials = BASE64.encode("\0alice\0p@ss word");
+        assert_eq!(
+            session.commands,
+            [
+                "EHLO dockium".to_string(),
+                format!("AUTH PLAIN {}", credentials),
+                "MAIL FROM:<dockium@example.com>".into(),
+                "RCPT TO:<ops@example.com>".into(),
+                "RCPT TO:<oncall@example.com>".into(),
+                "DATA".into(),
+                "QUIT".into(),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_auth_login() {
+        let (port, server) = smtp_server(&["AUTH LOGIN"]).await;
+        let notifier = notifier(port, SmtpSecurity::None, &["ops@example.com"], true);
+
+        notifier.send(&Notification::sample("t", "m")).await.unwrap();
+
+        let session = server.await.unwrap();
+        assert_eq!(
+            session.commands[1..4],
+            ["AUTH LOGIN".to_string(), BASE64.encode("alice"), BASE64.encode("p@ss word")]
+        );
+        assert_eq!(session.commands[4], "MAIL FROM:<dockium@example.com>");
+    }
+
+    #[tokio::test]
+    async fn data_needs_no_dot_stuffing_and_round_trips() {
+        let (port, server) = smtp_server(&[]).await;
+        let notifier = notifier(port, SmtpSecurity::None, &["ops@example.com"], false);
+        let message = ".leading dot\n..two dots\n.\nÜnïcode line";
+
+        notifier.send(&Notification::sample("Ärger: CPU", message)).await.unwrap();
+
+        let session = server.await.unwrap();
+        assert!(!session.commands.iter().any(|c| c.starts_with("AUTH")));
+        // A line starting with a dot would be unstuffed or end the message
+        assert!(session.data.iter().all(|line| !line.starts_with('.')), "{:?}", session.data);
+
+        let blank = session.data.iter().position(|line| line.is_empty()).unwrap();
+        let headers = &session.data[..blank];
+        assert!(headers.contains(&"To: ops@example.com".to_string()));
+        assert!(headers.contains(&format!("Subject: =?UTF-8?B?{}?=", BASE64.encode("Ärger: CPU"))));
+        assert!(headers.contains(&"Content-Transfer-Encoding: base64".to_string()));
+
+        let body = BASE64.decode(session.data[blank + 1..].concat()).unwrap();
+        let body = String::from_utf8(body).unwrap();
+        assert!(body.starts_with(&message.replace('\n', "\r\n")), "{:?}", body);
+        assert!(session.data[blank + 1..].iter().all(|line| line.len() <= 76));
+    }
+
+    #[tokio::test]
+    async fn requires_starttls_when_configured() {
+        let (port, server) = smtp_server(&["AUTH PLAIN"]).await;
+        let notifier = notifier(port, SmtpSecurity::Starttls, &["ops@example.com"], true);
+
+        let result = notifier.send(&Notification::sample("t", "m")).await;
+        assert!(result.is_err_and(|e| e.to_string().contains("does not support STARTTLS")));
+
+        // Credentials are never sent over the plain connection
+        let session = server.await.unwrap();
+        assert_eq!(session.commands, ["EHLO dockium"]);
+    }
+
+    #[tokio::test]
+    async fn reports_rejected_recipients() {
+        let (port, _server) = smtp_server(&[]).await;
+        let notifier = notifier(port, SmtpSecurity::None, &["reject@example.com"], false);
+
+        let result = notifier.send(&Notification::sample("t", "m")).await;
+        assert!(result.is_err_and(|e| e.to_string().contains("550 no such user")));
+    }
+
+    #[test]
+    fn validates_config() {
+        let config = |to: &[&str], username: Option<&str>| EmailConfig {
+            host: "smtp.example.com".into(),
+            port: None,
+            security: SmtpSecurity::default(),
+            username: username.map(Into::into),
+            password: None,
+            from: "a@example.com".into(),
+            to: to.iter().map(|s| s.to_string()).collect(),
+        };
+        assert!(EmailNotifier::new(config(&[], None)).is_err());
+        assert!(EmailNotifier::new(config(&["b@example.com\r\nBcc: x@evil.com"], None)).is_err());
+        assert!(EmailNotifier::new(config(&["b@example.com"], Some("user"))).is_err());
+        assert_eq!(EmailNotifier::new(config(&["b@example.com"], None)).unwrap().port(), 587);
+    }
+
+    #[test]
+    fn encode_header_strips_line_breaks() {
+        assert_eq!(encode_header("a\r\nBcc: x"), "a  Bcc: x");
+        assert_eq!(encode_header("plain"), "plain");
+        assert!(encode_header("ü").starts_with("=?UTF-8?B?"));
+    }
+}
@@ -0,0 +1,107 @@
+//! Delivery of notifications to external channels.
+
+mod chat;
+mod email;
+mod webhook;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// Channel kinds accepted by [`from_config`].
+pub const KINDS: &[&str] = &["webhook", "slack", "discord", "teams", "email"];
+
+/// Config fields holding credentials of a channel kind: redacted when
+/// channels are listed and encrypted at rest. Webhook URLs count, since chat
+/// services put the token in the URL.
+pub fn secret_fields(kind: &str) -> &'static [&'static str] {
+    match kind {
+        "webhook" => &["url", "secret"],
+        "slack" | "discord" | "teams" => &["url"],
+        "email" => &["password"],
+        _ => &[],
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct Notification {
+    /// `firing`, `resolved` or `test`.
+    pub event: String,
+    /// `info`, `warning` or `critical`.
+    pub severity: String,
+    pub title: String,
+    pub message: String,
+    pub alert_id: Option<String>,
+    pub rule: Option<String>,
+    pub subject: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl Notification {
+    /// RGB colour used by chat integrations.
+    fn color(&self) -> u32 {
+        match (self.event.as_str(), self.severity.as_str()) {
+            ("resolved", _) => 0x2ecc71,
+            (_, "critical") => 0xe74c3c,
+            (_, "warning") => 0xf39c12,
+            _ => 0x3498db,
+        }
+    }
+}
+
+#[cfg(test)]
+impl Notification {
+    pub fn sample(title: &str, message: &str) -> Self {
+        Self {
+            event: "firing".into(),
+            severity: "warning".into(),
+            title: title.into(),
+            message: message.into(),
+            alert_id: Some("alert-1".into()),
+            rule: Some("host.cpu_percent".into()),
+            subject: None,
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Makes a single delivery attempt.
+    async fn send(&self, notification: &Notification) -> Result<()>;
+}
+
+/// Builds the notifier for a channel, validating its configuration. Errors
+/// are meant to be shown to the user.
+pub fn from_config(kind: &str, config: &serde_json::Value) -> Result<Box<dyn Notifier>, String> {
+    let parse_error = |e: serde_json::Error| format!("Invalid {} config: {}", kind, e);
+    match kind {
+        "webhook" => {
+            let config: webhook::WebhookConfig = serde_json::from_value(config.clone()).map_err(parse_error)?;
+            Ok(Box::new(webhook::WebhookNotifier::new(config)?))
+        }
+        "slack" | "discord" | "teams" => {
+            let config: chat::ChatConfig = serde_json::from_value(config.clone()).map_err(parse_error)?;
+            let format = match kind {
+                "slack" => chat::ChatFormat::Slack,
+                "discord" => chat::ChatFormat::Discord,
+                _ => chat::ChatFormat::Teams,
+            };
+            Ok(Box::new(chat::ChatNotifier::new(config, format)?))
+        }
+        "email" => {
+            let config: email::EmailConfig = serde_json::from_value(config.clone()).map_err(parse_error)?;
+            Ok(Box::new(email::EmailNotifier::new(config)?))
+        }
+        _ => Err(format!("Unknown channel kind {:?}: expected one of {}", kind, KINDS.join(", "))),
+    }
+}
+
+fn validate_url(url: &str) -> Result<(), String> {
+    let uri: axum::http::Uri = url.parse().map_err(|_| format!("Invalid URL {:?}", url))?;
+    match (uri.scheme_str(), uri.host()) {
+        (Some("http") | Some("https"), Some(_)) => Ok(()),
+        _ => Err(format!("URL must start with http:// or https://, got {:?}", url)),
+    }
+}
@@ -0,0 +1,104 @@
+use super::{validate_url, Notification, Notifier};
+use crate::services::net;
+use anyhow::Result;
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+
+#[derive(Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// When set, requests carry an `X-Dockium-Signature` header.
+    pub secret: Option<String>,
+}
+
+/// POSTs the notification as JSON. With a secret, the body is signed as
+/// `sha256=<hex HMAC-SHA256 of "{timestamp}.{body}">`, where the timestamp
+/// is sent in `X-Dockium-Timestamp` so receivers can reject replays.
+pub struct WebhookNotifier {
+    config: WebhookConfig,
+}
+
+impl WebhookNotifier {
+    pub fn new(config: WebhookConfig) -> Result<Self, String> {
+        validate_url(&config.url)?;
+        if config.secret.as_deref() == Some("") {
+            return Err("secret must not be empty; omit it to disable signing".into());
+        }
+        Ok(Self { config })
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    async fn send(&self, notification: &Notification) -> Result<()> {
+        let body = serde_json::to_vec(notification)?;
+        let mut headers = vec![("X-Dockium-Event", notification.event.clone())];
+
+        if let Some(secret) = &self.config.secret {
+            let timestamp = chrono::Utc::now().timestamp().to_string();
+            let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())?;
+            mac.update(timestamp.as_bytes());
+            mac.update(b".");
+            mac.update(&body);
+            let signature = hex::encode(mac.finalize().into_bytes());
+
+            headers.push(("X-Dockium-Timestamp", timestamp));
+            headers.push(("X-Dockium-Signature", format!("sha256={}", signature)));
+        }
+
+        net::post_json(&self.config.url, &headers, body).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::net::mock::{self, response};
+
+    #[tokio::test]
+    async fn posts_the_notification_with_a_verifiable_signature() {
+        let (addr, mut received) = mock::serve(|_| response("200 OK", &[], "")).await;
+        let notifier = WebhookNotifier::new(WebhookConfig {
+            url: format!("http://{}/hook", addr),
+            secret: Some("s3cret".into()),
+        })
+        .unwrap();
+
+        notifier.send(&Notification::sample("CPU high", "95%")).await.unwrap();
+
+        let request = received.recv().await.unwrap();
+        assert_eq!(request.request_line(), "POST /hook HTTP/1.1");
+        assert_eq!(request.header("x-dockium-event"), Some("firing"));
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(body["title"], "CPU high");
+
+        let timestamp = request.header("x-dockium-timestamp").unwrap();
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"s3cret").unwrap();
+        mac.update(format!("{}.", timestamp).as_bytes());
+        mac.update(&request.body);
+        let expected = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+        assert_eq!(request.header("x-dockium-signature"), Some(expected.as_str()));
+    }
+
+    #[tokio::test]
+    async fn unsigned_without_a_secret() {
+        let (addr, mut received) = mock::serve(|_| response("200 OK", &[], "")).await;
+        let notifier = WebhookNotifier::new(WebhookConfig { url: format!("http://{}/", addr), secret: None }).unwrap();
+
+        notifier.send(&Notification::sample("t", "m")).await.unwrap();
+
+        let request = received.recv().await.unwrap();
+        assert_eq!(request.header("x-dockium-signature"), None);
+        assert_eq!(request.header("x-dockium-timestamp"), None);
+    }
+
+    #[test]
+    fn rejects_invalid_config() {
+        let config = |url: &str, secret: Option<&str>| WebhookConfig { url: url.into(), secret: secret.map(Into::into) };
+        assert!(WebhookNotifier::new(config("ftp://example.com", None)).is_err());
+        assert!(WebhookNotifier::new(config("https://example.com", Some(""))).is_err());
+        assert!(WebhookNotifier::new(config("https://example.com", Some("x"))).is_ok());
+    }
+}
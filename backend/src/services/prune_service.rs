@@ -0,0 +1,658 @@
+use crate::config::DockerConfig;
+use crate::db::Database;
+use crate::services::docker_service::DockerService;
+use crate::services::settings_service::{parse_bool, parse_number, SettingsService};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::process::Command;
+use tokio::sync::Mutex;
+
+/// Settings keys for scheduled runs. All are optional; see
+/// [`PruneSchedule`] for the defaults.
+pub const SETTING_ENABLED: &str = "prune.schedule.enabled";
+pub const SETTING_INTERVAL_HOURS: &str = "prune.schedule.interval_hours";
+pub const SETTING_TARGETS: &str = "prune.schedule.targets";
+pub const SETTING_IMAGE_AGE_DAYS: &str = "prune.schedule.image_age_days";
+pub const SETTING_ALL_VOLUMES: &str = "prune.schedule.all_volumes";
+
+const SETTINGS_PREFIX: &str = "prune.";
+const DEFAULT_INTERVAL_HOURS: u32 = 24;
+const MAX_INTERVAL_HOURS: u32 = 24 * 365;
+const DEFAULT_IMAGE_AGE_DAYS: u32 = 7;
+const DEFAULT_TARGETS: [PruneTarget; 2] = [PruneTarget::DanglingImages, PruneTarget::BuildCache];
+
+/// How often the scheduler checks whether a run is due.
+const SCHEDULER_TICK: Duration = Duration::from_secs(60);
+
+/// Networks created by the engine, which can never be removed.
+const PREDEFINED_NETWORKS: [&str; 3] = ["bridge", "host", "none"];
+
+/// Set by Docker 23+ on volumes created without a name.
+const ANONYMOUS_VOLUME_LABEL: &str = "com.docker.volume.anonymous";
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PruneTarget {
+    /// Untagged images no container uses.
+    DanglingImages,
+    /// Images no container uses, created more than `image_age_days` ago.
+    UnusedImages,
+    /// Containers that are created, exited or dead.
+    StoppedContainers,
+    /// User-defined networks no container is attached to.
+    UnusedNetworks,
+    /// Volumes no container mounts. Only anonymous volumes unless
+    /// `all_volumes` is set.
+    UnusedVolumes,
+    /// Build cache records not used by a running build.
+    BuildCache,
+}
+
+impl PruneTarget {
+    pub const ALL: [PruneTarget; 6] = [
+        PruneTarget::DanglingImages,
+        PruneTarget::UnusedImages,
+        PruneTarget::StoppedContainers,
+        PruneTarget::UnusedNetworks,
+        PruneTarget::UnusedVolumes,
+        PruneTarget::BuildCache,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PruneTarget::DanglingImages => "dangling_images",
+            PruneTarget::UnusedImages => "unused_images",
+            PruneTarget::StoppedContainers => "stopped_containers",
+            PruneTarget::UnusedNetworks => "unused_networks",
+            PruneTarget::UnusedVolumes => "unused_volumes",
+            PruneTarget::BuildCache => "build_cache",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.as_str() == value)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PruneOptions {
+    pub targets: Vec<PruneTarget>,
+    /// Minimum age of images removed by `unused_images`.
+    #[serde(default = "default_image_age_days")]
+    pub image_age_days: u32,
+    /// Also remove named volumes with `unused_volumes`.
+    #[serde(default)]
+    pub all_volumes: bool,
+    /// List what would be removed without removing anything.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+fn default_image_age_days() -> u32 {
+    DEFAULT_IMAGE_AGE_DAYS
+}
+
+#[derive(Serialize, Clone)]
+pub struct PruneItem {
+    pub target: PruneTarget,
+    pub id: String,
+    pub name: Option<String>,
+    /// Bytes freed by removing this item alone. Layers shared with other
+    /// images are not counted.
+    pub size: u64,
+    /// Why the item could not be removed.
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct PruneReport {
+    pub dry_run: bool,
+    pub items: Vec<PruneItem>,
+    /// Bytes freed, or that would be freed for a dry run.
+    pub reclaimed_bytes: u64,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct PruneRun {
+    pub id: i64,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    /// `manual` or `scheduled`.
+    pub trigger: String,
+    pub username: Option<String>,
+    /// Comma-separated target names.
+    pub targets: String,
+    pub removed: i64,
+    pub failed: i64,
+    pub reclaimed_bytes: i64,
+    /// Set when the run could not start, e.g. the engine was unreachable.
+    pub error: Option<String>,
+}
+
+/// Scheduled runs as configured in the `prune.schedule.*` settings.
+#[derive(Serialize)]
+pub struct PruneSchedule {
+    pub enabled: bool,
+    pub interval_hours: u32,
+    pub targets: Vec<PruneTarget>,
+    pub image_age_days: u32,
+    pub all_volumes: bool,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub next_run_at: Option<DateTime<Utc>>,
+}
+
+impl PruneSchedule {
+    fn options(&self) -> PruneOptions {
+        PruneOptions {
+            targets: self.targets.clone(),
+            image_age_days: self.image_age_days,
+            all_volumes: self.all_volumes,
+            dry_run: false,
+        }
+    }
+}
+
+/// Checks a `prune.*` setting before it is saved, so a typo is reported
+/// to the user rather than silently disabling scheduled runs.
+pub fn validate_setting(key: &str, value: &str) -> std::result::Result<(), String> {
+    let result = match key {
+        SETTING_ENABLED | SETTING_ALL_VOLUMES => parse_bool(value).map(|_| ()),
+        SETTING_INTERVAL_HOURS => parse_number(value, 1, MAX_INTERVAL_HOURS).map(|_| ()),
+        SETTING_IMAGE_AGE_DAYS => parse_number(value, 0, u32::MAX).map(|_| ()),
+        SETTING_TARGETS => parse_targets(value).map(|_| ()),
+        _ if key.starts_with(SETTINGS_PREFIX) => return Err(format!("Unknown setting {}", key)),
+        _ => return Ok(()),
+    };
+    result.map_err(|e| format!("Invalid setting {}: {}", key, e))
+}
+
+fn parse_targets(value: &str) -> std::result::Result<Vec<PruneTarget>, String> {
+    let mut targets = Vec::new();
+    for name in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let target = PruneTarget::parse(name).ok_or_else(|| {
+            let known: Vec<_> = PruneTarget::ALL.iter().map(|t| t.as_str()).collect();
+            format!("unknown target {:?}, expected one of {}", name, known.join(", "))
+        })?;
+        if !targets.contains(&target) {
+            targets.push(target);
+        }
+    }
+    if targets.is_empty() {
+        return Err("at least one target is required".to_string());
+    }
+    Ok(targets)
+}
+
+pub struct PruneService {
+    db: Arc<Database>,
+    docker: Arc<DockerService>,
+    settings: Arc<SettingsService>,
+    /// `DOCKER_HOST` for the CLI, which is needed to prune build cache.
+    docker_host: String,
+    /// Held for the length of a real run so manual and scheduled runs
+    /// don't race each other.
+    running: Mutex<()>,
+}
+
+impl PruneService {
+    pub fn new(
+        db: Arc<Database>,
+        docker: Arc<DockerService>,
+        settings: Arc<SettingsService>,
+        config: &DockerConfig,
+    ) -> Self {
+        let docker_host = match &config.remote_host {
+            Some(host) => host.replacen("http://", "tcp://", 1),
+            None => format!("unix://{}", config.socket_path),
+        };
+        Self {
+            db,
+            docker,
+            settings,
+            docker_host,
+            running: Mutex::new(()),
+        }
+    }
+
+    /// Runs the schedule from settings until the process exits. Settings
+    /// are re-read on every tick, so changes apply without a restart.
+    pub async fn run(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(SCHEDULER_TICK);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let schedule = match self.schedule().await {
+                Ok(schedule) => schedule,
+                Err(e) => {
+                    tracing::warn!("Failed to load prune schedule: {}", e);
+                    continue;
+                }
+            };
+            // Only set when enabled
+            let due = schedule.next_run_at.is_some_and(|next| next <= Utc::now());
+            if !due {
+                continue;
+            }
+
+            match self.prune(&schedule.options(), "scheduled", None).await {
+                Ok(report) => tracing::info!(
+                    "Scheduled prune removed {} items, reclaimed {} bytes",
+                    report.items.iter().filter(|i| i.error.is_none()).count(),
+                    report.reclaimed_bytes
+                ),
+                Err(e) => tracing::warn!("Scheduled prune failed: {}", e),
+            }
+        }
+    }
+
+    pub async fn schedule(&self) -> Result<PruneSchedule> {
+        let settings = self.settings.get_all_settings().await?;
+        let get = |key: &str| settings.get(key).map(String::as_str);
+        let invalid = |key: &str, e: String| anyhow!("Invalid setting {}: {}", key, e);
+
+        let enabled = get(SETTING_ENABLED)
+            .map(parse_bool)
+            .transpose()
+            .map_err(|e| invalid(SETTING_ENABLED, e))?
+            .unwrap_or(false);
+        let interval_hours = get(SETTING_INTERVAL_HOURS)
+            .map(|v| parse_number(v, 1, MAX_INTERVAL_HOURS))
+            .transpose()
+            .map_err(|e| invalid(SETTING_INTERVAL_HOURS, e))?
+            .unwrap_or(DEFAULT_INTERVAL_HOURS);
+        let targets = get(SETTING_TARGETS)
+            .map(parse_targets)
+            .transpose()
+            .map_err(|e| invalid(SETTING_TARGETS, e))?
+            .unwrap_or_else(|| DEFAULT_TARGETS.to_vec());
+        let image_age_days = get(SETTING_IMAGE_AGE_DAYS)
+            .map(|v| parse_number(v, 0, u32::MAX))
+            .transpose()
+            .map_err(|e| invalid(SETTING_IMAGE_AGE_DAYS, e))?
+            .unwrap_or(DEFAULT_IMAGE_AGE_DAYS);
+        let all_volumes = get(SETTING_ALL_VOLUMES)
+            .map(parse_bool)
+            .transpose()
+            .map_err(|e| invalid(SETTING_ALL_VOLUMES, e))?
+            .unwrap_or(false);
+
+        let last_run_at: Option<DateTime<Utc>> =
+            sqlx::query_scalar("SELECT MAX(started_at) FROM prune_runs WHERE trigger = 'scheduled'")
+                .fetch_one(&self.db.pool)
+                .await?;
+        let next_run_at = enabled.then(|| match last_run_at {
+            Some(last) => last + chrono::Duration::hours(interval_hours as i64),
+            None => Utc::now(),
+        });
+
+        Ok(PruneSchedule {
+            enabled,
+            interval_hours,
+            targets,
+            image_age_days,
+            all_volumes,
+            last_run_at,
+            next_run_at,
+        })
+    }
+
+    /// Finds everything the targets cover and, unless this is a dry run,
+    /// removes it item by item so the report shows exactly what a dry run
+    /// would have listed. Real runs are recorded in `prune_runs`.
+    pub async fn prune(&self, options: &PruneOptions, trigger: &str, username: Option<&str>) -> Result<PruneReport> {
+        if options.dry_run {
+            let items = self.candidates(options).await?;
+            let reclaimed_bytes = items.iter().map(|i| i.size).sum();
+            return Ok(PruneReport {
+                dry_run: true,
+                items,
+                reclaimed_bytes,
+            });
+        }
+
+        let _guard = self.running.lock().await;
+        let started_at = Utc::now();
+        let result = match self.candidates(options).await {
+            Ok(mut items) => {
+                self.remove(&mut items, started_at).await;
+                Ok(items)
+            }
+            Err(e) => Err(e),
+        };
+
+        let (items, error) = match result {
+            Ok(items) => (items, None),
+            Err(e) => (Vec::new(), Some(e)),
+        };
+        let removed: Vec<_> = items.iter().filter(|i| i.error.is_none()).collect();
+        let reclaimed_bytes: u64 = removed.iter().map(|i| i.size).sum();
+        let targets: Vec<_> = options.targets.iter().map(|t| t.as_str()).collect();
+
+        sqlx::query(
+            "INSERT INTO prune_runs (started_at, finished_at, trigger, username, targets, removed, failed, reclaimed_bytes, error)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(started_at)
+        .bind(Utc::now())
+        .bind(trigger)
+        .bind(username)
+        .bind(targets.join(","))
+        .bind(removed.len() as i64)
+        .bind((items.len() - removed.len()) as i64)
+        .bind(reclaimed_bytes as i64)
+        .bind(error.as_ref().map(|e| e.to_string()))
+        .execute(&self.db.pool)
+        .await?;
+
+        if let Some(e) = error {
+            return Err(e);
+        }
+        Ok(PruneReport {
+            dry_run: false,
+            items,
+            reclaimed_bytes,
+        })
+    }
+
+    /// Most recent real runs first.
+    pub async fn list_runs(&self, limit: i64, offset: i64) -> Result<(Vec<PruneRun>, i64)> {
+        let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM prune_runs")
+            .fetch_one(&self.db.pool)
+            .await?;
+        let runs = sqlx::query_as(
+            "SELECT id, started_at, finished_at, trigger, username, targets, removed, failed, reclaimed_bytes, error
+             FROM prune_runs ORDER BY id DESC LIMIT ? OFFSET ?",
+        )
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.db.pool)
+        .await?;
+        Ok((runs, total))
+    }
+
+    async fn candidates(&self, options: &PruneOptions) -> Result<Vec<PruneItem>> {
+        let usage = self.docker.data_usage().await?;
+        let wants = |target| options.targets.contains(&target);
+        let item = |target, id: String, name: Option<String>, size: i64| PruneItem {
+            target,
+            id,
+            name,
+            size: size.max(0) as u64,
+            error: None,
+        };
+        let mut items = Vec::new();
+
+        let containers = usage.containers.unwrap_or_default();
+        if wants(PruneTarget::StoppedContainers) {
+            for container in &containers {
+                let stopped = matches!(container.state.as_deref(), Some("created" | "exited" | "dead"));
+                if let (true, Some(id)) = (stopped, &container.id) {
+                    let name = container
+                        .names
+                        .as_ref()
+                        .and_then(|n| n.first())
+                        .map(|n| n.trim_start_matches('/').to_string());
+                    items.push(item(PruneTarget::StoppedContainers, id.clone(), name, container.size_rw.unwrap_or(0)));
+                }
+            }
+        }
+
+        let cutoff = (Utc::now() - chrono::Duration::days(options.image_age_days as i64)).timestamp();
+        for image in usage.images.unwrap_or_default() {
+            if image.containers > 0 {
+                continue;
+            }
+            let dangling = image.repo_tags.iter().all(|t| t == "<none>:<none>");
+            let old = image.created < cutoff;
+            let target = if dangling && wants(PruneTarget::DanglingImages) {
+                PruneTarget::DanglingImages
+            } else if old && wants(PruneTarget::UnusedImages) {
+                PruneTarget::UnusedImages
+            } else {
+                continue;
+            };
+            // -1 when the engine did not compute it
+            let unique = if image.shared_size >= 0 { image.size - image.shared_size } else { image.size };
+            let name = image.repo_tags.into_iter().find(|t| t != "<none>:<none>");
+            items.push(item(target, image.id, name, unique));
+        }
+
+        if wants(PruneTarget::UnusedNetworks) {
+            // Containers removed in this run don't keep their networks
+            let removed: HashSet<&str> = items
+                .iter()
+                .filter(|i| i.target == PruneTarget::StoppedContainers)
+                .map(|i| i.id.as_str())
+                .collect();
+            let attached: HashSet<String> = containers
+                .iter()
+                .filter(|c| !c.id.as_deref().is_some_and(|id| removed.contains(id)))
+                .filter_map(|c| c.network_settings.as_ref()?.networks.as_ref())
+                .flat_map(|networks| networks.values().filter_map(|n| n.network_id.clone()))
+                .collect();
+            for network in self.docker.list_networks().await? {
+                let (Some(id), Some(name)) = (network.id, network.name) else {
+                    continue;
+                };
+                if PREDEFINED_NETWORKS.contains(&name.as_str())
+                    || network.ingress == Some(true)
+                    || attached.contains(&id)
+                {
+                    continue;
+                }
+                items.push(item(PruneTarget::UnusedNetworks, id, Some(name), 0));
+            }
+        }
+
+        if wants(PruneTarget::UnusedVolumes) {
+            for volume in usage.volumes.unwrap_or_default() {
+                let Some(usage) = volume.usage_data else {
+                    continue;
+                };
+                let anonymous = volume.labels.contains_key(ANONYMOUS_VOLUME_LABEL) || is_generated_name(&volume.name);
+                if usage.ref_count == 0 && (options.all_volumes || anonymous) {
+                    items.push(item(PruneTarget::UnusedVolumes, volume.name, None, usage.size));
+                }
+            }
+        }
+
+        if wants(PruneTarget::BuildCache) {
+            for record in usage.build_cache.unwrap_or_default() {
+                let (Some(id), false) = (record.id, record.in_use.unwrap_or(false)) else {
+                    continue;
+                };
+                // Shared records stay on disk while another record uses them
+                let size = if record.shared.unwrap_or(false) { 0 } else { record.size.unwrap_or(0) };
+                items.push(item(PruneTarget::BuildCache, id, record.description, size));
+            }
+        }
+
+        Ok(items)
+    }
+
+    /// Removes containers first, so their networks can go in the same run.
+    /// `listed_at` is when the candidates were listed; build cache used
+    /// since then is kept.
+    async fn remove(&self, items: &mut [PruneItem], listed_at: DateTime<Utc>) {
+        let mut build_cache = false;
+        for item in items.iter_mut() {
+            let result = match item.target {
+                PruneTarget::StoppedContainers => self.docker.remove_container(&item.id).await,
+                PruneTarget::DanglingImages | PruneTarget::UnusedImages => {
+                    self.docker.remove_unused_image(&item.id).await
+                }
+                PruneTarget::UnusedNetworks => self.docker.remove_network(&item.id).await,
+                PruneTarget::UnusedVolumes => self.docker.remove_volume(&item.id).await,
+                PruneTarget::BuildCache => {
+                    build_cache = true;
+                    continue;
+                }
+            };
+            item.error = result.err().map(|e| e.to_string());
+        }
+
+        if build_cache {
+            if let Err(e) = self.prune_build_cache(listed_at).await {
+                let message = e.to_string();
+                for item in items.iter_mut().filter(|i| i.target == PruneTarget::BuildCache) {
+                    item.error = Some(message.clone());
+                }
+            }
+        }
+    }
+
+    /// The engine API client has no build cache endpoint, so this goes
+    /// through the CLI like the compose commands do. Only records last used
+    /// before `listed_at`, i.e. the ones listed as candidates, are removed.
+    async fn prune_build_cache(&self, listed_at: DateTime<Utc>) -> Result<()> {
+        let output = Command::new("docker")
+            .args(["builder", "prune", "--all", "--force", "--filter"])
+            .arg(unused_since_filter(listed_at, Utc::now()))
+            .env("DOCKER_HOST", &self.docker_host)
+            .output()
+            .await?;
+
+        if !output.status.success() {
+            return Err(anyhow!("Failed to prune build cache: {}", String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        Ok(())
+    }
+}
+
+/// BuildKit's `until` filter for records not used since `listed_at`.
+fn unused_since_filter(listed_at: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    format!("until={}ms", (now - listed_at).num_milliseconds().max(0))
+}
+
+/// Engines before 23 don't label anonymous volumes, but name them with 64
+/// hex characters.
+fn is_generated_name(name: &str) -> bool {
+    name.len() == 64 && name.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::net::mock;
+
+    #[test]
+    fn settings_are_validated_before_they_are_saved() {
+        assert!(validate_setting(SETTING_ENABLED, "true").is_ok());
+        assert!(validate_setting(SETTING_INTERVAL_HOURS, "12").is_ok());
+        assert!(validate_setting(SETTING_IMAGE_AGE_DAYS, "0").is_ok());
+        assert!(validate_setting(SETTING_TARGETS, "build_cache, dangling_images").is_ok());
+        assert!(validate_setting("theme", "anything").is_ok());
+
+        assert!(validate_setting(SETTING_ALL_VOLUMES, "yes").is_err());
+        assert!(validate_setting(SETTING_INTERVAL_HOURS, "0").is_err());
+        assert!(validate_setting(SETTING_TARGETS, " , ").is_err());
+        assert!(validate_setting(SETTING_TARGETS, "images").unwrap_err().contains("unknown target"));
+        assert!(validate_setting("prune.schedule.typo", "1").unwrap_err().contains("Unknown setting"));
+    }
+
+    #[test]
+    fn targets_are_deduplicated_in_order() {
+        assert_eq!(
+            parse_targets("build_cache,dangling_images,build_cache").unwrap(),
+            [PruneTarget::BuildCache, PruneTarget::DanglingImages]
+        );
+    }
+
+    #[test]
+    fn build_cache_filter_covers_the_time_since_listing() {
+        let listed_at = Utc::now();
+        assert_eq!(unused_since_filter(listed_at, listed_at + chrono::Duration::milliseconds(1500)), "until=1500ms");
+        assert_eq!(unused_since_filter(listed_at, listed_at - chrono::Duration::seconds(1)), "until=0ms");
+    }
+
+    #[test]
+    fn generated_volume_names_are_64_hex_characters() {
+        assert!(is_generated_name(&"0f".repeat(32)));
+        assert!(!is_generated_name(&"0g".repeat(32)));
+        assert!(!is_generated_name("postgres-data"));
+    }
+
+    /// An engine with a stopped container on `app`, a running one on `web`
+    /// and an idle `idle` network.
+    async fn service() -> (PruneService, tokio::sync::mpsc::UnboundedReceiver<mock::Received>) {
+        let (addr, requests) = mock::serve(|req| {
+            let path = req.request_line().split(' ').nth(1).unwrap_or_default();
+            let body = if path.contains("/system/df") {
+                r#"{"LayersSize":0,"Images":[],"Volumes":[],"BuildCache":[],"Containers":[
+                    {"Id":"c1","Names":["/stopped"],"State":"exited","SizeRw":42,"NetworkSettings":{"Networks":{"app":{"NetworkID":"n-app"}}}},
+                    {"Id":"c2","Names":["/web"],"State":"running","NetworkSettings":{"Networks":{"web":{"NetworkID":"n-web"}}}}]}"#
+            } else if path.contains("/networks") && req.request_line().starts_with("GET") {
+                r#"[{"Id":"n-bridge","Name":"bridge"},{"Id":"n-app","Name":"app"},{"Id":"n-web","Name":"web"},{"Id":"n-idle","Name":"idle"}]"#
+            } else if req.request_line().starts_with("DELETE") {
+                return mock::response("204 No Content", &[], "");
+            } else {
+                return mock::response("404 Not Found", &[], "{}");
+            };
+            mock::response("200 OK", &[("Content-Type", "application/json")], body)
+        })
+        .await;
+
+        let config = DockerConfig {
+            socket_path: String::new(),
+            remote_host: Some(format!("http://{}", addr)),
+            build_context_roots: Vec::new(),
+            max_build_context_bytes: 1,
+        };
+        let db = Arc::new(Database::in_memory().await);
+        let service = PruneService::new(
+            db.clone(),
+            Arc::new(DockerService::new(&config).unwrap()),
+            Arc::new(SettingsService::new(db)),
+            &config,
+        );
+        (service, requests)
+    }
+
+    fn options(targets: &[PruneTarget], dry_run: bool) -> PruneOptions {
+        PruneOptions { targets: targets.to_vec(), image_age_days: 7, all_volumes: false, dry_run }
+    }
+
+    fn ids(report: &PruneReport) -> Vec<&str> {
+        report.items.iter().map(|i| i.id.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn networks_of_containers_removed_in_the_same_run_are_listed() {
+        let (service, _) = service().await;
+
+        let networks_only = service.prune(&options(&[PruneTarget::UnusedNetworks], true), "manual", None).await.unwrap();
+        assert_eq!(ids(&networks_only), ["n-idle"]);
+
+        let both = [PruneTarget::StoppedContainers, PruneTarget::UnusedNetworks];
+        let report = service.prune(&options(&both, true), "manual", None).await.unwrap();
+        assert_eq!(ids(&report), ["c1", "n-app", "n-idle"]);
+        assert_eq!(report.reclaimed_bytes, 42);
+    }
+
+    #[tokio::test]
+    async fn real_runs_remove_containers_before_their_networks_and_are_recorded() {
+        let (service, mut requests) = service().await;
+        let both = [PruneTarget::StoppedContainers, PruneTarget::UnusedNetworks];
+        let report = service.prune(&options(&both, false), "manual", Some("admin")).await.unwrap();
+        assert!(report.items.iter().all(|i| i.error.is_none()));
+
+        let mut deletes = Vec::new();
+        while let Ok(req) = requests.try_recv() {
+            if req.request_line().starts_with("DELETE") {
+                let path = req.request_line().split(' ').nth(1).unwrap().split('?').next().unwrap();
+                deletes.push(path.to_string());
+            }
+        }
+        assert_eq!(deletes, ["/containers/c1", "/networks/n-app", "/networks/n-idle"]);
+
+        let (runs, total) = service.list_runs(10, 0).await.unwrap();
+        assert_eq!(total, 1);
+        assert_eq!((runs[0].removed, runs[0].failed, runs[0].reclaimed_bytes), (3, 0, 42));
+        assert_eq!(runs[0].username.as_deref(), Some("admin"));
+    }
+}
@@ -0,0 +1,500 @@
+use crate::services::docker_service::{split_tag, DockerService};
+use crate::services::registry_service::{RegistryError, RegistryService};
+use anyhow::anyhow;
+use bollard::container::{Config, NetworkingConfig};
+use bollard::service::{
+    ContainerConfig, ContainerInspectResponse, EndpointSettings, HealthStatusEnum, HostConfig, ImageConfig,
+    MountPointTypeEnum,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+
+const DEFAULT_WAIT_SECONDS: u64 = 5;
+const MAX_WAIT_SECONDS: u64 = 300;
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug)]
+pub enum RecreateError {
+    NotFound,
+    InvalidInput(String),
+    /// The new container failed and the old one was put back.
+    RolledBack(String),
+    Internal(anyhow::Error),
+}
+
+impl fmt::Display for RecreateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecreateError::NotFound => write!(f, "Container not found"),
+            RecreateError::InvalidInput(msg) => write!(f, "{}", msg),
+            RecreateError::RolledBack(msg) => write!(f, "New container failed, restored the previous one: {}", msg),
+            RecreateError::Internal(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<anyhow::Error> for RecreateError {
+    fn from(e: anyhow::Error) -> Self {
+        RecreateError::Internal(e)
+    }
+}
+
+impl From<RegistryError> for RecreateError {
+    fn from(e: RegistryError) -> Self {
+        RecreateError::Internal(anyhow!("Failed to load registry credentials: {}", e))
+    }
+}
+
+#[derive(Deserialize, Default)]
+pub struct RecreateOptions {
+    /// Image reference for the new container. Defaults to the one the
+    /// container was created from.
+    pub image: Option<String>,
+    /// Keep the repository but switch to this tag.
+    pub tag: Option<String>,
+    /// Pull the image first, using stored registry credentials.
+    #[serde(default)]
+    pub pull: bool,
+    /// How long the new container must stay up, or become healthy if it
+    /// has a healthcheck, before the old one is removed.
+    pub wait_seconds: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct RecreateReport {
+    pub id: String,
+    pub name: String,
+    pub image: String,
+    pub image_id: Option<String>,
+    pub previous_id: String,
+    pub previous_image_id: Option<String>,
+    pub pulled: bool,
+}
+
+pub struct RecreateService {
+    docker: Arc<DockerService>,
+    registries: Arc<RegistryService>,
+}
+
+impl RecreateService {
+    pub fn new(docker: Arc<DockerService>, registries: Arc<RegistryService>) -> Self {
+        Self { docker, registries }
+    }
+
+    /// Replaces a container with a new one built from its inspect output,
+    /// optionally on a different or freshly pulled image. The old container
+    /// is renamed aside and only removed once the new one is up; if the new
+    /// one fails it is removed and the old one restored.
+    pub async fn recreate(&self, id: &str, options: RecreateOptions) -> Result<RecreateReport, RecreateError> {
+        let wait = Duration::from_secs(options.wait_seconds.unwrap_or(DEFAULT_WAIT_SECONDS));
+        if wait.as_secs() > MAX_WAIT_SECONDS {
+            return Err(RecreateError::InvalidInput(format!("wait_seconds must be at most {}", MAX_WAIT_SECONDS)));
+        }
+
+        let old = self.docker.inspect_container(id).await.map_err(not_found)?;
+        let old_id = old.id.clone().unwrap_or_default();
+        let name = old.name.as_deref().unwrap_or_default().trim_start_matches('/').to_string();
+        let config = old.config.clone().unwrap_or_default();
+        let host_config = old.host_config.clone().unwrap_or_default();
+        if host_config.auto_remove == Some(true) {
+            return Err(RecreateError::InvalidInput(
+                "Containers started with --rm are removed when stopped and can't be recreated".into(),
+            ));
+        }
+
+        let image = target_image(config.image.as_deref(), &options)?;
+        if options.pull {
+            let credentials = self.registries.credentials_for(&image).await?;
+            self.docker.pull_image(&image, credentials).await?;
+        }
+
+        // Values the container inherited from its old image are dropped, so
+        // the new image's defaults apply instead of the old ones.
+        let old_image = match &old.image {
+            Some(image_id) => self.docker.inspect_image(image_id).await.ok().and_then(|i| i.config),
+            None => None,
+        };
+        let (new_config, extra_networks) = build_config(&old, config, host_config, &image, old_image.as_ref());
+
+        let was_running = old.state.as_ref().and_then(|s| s.running).unwrap_or(false);
+        let short_id: String = old_id.chars().take(12).collect();
+        let aside = format!("{}-old-{}", name, short_id);
+        self.docker.rename_container(&old_id, &aside).await?;
+
+        match self.replace(&old_id, &name, new_config, extra_networks, was_running, wait).await {
+            Ok(new_id) => {
+                if let Err(e) = self.docker.remove_container(&old_id).await {
+                    tracing::warn!("Recreated {} but failed to remove the old container: {}", name, e);
+                }
+                let new = self.docker.inspect_container(&new_id).await?;
+                Ok(RecreateReport {
+                    id: new_id,
+                    name,
+                    image,
+                    image_id: new.image,
+                    previous_id: old_id,
+                    previous_image_id: old.image,
+                    pulled: options.pull,
+                })
+            }
+            Err((new_id, e)) => {
+                self.rollback(&old_id, &name, new_id.as_deref(), was_running)
+                    .await
+                    .map_err(|rollback| anyhow!("New container failed ({}) and rollback failed: {}", e, rollback))?;
+                Err(RecreateError::RolledBack(e.to_string()))
+            }
+        }
+    }
+
+    /// Creates and starts the new container. On failure, returns the new
+    /// container's id if it got that far, for cleanup.
+    async fn replace(
+        &self,
+        old_id: &str,
+        name: &str,
+        config: Config<String>,
+        extra_networks: HashMap<String, EndpointSettings>,
+        start: bool,
+        wait: Duration,
+    ) -> Result<String, (Option<String>, anyhow::Error)> {
+        let new_id = self.docker.create_container(name, config).await.map_err(|e| (None, e))?.id;
+        let fail = |e: anyhow::Error| (Some(new_id.clone()), e);
+
+        for (network, endpoint) in extra_networks {
+            self.docker.connect_network(&network, &new_id, endpoint).await.map_err(fail)?;
+        }
+        if !start {
+            return Ok(new_id);
+        }
+
+        self.docker.stop_container(old_id).await.map_err(fail)?;
+        self.docker.start_container(&new_id).await.map_err(fail)?;
+        self.wait_until_up(&new_id, wait).await.map_err(fail)?;
+        Ok(new_id)
+    }
+
+    /// Fails if the container exits or turns unhealthy within `wait`.
+    /// Containers with a healthcheck succeed as soon as they are healthy.
+    async fn wait_until_up(&self, id: &str, wait: Duration) -> anyhow::Result<()> {
+        let deadline = Instant::now() + wait;
+        loop {
+            let state = self.docker.inspect_container(id).await?.state.unwrap_or_default();
+            if state.running != Some(true) {
+                return Err(anyhow!(
+                    "Container exited with code {}{}",
+                    state.exit_code.unwrap_or_default(),
+                    state.error.filter(|e| !e.is_empty()).map(|e| format!(": {}", e)).unwrap_or_default()
+                ));
+            }
+            match state.health.and_then(|h| h.status) {
+                Some(HealthStatusEnum::HEALTHY) => return Ok(()),
+                Some(HealthStatusEnum::UNHEALTHY) => return Err(anyhow!("Container is unhealthy")),
+                Some(HealthStatusEnum::STARTING) if Instant::now() >= deadline => {
+                    return Err(anyhow!("Container did not become healthy within {}s", wait.as_secs()))
+                }
+                _ if Instant::now() >= deadline => return Ok(()),
+                _ => {}
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    async fn rollback(&self, old_id: &str, name: &str, new_id: Option<&str>, restart: bool) -> anyhow::Result<()> {
+        if let Some(new_id) = new_id {
+            let _ = self.docker.stop_container(new_id).await;
+            self.docker.remove_container(new_id).await?;
+        }
+        self.docker.rename_container(old_id, name).await?;
+        if restart {
+            self.docker.start_container(old_id).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Maps an inspect failure, telling an unknown container apart.
+pub fn not_found(e: anyhow::Error) -> RecreateError {
+    match e.downcast_ref::<bollard::errors::Error>() {
+        Some(bollard::errors::Error::DockerResponseServerError { status_code: 404, .. }) => RecreateError::NotFound,
+        _ => RecreateError::Internal(e),
+    }
+}
+
+fn target_image(current: Option<&str>, options: &RecreateOptions) -> Result<String, RecreateError> {
+    let invalid = |msg: &str| RecreateError::InvalidInput(msg.to_string());
+    match (options.image.as_deref().map(str::trim), options.tag.as_deref().map(str::trim)) {
+        (Some(_), Some(_)) => Err(invalid("Set either image or tag, not both")),
+        (Some(""), None) | (None, Some("")) => Err(invalid("Image and tag must not be empty")),
+        (Some(image), None) => Ok(image.to_string()),
+        (None, tag) => {
+            let current = current
+                .filter(|c| !c.starts_with("sha256:"))
+                .ok_or_else(|| invalid("The container was created from an image id; set image instead"))?;
+            let Some(tag) = tag else {
+                return Ok(current.to_string());
+            };
+            if tag.contains(['/', ':', '@']) {
+                return Err(invalid("Tag must not contain '/', ':' or '@'"));
+            }
+            let repo = match current.split_once('@') {
+                Some((repo, _)) => repo,
+                None => split_tag(current).0,
+            };
+            Ok(format!("{}:{}", repo, tag))
+        }
+    }
+}
+
+/// The create request for the replacement, plus the networks to connect
+/// before it starts; only one can be given at creation on older engines.
+fn build_config(
+    old: &ContainerInspectResponse,
+    config: ContainerConfig,
+    mut host_config: HostConfig,
+    image: &str,
+    old_image: Option<&ImageConfig>,
+) -> (Config<String>, HashMap<String, EndpointSettings>) {
+    let old_id = old.id.as_deref().unwrap_or_default();
+
+    let env = config.env.map(|env| {
+        let image_env = old_image.and_then(|i| i.env.as_ref());
+        env.into_iter().filter(|e| !image_env.is_some_and(|i| i.contains(e))).collect()
+    });
+    let labels = config.labels.map(|labels| {
+        let image_labels = old_image.and_then(|i| i.labels.as_ref());
+        labels
+            .into_iter()
+            .filter(|(k, v)| image_labels.and_then(|l| l.get(k)) != Some(v))
+            .collect()
+    });
+    let exposed_ports = config.exposed_ports.map(|ports| {
+        let image_ports = old_image.and_then(|i| i.exposed_ports.as_ref());
+        ports.into_iter().filter(|(p, _)| !image_ports.is_some_and(|i| i.contains_key(p))).collect()
+    });
+    let volumes = config.volumes.map(|volumes| {
+        let image_volumes = old_image.and_then(|i| i.volumes.as_ref());
+        volumes.into_iter().filter(|(v, _)| !image_volumes.is_some_and(|i| i.contains_key(v))).collect()
+    });
+    // Docker defaults the hostname to the short container id
+    let hostname = config.hostname.filter(|h| !old_id.starts_with(h.as_str()));
+
+    // Anonymous volumes aren't in the host config; mount them by name so
+    // their data carries over instead of the new container getting empty ones.
+    let mut binds = host_config.binds.take().unwrap_or_default();
+    let covered: Vec<String> = binds
+        .iter()
+        .filter_map(|b| b.split(':').nth(1).map(str::to_string))
+        .chain(host_config.mounts.iter().flatten().filter_map(|m| m.target.clone()))
+        .collect();
+    for mount in old.mounts.iter().flatten() {
+        if let (Some(MountPointTypeEnum::VOLUME), Some(name), Some(destination)) = (mount.typ, &mount.name, &mount.destination) {
+            if !covered.contains(destination) {
+                let mode = if mount.rw == Some(false) { ":ro" } else { "" };
+                binds.push(format!("{}:{}{}", name, destination, mode));
+            }
+        }
+    }
+    host_config.binds = (!binds.is_empty()).then_some(binds);
+
+    let (networking_config, extra_networks) = networks(old, &host_config);
+    let config = Config {
+        hostname,
+        domainname: config.domainname,
+        user: own(config.user, old_image.and_then(|i| i.user.as_ref())),
+        attach_stdin: config.attach_stdin,
+        attach_stdout: config.attach_stdout,
+        attach_stderr: config.attach_stderr,
+        exposed_ports,
+        tty: config.tty,
+        open_stdin: config.open_stdin,
+        stdin_once: config.stdin_once,
+        env,
+        cmd: own(config.cmd, old_image.and_then(|i| i.cmd.as_ref())),
+        healthcheck: own(config.healthcheck, old_image.and_then(|i| i.healthcheck.as_ref())),
+        args_escaped: None,
+        image: Some(image.to_string()),
+        volumes,
+        working_dir: own(config.working_dir, old_image.and_then(|i| i.working_dir.as_ref())),
+        entrypoint: own(config.entrypoint, old_image.and_then(|i| i.entrypoint.as_ref())),
+        network_disabled: config.network_disabled,
+        mac_address: None,
+        on_build: None,
+        labels,
+        stop_signal: own(config.stop_signal, old_image.and_then(|i| i.stop_signal.as_ref())),
+        stop_timeout: config.stop_timeout,
+        shell: None,
+        host_config: Some(host_config),
+        networking_config,
+    };
+    (config, extra_networks)
+}
+
+/// `None` when the value came from the old image rather than the container.
+fn own<T: PartialEq>(value: Option<T>, image_value: Option<&T>) -> Option<T> {
+    match (&value, image_value) {
+        (Some(value), Some(image_value)) if value == image_value => None,
+        _ => value,
+    }
+}
+
+/// Splits the container's networks into the one it is created on (its
+/// network mode) and the rest. Only user settings are carried over; the
+/// engine assigns addresses and endpoint ids again.
+fn networks(
+    old: &ContainerInspectResponse,
+    host_config: &HostConfig,
+) -> (Option<NetworkingConfig<String>>, HashMap<String, EndpointSettings>) {
+    let old_id = old.id.as_deref().unwrap_or_default();
+    let mode = match host_config.network_mode.as_deref() {
+        Some("default") | None => "bridge",
+        Some(mode) => mode,
+    };
+    if mode == "host" || mode == "none" || mode.starts_with("container:") {
+        return (None, HashMap::new());
+    }
+
+    let mut endpoints: HashMap<String, EndpointSettings> = old
+        .network_settings
+        .as_ref()
+        .and_then(|n| n.networks.clone())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(network, endpoint)| {
+            let aliases = endpoint
+                .aliases
+                .map(|a| a.into_iter().filter(|a| !old_id.starts_with(a.as_str())).collect::<Vec<_>>())
+                .filter(|a| !a.is_empty());
+            let settings = EndpointSettings {
+                ipam_config: endpoint.ipam_config,
+                links: endpoint.links,
+                aliases,
+                driver_opts: endpoint.driver_opts,
+                ..Default::default()
+            };
+            (network, settings)
+        })
+        .collect();
+
+    let primary = endpoints.remove(mode).unwrap_or_default();
+    let networking_config = NetworkingConfig {
+        endpoints_config: HashMap::from([(mode.to_string(), primary)]),
+    };
+    (Some(networking_config), endpoints)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bollard::service::{MountPoint, NetworkSettings};
+
+    fn options(image: Option<&str>, tag: Option<&str>) -> RecreateOptions {
+        RecreateOptions { image: image.map(Into::into), tag: tag.map(Into::into), ..Default::default() }
+    }
+
+    #[test]
+    fn target_image_keeps_or_retags_the_current_reference() {
+        let target = |current, image, tag| target_image(current, &options(image, tag)).map_err(|e| e.to_string());
+
+        assert_eq!(target(Some("nginx:1.25"), None, None).unwrap(), "nginx:1.25");
+        assert_eq!(target(Some("nginx:1.25"), None, Some("1.27")).unwrap(), "nginx:1.27");
+        assert_eq!(target(Some("nginx"), None, Some(" 1.27 ")).unwrap(), "nginx:1.27");
+        assert_eq!(target(Some("localhost:5000/app:v1"), None, Some("v2")).unwrap(), "localhost:5000/app:v2");
+        assert_eq!(target(Some("app@sha256:abc"), None, Some("v2")).unwrap(), "app:v2");
+        assert_eq!(target(Some("sha256:abc"), Some(" redis:7 "), None).unwrap(), "redis:7");
+
+        assert!(target(Some("nginx"), Some("redis"), Some("7")).is_err());
+        assert!(target(Some("nginx"), Some(" "), None).is_err());
+        assert!(target(Some("nginx"), None, Some("")).is_err());
+        assert!(target(Some("nginx"), None, Some("evil/app")).is_err());
+        assert!(target(Some("sha256:abc"), None, None).is_err());
+        assert!(target(None, None, Some("v2")).is_err());
+    }
+
+    #[test]
+    fn build_config_drops_image_defaults_and_keeps_user_settings() {
+        let old = ContainerInspectResponse {
+            id: Some("0123456789abcdef".into()),
+            mounts: Some(vec![
+                MountPoint {
+                    typ: Some(MountPointTypeEnum::VOLUME),
+                    name: Some("anon".into()),
+                    destination: Some("/data".into()),
+                    rw: Some(false),
+                    ..Default::default()
+                },
+                MountPoint {
+                    typ: Some(MountPointTypeEnum::VOLUME),
+                    name: Some("named".into()),
+                    destination: Some("/config".into()),
+                    ..Default::default()
+                },
+            ]),
+            network_settings: Some(NetworkSettings {
+                networks: Some(HashMap::from([
+                    (
+                        "web".to_string(),
+                        EndpointSettings {
+                            aliases: Some(vec!["0123456789ab".into(), "api".into()]),
+                            ip_address: Some("172.18.0.2".into()),
+                            ..Default::default()
+                        },
+                    ),
+                    ("backend".to_string(), EndpointSettings::default()),
+                ])),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let image = ImageConfig {
+            env: Some(vec!["PATH=/usr/bin".into()]),
+            cmd: Some(vec!["nginx".into()]),
+            labels: Some(HashMap::from([("maintainer".to_string(), "upstream".to_string())])),
+            ..Default::default()
+        };
+        let config = ContainerConfig {
+            hostname: Some("0123456789ab".into()),
+            env: Some(vec!["PATH=/usr/bin".into(), "MODE=prod".into()]),
+            cmd: Some(vec!["nginx".into()]),
+            labels: Some(HashMap::from([
+                ("maintainer".to_string(), "upstream".to_string()),
+                ("team".to_string(), "ops".to_string()),
+            ])),
+            ..Default::default()
+        };
+        let host_config = HostConfig {
+            binds: Some(vec!["named:/config".into()]),
+            network_mode: Some("web".into()),
+            ..Default::default()
+        };
+
+        let (config, extra) = build_config(&old, config, host_config, "nginx:1.27", Some(&image));
+
+        assert_eq!(config.image.as_deref(), Some("nginx:1.27"));
+        assert_eq!(config.hostname, None);
+        assert_eq!(config.env, Some(vec!["MODE=prod".to_string()]));
+        assert_eq!(config.cmd, None);
+        assert_eq!(config.labels, Some(HashMap::from([("team".to_string(), "ops".to_string())])));
+        assert_eq!(
+            config.host_config.unwrap().binds,
+            Some(vec!["named:/config".to_string(), "anon:/data:ro".to_string()])
+        );
+
+        let endpoints = config.networking_config.unwrap().endpoints_config;
+        let primary = &endpoints["web"];
+        assert_eq!(primary.aliases, Some(vec!["api".to_string()]));
+        assert_eq!(primary.ip_address, None);
+        assert_eq!(extra.keys().collect::<Vec<_>>(), ["backend"]);
+    }
+
+    #[test]
+    fn build_config_skips_networks_in_host_mode() {
+        let host_config = HostConfig { network_mode: Some("host".into()), ..Default::default() };
+        let (config, extra) = build_config(&Default::default(), Default::default(), host_config, "app", None);
+        assert!(config.networking_config.is_none());
+        assert!(extra.is_empty());
+    }
+}
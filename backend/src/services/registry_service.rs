@@ -0,0 +1,658 @@
+use crate::db::Database;
+use crate::services::cipher::Cipher;
+use crate::services::docker_service::split_tag;
+use crate::services::net;
+use axum::http::{header, HeaderMap, Method, StatusCode};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use bollard::auth::DockerCredentials;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Manifest media types accepted when resolving a tag to its digest.
+const MANIFEST_TYPES: [&str; 4] = [
+    "application/vnd.oci.image.index.v1+json",
+    "application/vnd.docker.distribution.manifest.list.v2+json",
+    "application/vnd.docker.distribution.manifest.v2+json",
+    "application/vnd.oci.image.manifest.v1+json",
+];
+
+/// Host that image references without a registry resolve to.
+pub const DOCKER_HUB: &str = "docker.io";
+
+#[derive(Debug)]
+pub enum RegistryError {
+    NotFound,
+    HostTaken,
+    InvalidInput(String),
+    /// The registry rejected or couldn't be reached by a test login.
+    Login(String),
+    Internal(anyhow::Error),
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistryError::NotFound => write!(f, "Registry not found"),
+            RegistryError::HostTaken => write!(f, "Credentials for this registry already exist"),
+            RegistryError::InvalidInput(msg) => write!(f, "{}", msg),
+            RegistryError::Login(msg) => write!(f, "Login failed: {}", msg),
+            RegistryError::Internal(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<sqlx::Error> for RegistryError {
+    fn from(e: sqlx::Error) -> Self {
+        RegistryError::Internal(e.into())
+    }
+}
+
+impl From<anyhow::Error> for RegistryError {
+    fn from(e: anyhow::Error) -> Self {
+        RegistryError::Internal(e)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, RegistryError>;
+
+/// Stored credentials. The token is never returned.
+#[derive(Serialize)]
+pub struct Registry {
+    pub id: String,
+    pub url: String,
+    /// Registry host matched against image references, e.g. `ghcr.io`.
+    pub host: String,
+    pub username: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct RegistryInput {
+    pub url: String,
+    pub username: String,
+    /// Password or access token. Required on create; omit on update to keep
+    /// the stored one.
+    pub token: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct RegistryRow {
+    id: String,
+    url: String,
+    host: String,
+    username: String,
+    token: String,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl RegistryRow {
+    fn into_registry(self) -> Registry {
+        Registry {
+            id: self.id,
+            url: self.url,
+            host: self.host,
+            username: self.username,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+        }
+    }
+}
+
+pub struct RegistryService {
+    db: Arc<Database>,
+    cipher: Cipher,
+}
+
+impl RegistryService {
+    pub fn new(db: Arc<Database>, secret: &str) -> Self {
+        Self { db, cipher: Cipher::new(secret, b"registry-credentials") }
+    }
+
+    pub async fn list(&self) -> Result<Vec<Registry>> {
+        let rows: Vec<RegistryRow> = sqlx::query_as("SELECT * FROM registries ORDER BY host")
+            .fetch_all(&self.db.pool)
+            .await?;
+        Ok(rows.into_iter().map(RegistryRow::into_registry).collect())
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Registry> {
+        Ok(self.row(id).await?.into_registry())
+    }
+
+    async fn row(&self, id: &str) -> Result<RegistryRow> {
+        let row: Option<RegistryRow> = sqlx::query_as("SELECT * FROM registries WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.db.pool)
+            .await?;
+        row.ok_or(RegistryError::NotFound)
+    }
+
+    pub async fn create(&self, input: RegistryInput) -> Result<Registry> {
+        let (url, host, username) = validate(&input)?;
+        let token = match input.token.as_deref() {
+            Some(token) if !token.is_empty() => token,
+            _ => return Err(RegistryError::InvalidInput("Token must not be empty".into())),
+        };
+        let id = uuid::Uuid::new_v4().to_string();
+        let now = Utc::now();
+
+        let result = sqlx::query(
+            "INSERT INTO registries (id, url, host, username, token, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&id)
+        .bind(url)
+        .bind(&host)
+        .bind(username)
+        .bind(self.cipher.seal(&id, token))
+        .bind(now)
+        .bind(now)
+        .execute(&self.db.pool)
+        .await;
+
+        match result {
+            Ok(_) => self.get(&id).await,
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Err(RegistryError::HostTaken),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Replaces the URL and username, and the token if one is given.
+    pub async fn update(&self, id: &str, input: RegistryInput) -> Result<Registry> {
+        let existing = self.row(id).await?;
+        let (url, host, username) = validate(&input)?;
+        let token = match input.token.as_deref() {
+            Some(token) if !token.is_empty() => self.cipher.seal(id, token),
+            _ => existing.token,
+        };
+
+        let result = sqlx::query("UPDATE registries SET url = ?, host = ?, username = ?, token = ?, updated_at = ? WHERE id = ?")
+            .bind(url)
+            .bind(&host)
+            .bind(username)
+            .bind(token)
+            .bind(Utc::now())
+            .bind(id)
+            .execute(&self.db.pool)
+            .await;
+
+        match result {
+            Ok(_) => self.get(id).await,
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Err(RegistryError::HostTaken),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn delete(&self, id: &str) -> Result<()> {
+        let result = sqlx::query("DELETE FROM registries WHERE id = ?")
+            .bind(id)
+            .execute(&self.db.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(RegistryError::NotFound);
+        }
+        Ok(())
+    }
+
+    /// Logs in to the registry with the stored credentials.
+    pub async fn test_login(&self, id: &str) -> Result<()> {
+        let row = self.row(id).await?;
+        let token = self.cipher.open(&row.id, &row.token, "registry token")?;
+        login(&api_base(&row.url, &row.host), &row.username, &token)
+            .await
+            .map_err(RegistryError::Login)
+    }
+
+    /// Credentials of a registry picked explicitly by the client. They are
+    /// only handed out for an image hosted on that registry, so they can't be
+    /// sent to whichever host the image happens to name.
+    pub async fn credentials(&self, id: &str, image: &str) -> Result<DockerCredentials> {
+        let row = self.row(id).await?;
+        let host = image_host(image);
+        if host != row.host {
+            return Err(RegistryError::InvalidInput(format!(
+                "Image {} is hosted on {}, not on registry {}",
+                image, host, row.host
+            )));
+        }
+        self.to_credentials(&row)
+    }
+
+    /// Credentials for the registry an image is pulled from or pushed to, if
+    /// any are stored.
+    pub async fn credentials_for(&self, image: &str) -> Result<Option<DockerCredentials>> {
+        let row: Option<RegistryRow> = sqlx::query_as("SELECT * FROM registries WHERE host = ?")
+            .bind(image_host(image))
+            .fetch_optional(&self.db.pool)
+            .await?;
+        row.map(|row| self.to_credentials(&row)).transpose()
+    }
+
+    /// Every stored registry's credentials, keyed the way the Docker CLI
+    /// keys them, for builds that may pull base images from any registry.
+    pub async fn all_credentials(&self) -> Result<HashMap<String, DockerCredentials>> {
+        let rows: Vec<RegistryRow> = sqlx::query_as("SELECT * FROM registries")
+            .fetch_all(&self.db.pool)
+            .await?;
+        rows.iter()
+            .map(|row| Ok((config_key(&row.host), self.to_credentials(row)?)))
+            .collect()
+    }
+
+    /// A Docker CLI `config.json` holding every stored registry, for tools
+    /// that read credentials from disk. `None` if there are none.
+    pub async fn docker_config(&self) -> Result<Option<serde_json::Value>> {
+        let credentials = self.all_credentials().await?;
+        if credentials.is_empty() {
+            return Ok(None);
+        }
+
+        let auths: serde_json::Map<_, _> = credentials
+            .into_iter()
+            .map(|(key, credentials)| {
+                let auth = format!("{}:{}", credentials.username.unwrap_or_default(), credentials.password.unwrap_or_default());
+                (key, serde_json::json!({ "auth": BASE64.encode(auth) }))
+            })
+            .collect();
+        Ok(Some(serde_json::json!({ "auths": auths })))
+    }
+
+    /// Digest the registry currently serves for an image's tag, the one
+    /// `docker pull` would resolve it to. Multi-platform images resolve to
+    /// their index, matching the repo digest Docker records on pull.
+    pub async fn remote_digest(&self, image: &str) -> Result<String> {
+        let host = image_host(image);
+        let row: Option<RegistryRow> = sqlx::query_as("SELECT * FROM registries WHERE host = ?")
+            .bind(&host)
+            .fetch_optional(&self.db.pool)
+            .await?;
+        let api = api_base(row.as_ref().map_or("", |r| r.url.as_str()), &host);
+        let basic = match &row {
+            Some(row) => {
+                let token = self.cipher.open(&row.id, &row.token, "registry token")?;
+                Some((row.username.clone(), format!("Basic {}", BASE64.encode(format!("{}:{}", row.username, token)))))
+            }
+            None => None,
+        };
+
+        let (repository, reference) = manifest_path(image, &host);
+        let url = format!("{}/v2/{}/manifests/{}", api, repository, reference);
+        let fail = |e: String| RegistryError::Internal(anyhow::anyhow!("{}: {}", image, e));
+        let headers = |authorization: &Option<String>| {
+            let mut headers = vec![(header::ACCEPT.as_str(), MANIFEST_TYPES.join(", "))];
+            headers.extend(authorization.clone().map(|a| (header::AUTHORIZATION.as_str(), a)));
+            headers
+        };
+
+        let mut response = net::send(Method::HEAD, &url, &headers(&None), Vec::new())
+            .await
+            .map_err(|e| fail(format!("{:#}", e)))?;
+        let mut authorization = None;
+        if response.status == StatusCode::UNAUTHORIZED {
+            let scope = format!("repository:{}:pull", repository);
+            let account = basic.as_ref().map(|(username, _)| username.as_str());
+            let auth_url = authorization_url(&api, &response.headers, account, Some(&scope)).map_err(fail)?;
+            authorization = Some(if auth_url.ends_with("/v2/") {
+                basic.map(|(_, basic)| basic).ok_or_else(|| fail("registry requires credentials".into()))?
+            } else {
+                format!("Bearer {}", bearer_token(&auth_url, basic.map(|(_, basic)| basic)).await.map_err(fail)?)
+            });
+            response = net::send(Method::HEAD, &url, &headers(&authorization), Vec::new())
+                .await
+                .map_err(|e| fail(format!("{:#}", e)))?;
+        }
+
+        match response.status {
+            status if status.is_success() => {}
+            StatusCode::NOT_FOUND => return Err(fail("tag not found in registry".into())),
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => return Err(fail("access denied by registry".into())),
+            status => return Err(fail(format!("registry responded with {}", status))),
+        }
+        if let Some(digest) = response.headers.get("docker-content-digest").and_then(|v| v.to_str().ok()) {
+            return Ok(digest.to_string());
+        }
+
+        // Not every registry sends the digest header; hash the manifest instead
+        let response = net::send(Method::GET, &url, &headers(&authorization), Vec::new())
+            .await
+            .map_err(|e| fail(format!("{:#}", e)))?;
+        if !response.status.is_success() {
+            return Err(fail(format!("registry responded with {}", response.status)));
+        }
+        Ok(format!("sha256:{}", hex::encode(Sha256::digest(&response.body))))
+    }
+
+    fn to_credentials(&self, row: &RegistryRow) -> Result<DockerCredentials> {
+        Ok(DockerCredentials {
+            username: Some(row.username.clone()),
+            password: Some(self.cipher.open(&row.id, &row.token, "registry token")?),
+            serveraddress: Some(row.host.clone()),
+            ..Default::default()
+        })
+    }
+}
+
+/// Checks the input and returns the trimmed URL, its host and the username.
+fn validate(input: &RegistryInput) -> Result<(&str, String, &str)> {
+    let url = input.url.trim();
+    if let Some((scheme, _)) = url.split_once("://") {
+        if scheme != "http" && scheme != "https" {
+            return Err(RegistryError::InvalidInput(format!("Invalid registry URL {:?}: expected http:// or https://", url)));
+        }
+    }
+    let host = normalize_host(url);
+    if host.is_empty() || host.contains(char::is_whitespace) {
+        return Err(RegistryError::InvalidInput(format!("Invalid registry URL {:?}", url)));
+    }
+    let username = input.username.trim();
+    if username.is_empty() {
+        return Err(RegistryError::InvalidInput("Username must not be empty".into()));
+    }
+    Ok((url, host, username))
+}
+
+/// Lowercased host (and port) of a registry URL, with Docker Hub's aliases
+/// folded into [`DOCKER_HUB`].
+pub fn normalize_host(url: &str) -> String {
+    let url = url.trim();
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let host = rest.split('/').next().unwrap_or_default().to_lowercase();
+    match host.as_str() {
+        "index.docker.io" | "registry-1.docker.io" | "registry.hub.docker.com" => DOCKER_HUB.into(),
+        _ => host,
+    }
+}
+
+/// Key of a registry in Docker CLI config files; Docker Hub is still keyed
+/// by its legacy v1 index URL.
+fn config_key(host: &str) -> String {
+    if host == DOCKER_HUB {
+        "https://index.docker.io/v1/".into()
+    } else {
+        host.into()
+    }
+}
+
+/// Registry host of an image reference, following Docker's rule that the
+/// first path component is a registry only if it looks like a host name.
+pub fn image_host(image: &str) -> String {
+    match image.split_once('/') {
+        Some((first, _)) if first.contains('.') || first.contains(':') || first == "localhost" => normalize_host(first),
+        _ => DOCKER_HUB.into(),
+    }
+}
+
+/// Base URL of the registry's v2 API.
+pub fn api_base(url: &str, host: &str) -> String {
+    if host == DOCKER_HUB {
+        "https://registry-1.docker.io".into()
+    } else if url.trim().starts_with("http://") {
+        format!("http://{}", host)
+    } else {
+        format!("https://{}", host)
+    }
+}
+
+/// Checks credentials against the registry's v2 API, answering its auth
+/// challenge the way `docker login` does. Errors are meant for the user.
+async fn login(api: &str, username: &str, token: &str) -> std::result::Result<(), String> {
+    let response = net::send(Method::GET, &format!("{}/v2/", api), &[], Vec::new())
+        .await
+        .map_err(|e| format!("{:#}", e))?;
+    match response.status {
+        // The registry allows anonymous access; there is nothing to check
+        status if status.is_success() => return Ok(()),
+        StatusCode::UNAUTHORIZED => {}
+        status => return Err(format!("Registry responded with {}: {}", status, response.snippet())),
+    }
+
+    let basic = format!("Basic {}", BASE64.encode(format!("{}:{}", username, token)));
+    let url = authorization_url(api, &response.headers, Some(username), None)?;
+    let response = net::send(Method::GET, &url, &[(header::AUTHORIZATION.as_str(), basic)], Vec::new())
+        .await
+        .map_err(|e| format!("{:#}", e))?;
+    match response.status {
+        status if status.is_success() => Ok(()),
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Err("Invalid username or token".into()),
+        status => Err(format!("Registry responded with {}: {}", status, response.snippet())),
+    }
+}
+
+/// Repository path and tag of an image on its registry's API. Docker Hub
+/// keeps official images under `library/`.
+fn manifest_path(image: &str, host: &str) -> (String, String) {
+    let path = match image.split_once('/') {
+        Some((first, rest)) if first.contains('.') || first.contains(':') || first == "localhost" => rest,
+        _ => image,
+    };
+    let (repository, reference) = match path.split_once('@') {
+        Some((repository, digest)) => (repository, digest),
+        None => split_tag(path),
+    };
+    let repository = if host == DOCKER_HUB && !repository.contains('/') {
+        format!("library/{}", repository)
+    } else {
+        repository.to_string()
+    };
+    (repository, reference.to_string())
+}
+
+/// Fetches a bearer token for a challenge, with basic credentials if any.
+async fn bearer_token(url: &str, basic: Option<String>) -> std::result::Result<String, String> {
+    let headers: Vec<_> = basic.map(|b| (header::AUTHORIZATION.as_str(), b)).into_iter().collect();
+    let response = net::send(Method::GET, url, &headers, Vec::new())
+        .await
+        .map_err(|e| format!("{:#}", e))?;
+    if !response.status.is_success() {
+        return Err(format!("token endpoint responded with {}: {}", response.status, response.snippet()));
+    }
+    let body: serde_json::Value = serde_json::from_slice(&response.body).map_err(|e| format!("invalid token response: {}", e))?;
+    body.get("token")
+        .or_else(|| body.get("access_token"))
+        .and_then(|t| t.as_str())
+        .map(str::to_string)
+        .ok_or_else(|| "token endpoint sent no token".to_string())
+}
+
+/// URL to send basic credentials to in answer to a 401 challenge: the token
+/// endpoint for bearer auth, or the API itself for basic auth.
+pub fn authorization_url(api: &str, headers: &HeaderMap, account: Option<&str>, scope: Option<&str>) -> std::result::Result<String, String> {
+    let challenge = headers
+        .get(header::WWW_AUTHENTICATE)
+        .and_then(|v| v.to_str().ok())
+        .ok_or("Registry sent no authentication challenge")?;
+    let (scheme, params) = parse_challenge(challenge);
+
+    match scheme.to_ascii_lowercase().as_str() {
+        "basic" => Ok(format!("{}/v2/", api)),
+        "bearer" => {
+            let realm = params.get("realm").ok_or("Registry sent a bearer challenge without a realm")?;
+            let mut query = Vec::new();
+            if let Some(service) = params.get("service") {
+                query.push(format!("service={}", percent_encode(service)));
+            }
+            if let Some(account) = account {
+                query.push(format!("account={}", percent_encode(account)));
+            }
+            if let Some(scope) = scope {
+                query.push(format!("scope={}", percent_encode(scope)));
+            }
+            let separator = if realm.contains('?') { '&' } else { '?' };
+            Ok(format!("{}{}{}", realm, separator, query.join("&")))
+        }
+        other => Err(format!("Unsupported registry auth scheme {:?}", other)),
+    }
+}
+
+/// Splits a `WWW-Authenticate` header into its scheme and parameters.
+/// Quoted values may contain commas, e.g. `scope="repository:a:pull,push"`.
+fn parse_challenge(challenge: &str) -> (String, HashMap<String, String>) {
+    let (scheme, rest) = challenge.trim().split_once(' ').unwrap_or((challenge.trim(), ""));
+    let mut params = HashMap::new();
+    let mut chars = rest.chars().peekable();
+
+    loop {
+        let key: String = chars.by_ref().skip_while(|c| *c == ',' || c.is_whitespace()).take_while(|c| *c != '=').collect();
+        if key.is_empty() {
+            break;
+        }
+        let mut value = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            value.extend(chars.by_ref().take_while(|c| *c != '"'));
+        } else {
+            value.extend(chars.by_ref().take_while(|c| *c != ','));
+        }
+        params.insert(key.trim().to_lowercase(), value);
+    }
+    (scheme.to_string(), params)
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::net::mock;
+
+    async fn service() -> RegistryService {
+        RegistryService::new(Arc::new(Database::in_memory().await), "0123456789abcdef0123456789abcdef")
+    }
+
+    fn input(url: &str) -> RegistryInput {
+        RegistryInput { url: url.into(), username: "deploy".into(), token: Some("s3cret".into()) }
+    }
+
+    #[test]
+    fn image_host_follows_docker_naming_rules() {
+        assert_eq!(image_host("nginx:1.27"), DOCKER_HUB);
+        assert_eq!(image_host("library/nginx"), DOCKER_HUB);
+        assert_eq!(image_host("ghcr.io/acme/app:v1"), "ghcr.io");
+        assert_eq!(image_host("localhost:5000/app"), "localhost:5000");
+        assert_eq!(image_host("index.docker.io/library/nginx"), DOCKER_HUB);
+    }
+
+    #[tokio::test]
+    async fn credentials_are_only_handed_out_for_their_own_host() {
+        let registries = service().await;
+        let registry = registries.create(input("https://ghcr.io")).await.unwrap();
+
+        let credentials = registries.credentials(&registry.id, "ghcr.io/acme/app:v1").await.unwrap();
+        assert_eq!(credentials.username.as_deref(), Some("deploy"));
+        assert_eq!(credentials.password.as_deref(), Some("s3cret"));
+
+        for image in ["evil.example.com/acme/app", "nginx:latest", "ghcr.io.evil.example.com/app"] {
+            let result = registries.credentials(&registry.id, image).await;
+            assert!(matches!(result, Err(RegistryError::InvalidInput(_))), "{}", image);
+        }
+        assert!(matches!(registries.credentials("missing", "ghcr.io/app").await, Err(RegistryError::NotFound)));
+    }
+
+    /// A registry with stored credentials for the mock server at `addr`.
+    async fn registry_at(addr: std::net::SocketAddr) -> RegistryService {
+        let registries = service().await;
+        registries.create(input(&format!("http://{}", addr))).await.unwrap();
+        registries
+    }
+
+    fn method_and_path(req: &mock::Received) -> (&str, &str) {
+        let mut parts = req.request_line().split(' ');
+        (parts.next().unwrap_or_default(), parts.next().unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn remote_digest_reads_the_digest_header() {
+        let (addr, mut requests) = mock::serve(|_| {
+            mock::response("200 OK", &[("Docker-Content-Digest", "sha256:abc")], "")
+        })
+        .await;
+        let registries = registry_at(addr).await;
+
+        let digest = registries.remote_digest(&format!("{}/acme/app:v1", addr)).await.unwrap();
+        assert_eq!(digest, "sha256:abc");
+
+        let req = requests.recv().await.unwrap();
+        assert_eq!(method_and_path(&req), ("HEAD", "/v2/acme/app/manifests/v1"));
+        assert!(req.header("accept").unwrap().contains("application/vnd.oci.image.index.v1+json"));
+        assert_eq!(req.header("authorization"), None);
+        assert!(requests.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn remote_digest_answers_a_bearer_challenge() {
+        let (addr, mut requests) = mock::serve(|req| {
+            let (method, path) = method_and_path(req);
+            if path.starts_with("/token") {
+                return mock::response("200 OK", &[("Content-Type", "application/json")], r#"{"token":"tok123"}"#);
+            }
+            if method == "HEAD" && req.header("authorization") == Some("Bearer tok123") {
+                return mock::response("200 OK", &[("Docker-Content-Digest", "sha256:def")], "");
+            }
+            let challenge = format!(
+                r#"Bearer realm="http://{}/token",service="registry.test",scope="repository:acme/app:pull""#,
+                req.header("host").unwrap_or_default()
+            );
+            mock::response("401 Unauthorized", &[("WWW-Authenticate", &challenge)], "")
+        })
+        .await;
+        let registries = registry_at(addr).await;
+
+        let digest = registries.remote_digest(&format!("{}/acme/app:v1", addr)).await.unwrap();
+        assert_eq!(digest, "sha256:def");
+
+        let challenged = requests.recv().await.unwrap();
+        assert_eq!(method_and_path(&challenged), ("HEAD", "/v2/acme/app/manifests/v1"));
+        let token = requests.recv().await.unwrap();
+        assert_eq!(
+            method_and_path(&token),
+            ("GET", "/token?service=registry.test&account=deploy&scope=repository%3Aacme%2Fapp%3Apull")
+        );
+        let basic = format!("Basic {}", BASE64.encode("deploy:s3cret"));
+        assert_eq!(token.header("authorization"), Some(basic.as_str()));
+        let retried = requests.recv().await.unwrap();
+        assert_eq!(method_and_path(&retried), ("HEAD", "/v2/acme/app/manifests/v1"));
+        assert_eq!(retried.header("authorization"), Some("Bearer tok123"));
+    }
+
+    #[tokio::test]
+    async fn remote_digest_hashes_the_manifest_without_a_digest_header() {
+        const MANIFEST: &str = r#"{"schemaVersion":2}"#;
+        let (addr, mut requests) = mock::serve(|req| match method_and_path(req).0 {
+            "GET" => mock::response("200 OK", &[("Content-Type", "application/vnd.oci.image.index.v1+json")], MANIFEST),
+            _ => mock::response("200 OK", &[], ""),
+        })
+        .await;
+        let registries = registry_at(addr).await;
+
+        let digest = registries.remote_digest(&format!("{}/acme/app", addr)).await.unwrap();
+        assert_eq!(digest, format!("sha256:{}", hex::encode(Sha256::digest(MANIFEST))));
+
+        assert_eq!(method_and_path(&requests.recv().await.unwrap()), ("HEAD", "/v2/acme/app/manifests/latest"));
+        let get = requests.recv().await.unwrap();
+        assert_eq!(method_and_path(&get), ("GET", "/v2/acme/app/manifests/latest"));
+        assert!(get.header("accept").unwrap().contains("application/vnd.docker.distribution.manifest.v2+json"));
+    }
+
+    #[tokio::test]
+    async fn remote_digest_reports_missing_tags() {
+        let (addr, _) = mock::serve(|_| mock::response("404 Not Found", &[], "")).await;
+        let registries = registry_at(addr).await;
+
+        let error = registries.remote_digest(&format!("{}/acme/app:gone", addr)).await.unwrap_err();
+        assert!(error.to_string().contains("tag not found"), "{}", error);
+    }
+}
@@ -0,0 +1,456 @@
+use crate::services::docker_service::DockerService;
+use crate::services::system_service::{HostCapacity, SystemService};
+use bollard::container::UpdateContainerOptions;
+use bollard::service::{HostConfig, RestartPolicy, RestartPolicyNameEnum};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt;
+use std::sync::Arc;
+
+/// Docker refuses memory limits below this.
+const MIN_MEMORY: i64 = 6 * 1024 * 1024;
+/// Used by the kernel when no CPU period is set.
+const DEFAULT_CPU_PERIOD: i64 = 100_000;
+const NANO_CPUS: i64 = 1_000_000_000;
+
+#[derive(Debug)]
+pub enum ResourceError {
+    NotFound,
+    InvalidInput(String),
+    Internal(anyhow::Error),
+}
+
+impl fmt::Display for ResourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResourceError::NotFound => write!(f, "Container not found"),
+            ResourceError::InvalidInput(msg) => write!(f, "{}", msg),
+            ResourceError::Internal(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<anyhow::Error> for ResourceError {
+    fn from(e: anyhow::Error) -> Self {
+        match e.downcast_ref::<bollard::errors::Error>() {
+            Some(bollard::errors::Error::DockerResponseServerError { status_code: 404, .. }) => ResourceError::NotFound,
+            // The engine's own checks, e.g. a kernel without swap limits
+            Some(bollard::errors::Error::DockerResponseServerError { status_code: 400, message }) => {
+                ResourceError::InvalidInput(message.clone())
+            }
+            _ => ResourceError::Internal(e),
+        }
+    }
+}
+
+/// New limits for a running or stopped container. Fields left out keep
+/// their current value; `0` means unlimited or the engine default.
+#[derive(Deserialize)]
+pub struct ResourceLimits {
+    /// Relative CPU weight, 2 to 262144 (default 1024).
+    pub cpu_shares: Option<i64>,
+    /// Microseconds, 1000 to 1000000.
+    pub cpu_period: Option<i64>,
+    /// Microseconds per period, at least 1000, or -1 for unlimited.
+    pub cpu_quota: Option<i64>,
+    /// CPUs in billionths, as `docker run --cpus` sets it. Can't be
+    /// combined with a period or quota.
+    pub nano_cpus: Option<i64>,
+    /// e.g. `0-3` or `0,2`. Empty allows every CPU.
+    pub cpuset_cpus: Option<String>,
+    /// NUMA memory nodes, in the same format.
+    pub cpuset_mems: Option<String>,
+    /// Bytes.
+    pub memory: Option<i64>,
+    /// Soft limit in bytes.
+    pub memory_reservation: Option<i64>,
+    /// Memory plus swap in bytes, or -1 for unlimited swap.
+    pub memory_swap: Option<i64>,
+    /// Maximum number of processes, or -1 for unlimited.
+    pub pids_limit: Option<i64>,
+    /// Relative block IO weight, 10 to 1000.
+    pub blkio_weight: Option<u16>,
+    pub restart_policy: Option<RestartPolicyUpdate>,
+}
+
+#[derive(Deserialize)]
+pub struct RestartPolicyUpdate {
+    pub name: RestartPolicyNameEnum,
+    /// Only for `on-failure`.
+    pub maximum_retry_count: Option<i64>,
+}
+
+/// The engine's values after the update, for fields that changed.
+#[derive(Serialize)]
+pub struct ResourceUpdateReport {
+    pub id: String,
+    pub name: String,
+    pub changes: Vec<ResourceChange>,
+}
+
+#[derive(Serialize)]
+pub struct ResourceChange {
+    pub field: &'static str,
+    pub before: Value,
+    pub after: Value,
+}
+
+pub struct ResourceService {
+    docker: Arc<DockerService>,
+    system: Arc<SystemService>,
+}
+
+impl ResourceService {
+    pub fn new(docker: Arc<DockerService>, system: Arc<SystemService>) -> Self {
+        Self { docker, system }
+    }
+
+    /// Applies the limits without restarting the container and reports what
+    /// the engine changed. Limits beyond what the host has are rejected.
+    pub async fn update(&self, id: &str, limits: ResourceLimits) -> Result<ResourceUpdateReport, ResourceError> {
+        let container = self.docker.inspect_container(id).await?;
+        let current = container.host_config.unwrap_or_default();
+        validate(&limits, &current, &self.system.capacity()).map_err(ResourceError::InvalidInput)?;
+
+        let id = container.id.unwrap_or_else(|| id.to_string());
+        let options = UpdateContainerOptions::<String> {
+            cpu_shares: limits.cpu_shares.map(|n| n as isize),
+            cpu_period: limits.cpu_period,
+            cpu_quota: limits.cpu_quota,
+            nano_cpus: limits.nano_cpus,
+            cpuset_cpus: limits.cpuset_cpus.map(|s| s.trim().to_string()),
+            cpuset_mems: limits.cpuset_mems.map(|s| s.trim().to_string()),
+            memory: limits.memory,
+            memory_reservation: limits.memory_reservation,
+            memory_swap: limits.memory_swap,
+            pids_limit: limits.pids_limit,
+            blkio_weight: limits.blkio_weight,
+            restart_policy: limits.restart_policy.map(|policy| RestartPolicy {
+                name: Some(policy.name),
+                maximum_retry_count: policy.maximum_retry_count,
+            }),
+            ..Default::default()
+        };
+        self.docker.update_container(&id, options).await?;
+
+        let updated = self.docker.inspect_container(&id).await?;
+        let after = snapshot(&updated.host_config.unwrap_or_default());
+        let changes = snapshot(&current)
+            .into_iter()
+            .zip(after)
+            .filter(|((_, before), (_, after))| before != after)
+            .map(|((field, before), (_, after))| ResourceChange { field, before, after })
+            .collect();
+
+        Ok(ResourceUpdateReport {
+            id,
+            name: container.name.unwrap_or_default().trim_start_matches('/').to_string(),
+            changes,
+        })
+    }
+}
+
+fn snapshot(host: &HostConfig) -> Vec<(&'static str, Value)> {
+    let restart_policy = host.restart_policy.as_ref().map(|policy| {
+        json!({
+            "name": policy.name.map(|n| n.to_string()).unwrap_or_default(),
+            "maximum_retry_count": policy.maximum_retry_count.unwrap_or(0),
+        })
+    });
+    vec![
+        ("cpu_shares", json!(host.cpu_shares)),
+        ("cpu_period", json!(host.cpu_period)),
+        ("cpu_quota", json!(host.cpu_quota)),
+        ("nano_cpus", json!(host.nano_cpus)),
+        ("cpuset_cpus", json!(host.cpuset_cpus)),
+        ("cpuset_mems", json!(host.cpuset_mems)),
+        ("memory", json!(host.memory)),
+        ("memory_reservation", json!(host.memory_reservation)),
+        ("memory_swap", json!(host.memory_swap)),
+        ("pids_limit", json!(host.pids_limit)),
+        ("blkio_weight", json!(host.blkio_weight)),
+        ("restart_policy", json!(restart_policy)),
+    ]
+}
+
+/// Checks each limit on its own, then the combination of the new values
+/// with the ones being kept.
+fn validate(limits: &ResourceLimits, current: &HostConfig, host: &HostCapacity) -> Result<(), String> {
+    if limits.cpu_shares.is_none()
+        && limits.cpu_period.is_none()
+        && limits.cpu_quota.is_none()
+        && limits.nano_cpus.is_none()
+        && limits.cpuset_cpus.is_none()
+        && limits.cpuset_mems.is_none()
+        && limits.memory.is_none()
+        && limits.memory_reservation.is_none()
+        && limits.memory_swap.is_none()
+        && limits.pids_limit.is_none()
+        && limits.blkio_weight.is_none()
+        && limits.restart_policy.is_none()
+    {
+        return Err("Nothing to update".to_string());
+    }
+
+    let cpus = host.cpus.max(1) as i64;
+    let memory_total = host.memory_total as i64;
+    let gib = |bytes: i64| bytes as f64 / (1024.0 * 1024.0 * 1024.0);
+
+    if let Some(shares) = limits.cpu_shares {
+        if shares != 0 && !(2..=262_144).contains(&shares) {
+            return Err("cpu_shares must be between 2 and 262144, or 0 for the default".to_string());
+        }
+    }
+    if let Some(period) = limits.cpu_period {
+        if period != 0 && !(1_000..=1_000_000).contains(&period) {
+            return Err("cpu_period must be between 1000 and 1000000 microseconds".to_string());
+        }
+    }
+    if let Some(quota) = limits.cpu_quota {
+        if quota != 0 && quota != -1 && quota < 1_000 {
+            return Err("cpu_quota must be at least 1000 microseconds, or -1 for unlimited".to_string());
+        }
+    }
+    if let Some(nano_cpus) = limits.nano_cpus {
+        if nano_cpus < 0 {
+            return Err("nano_cpus must not be negative".to_string());
+        }
+        if nano_cpus > cpus * NANO_CPUS {
+            return Err(format!(
+                "nano_cpus allows {:.2} CPUs but the host has {}",
+                nano_cpus as f64 / NANO_CPUS as f64,
+                cpus
+            ));
+        }
+    }
+    if let Some(cpuset) = limits.cpuset_cpus.as_deref() {
+        if let Some(cpu) = highest_in_cpuset(cpuset).map_err(|e| format!("cpuset_cpus: {}", e))? {
+            if cpu as i64 >= cpus {
+                return Err(format!("cpuset_cpus includes CPU {} but the host has {} (0-{})", cpu, cpus, cpus - 1));
+            }
+        }
+    }
+    if let Some(cpuset) = limits.cpuset_mems.as_deref() {
+        highest_in_cpuset(cpuset).map_err(|e| format!("cpuset_mems: {}", e))?;
+    }
+    if let Some(memory) = limits.memory {
+        if memory != 0 && memory < MIN_MEMORY {
+            return Err("memory must be at least 6 MiB, or 0 for unlimited".to_string());
+        }
+        if memory > memory_total {
+            return Err(format!("memory is {:.2} GiB but the host has {:.2} GiB", gib(memory), gib(memory_total)));
+        }
+    }
+    if let Some(reservation) = limits.memory_reservation {
+        if reservation < 0 {
+            return Err("memory_reservation must not be negative".to_string());
+        }
+        if reservation > memory_total {
+            return Err(format!(
+                "memory_reservation is {:.2} GiB but the host has {:.2} GiB",
+                gib(reservation),
+                gib(memory_total)
+            ));
+        }
+    }
+    if let Some(swap) = limits.memory_swap {
+        let available = memory_total + host.swap_total as i64;
+        if swap < -1 {
+            return Err("memory_swap must be a size in bytes, or -1 for unlimited".to_string());
+        }
+        if swap > available {
+            return Err(format!(
+                "memory_swap is {:.2} GiB but the host has {:.2} GiB of memory and swap",
+                gib(swap),
+                gib(available)
+            ));
+        }
+    }
+    if let Some(pids) = limits.pids_limit {
+        if pids < -1 {
+            return Err("pids_limit must be positive, or -1 for unlimited".to_string());
+        }
+    }
+    if let Some(weight) = limits.blkio_weight {
+        if weight != 0 && !(10..=1_000).contains(&weight) {
+            return Err("blkio_weight must be between 10 and 1000".to_string());
+        }
+    }
+    if let Some(policy) = &limits.restart_policy {
+        match (policy.name, policy.maximum_retry_count) {
+            (_, Some(n)) if n < 0 => return Err("maximum_retry_count must not be negative".to_string()),
+            (RestartPolicyNameEnum::ON_FAILURE, _) | (_, None | Some(0)) => {}
+            _ => return Err("maximum_retry_count only applies to the on-failure policy".to_string()),
+        }
+        let restarts = !matches!(policy.name, RestartPolicyNameEnum::NO | RestartPolicyNameEnum::EMPTY);
+        if restarts && current.auto_remove == Some(true) {
+            return Err("Containers started with --rm can't have a restart policy".to_string());
+        }
+    }
+
+    // Values after the update, for limits that depend on each other
+    let memory = limits.memory.or(current.memory).unwrap_or(0);
+    let reservation = limits.memory_reservation.or(current.memory_reservation).unwrap_or(0);
+    let swap = limits.memory_swap.or(current.memory_swap).unwrap_or(0);
+    if memory > 0 && reservation > memory {
+        return Err("memory_reservation must be below the memory limit".to_string());
+    }
+    if swap > 0 && memory == 0 {
+        return Err("memory_swap needs a memory limit".to_string());
+    }
+    if swap > 0 && swap < memory {
+        return Err("memory_swap includes memory, so it must be at least the memory limit".to_string());
+    }
+
+    let nano_cpus = limits.nano_cpus.or(current.nano_cpus).unwrap_or(0);
+    let period = limits.cpu_period.or(current.cpu_period).unwrap_or(0);
+    let quota = limits.cpu_quota.or(current.cpu_quota).unwrap_or(0);
+    if nano_cpus > 0 && (period > 0 || quota > 0) {
+        return Err("nano_cpus can't be combined with cpu_period or cpu_quota; set the other to 0".to_string());
+    }
+    if quota > 0 {
+        let period = if period > 0 { period } else { DEFAULT_CPU_PERIOD };
+        if quota > cpus * period {
+            return Err(format!(
+                "cpu_quota allows {:.2} CPUs but the host has {}",
+                quota as f64 / period as f64,
+                cpus
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses a list such as `0-3,5` and returns the highest number in it, or
+/// `None` when it is empty.
+fn highest_in_cpuset(value: &str) -> Result<Option<u32>, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(None);
+    }
+    let invalid = || format!("invalid list {:?}, expected e.g. 0-3 or 0,2", value);
+    let mut highest = 0;
+    for part in value.split(',') {
+        let (start, end) = part.split_once('-').unwrap_or((part, part));
+        let start: u32 = start.trim().parse().map_err(|_| invalid())?;
+        let end: u32 = end.trim().parse().map_err(|_| invalid())?;
+        if start > end {
+            return Err(invalid());
+        }
+        highest = highest.max(end);
+    }
+    Ok(Some(highest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GIB: i64 = 1024 * 1024 * 1024;
+
+    /// 4 CPUs, 8 GiB of memory and 2 GiB of swap.
+    fn host() -> HostCapacity {
+        HostCapacity { cpus: 4, memory_total: 8 * GIB as u64, swap_total: 2 * GIB as u64 }
+    }
+
+    fn check(limits: Value, current: &HostConfig) -> Result<(), String> {
+        validate(&serde_json::from_value(limits).unwrap(), current, &host())
+    }
+
+    #[test]
+    fn highest_in_cpuset_parses_lists_and_ranges() {
+        assert_eq!(highest_in_cpuset(""), Ok(None));
+        assert_eq!(highest_in_cpuset(" "), Ok(None));
+        assert_eq!(highest_in_cpuset("0"), Ok(Some(0)));
+        assert_eq!(highest_in_cpuset("0-3"), Ok(Some(3)));
+        assert_eq!(highest_in_cpuset("5,0-2"), Ok(Some(5)));
+        assert_eq!(highest_in_cpuset(" 1 - 2 , 7 "), Ok(Some(7)));
+
+        for value in ["a", "3-1", "0-", "-1", "0,,1", "1.5"] {
+            assert!(highest_in_cpuset(value).is_err(), "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn validate_accepts_limits_within_the_host() {
+        let current = HostConfig::default();
+        for limits in [
+            json!({"cpu_shares": 512}),
+            json!({"nano_cpus": 4 * NANO_CPUS}),
+            json!({"cpu_period": 50_000, "cpu_quota": 200_000}),
+            json!({"cpu_quota": -1}),
+            json!({"cpuset_cpus": "0-3", "cpuset_mems": "0"}),
+            json!({"memory": GIB, "memory_reservation": GIB / 2, "memory_swap": 2 * GIB}),
+            json!({"memory": 0}),
+            json!({"memory_swap": -1}),
+            json!({"pids_limit": -1, "blkio_weight": 500}),
+            json!({"restart_policy": {"name": "on-failure", "maximum_retry_count": 5}}),
+            json!({"restart_policy": {"name": "unless-stopped"}}),
+        ] {
+            assert_eq!(check(limits.clone(), &current), Ok(()), "{}", limits);
+        }
+    }
+
+    #[test]
+    fn validate_rejects_limits_out_of_range_or_beyond_the_host() {
+        let current = HostConfig::default();
+        for (limits, message) in [
+            (json!({}), "Nothing to update"),
+            (json!({"cpu_shares": 1}), "cpu_shares"),
+            (json!({"cpu_period": 999}), "cpu_period"),
+            (json!({"cpu_quota": 500}), "cpu_quota"),
+            (json!({"nano_cpus": 4 * NANO_CPUS + 1}), "the host has 4"),
+            (json!({"cpuset_cpus": "0-4"}), "includes CPU 4"),
+            (json!({"cpuset_mems": "x"}), "cpuset_mems"),
+            (json!({"memory": 1024}), "at least 6 MiB"),
+            (json!({"memory": 9 * GIB}), "the host has 8.00 GiB"),
+            (json!({"memory_reservation": -1}), "memory_reservation"),
+            (json!({"memory": GIB, "memory_swap": 11 * GIB}), "memory and swap"),
+            (json!({"memory_swap": -2}), "memory_swap"),
+            (json!({"pids_limit": -2}), "pids_limit"),
+            (json!({"blkio_weight": 5}), "blkio_weight"),
+            (json!({"restart_policy": {"name": "always", "maximum_retry_count": 3}}), "on-failure"),
+            (json!({"restart_policy": {"name": "on-failure", "maximum_retry_count": -1}}), "negative"),
+        ] {
+            let error = check(limits.clone(), &current).unwrap_err();
+            assert!(error.contains(message), "{}: {}", limits, error);
+        }
+    }
+
+    #[test]
+    fn validate_checks_new_values_against_the_ones_kept() {
+        let limited = HostConfig { memory: Some(GIB), nano_cpus: Some(NANO_CPUS), ..Default::default() };
+        assert!(check(json!({"memory_reservation": 2 * GIB}), &limited).unwrap_err().contains("below the memory limit"));
+        assert!(check(json!({"memory_swap": GIB / 2}), &limited).unwrap_err().contains("at least the memory limit"));
+        assert!(check(json!({"cpu_quota": 50_000}), &limited).unwrap_err().contains("can't be combined"));
+        assert_eq!(check(json!({"nano_cpus": 0, "cpu_quota": 50_000}), &limited), Ok(()));
+
+        let unlimited = HostConfig::default();
+        assert!(check(json!({"memory_swap": GIB}), &unlimited).unwrap_err().contains("needs a memory limit"));
+        // Quotas are relative to the default 100ms period when none is set
+        assert!(check(json!({"cpu_quota": 500_000}), &unlimited).unwrap_err().contains("5.00 CPUs"));
+
+        let removed_on_exit = HostConfig { auto_remove: Some(true), ..Default::default() };
+        let always = json!({"restart_policy": {"name": "always"}});
+        assert!(check(always, &removed_on_exit).unwrap_err().contains("--rm"));
+        assert_eq!(check(json!({"restart_policy": {"name": "no"}}), &removed_on_exit), Ok(()));
+    }
+
+    #[test]
+    fn snapshot_lists_every_field_in_a_fixed_order() {
+        let host = HostConfig {
+            memory: Some(GIB),
+            restart_policy: Some(RestartPolicy { name: Some(RestartPolicyNameEnum::ON_FAILURE), maximum_retry_count: Some(3) }),
+            ..Default::default()
+        };
+        let fields = snapshot(&host);
+        assert_eq!(fields.first().map(|(f, _)| *f), Some("cpu_shares"));
+        assert_eq!(fields.iter().find(|(f, _)| *f == "memory").unwrap().1, json!(GIB));
+        assert_eq!(
+            fields.iter().find(|(f, _)| *f == "restart_policy").unwrap().1,
+            json!({"name": "on-failure", "maximum_retry_count": 3})
+        );
+        assert_eq!(snapshot(&HostConfig::default()).len(), fields.len());
+    }
+}
@@ -2,6 +2,10 @@ use crate::db::Database;
 use std::sync::Arc;
 use anyhow::Result;
 
+/// Keys with this prefix are managed by the server itself and are neither
+/// listed nor writable through the settings API.
+pub const INTERNAL_PREFIX: &str = "internal.";
+
 pub struct SettingsService {
     db: Arc<Database>,
 }
@@ -35,6 +39,63 @@ impl SettingsService {
             .fetch_all(&self.db.pool)
             .await?;
         
-        Ok(rows.into_iter().collect())
+        Ok(rows
+            .into_iter()
+            .filter(|(key, _)| !key.starts_with(INTERNAL_PREFIX))
+            .collect())
+    }
+}
+
+/// Parses a boolean setting value.
+pub fn parse_bool(value: &str) -> std::result::Result<bool, String> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err("expected true or false".to_string()),
+    }
+}
+
+/// Parses a whole-number setting value within `min..=max`.
+pub fn parse_number(value: &str, min: u32, max: u32) -> std::result::Result<u32, String> {
+    value
+        .parse::<u32>()
+        .ok()
+        .filter(|n| (min..=max).contains(n))
+        .ok_or_else(|| format!("expected a whole number between {} and {}", min, max))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_bool_accepts_only_true_and_false() {
+        assert_eq!(parse_bool("true"), Ok(true));
+        assert_eq!(parse_bool("false"), Ok(false));
+        for value in ["", "True", "1", "yes", " true"] {
+            assert!(parse_bool(value).is_err(), "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn parse_number_checks_the_range() {
+        assert_eq!(parse_number("1", 1, 24), Ok(1));
+        assert_eq!(parse_number("24", 1, 24), Ok(24));
+        for value in ["0", "25", "-1", "1.5", "", "4294967296"] {
+            let error = parse_number(value, 1, 24).unwrap_err();
+            assert_eq!(error, "expected a whole number between 1 and 24", "{:?}", value);
+        }
+    }
+
+    #[tokio::test]
+    async fn internal_settings_are_not_listed() {
+        let settings = SettingsService::new(Arc::new(Database::in_memory().await));
+        settings.set_setting("theme", "dark").await.unwrap();
+        settings.set_setting("internal.updates.last_check_at", "now").await.unwrap();
+
+        let all = settings.get_all_settings().await.unwrap();
+        assert_eq!(all.len(), 1);
+        assert_eq!(all["theme"], "dark");
+        assert_eq!(settings.get_setting("internal.updates.last_check_at").await.unwrap().as_deref(), Some("now"));
     }
 }
@@ -2,6 +2,8 @@ use sysinfo::{System, Disks, Networks};
 use std::sync::{Arc, Mutex};
 use serde::Serialize;
 
+const READ_ONLY_FILESYSTEMS: &[&str] = &["squashfs", "iso9660", "erofs"];
+
 #[derive(Serialize, Clone)]
 pub struct SystemStats {
     pub cpu_usage: f32,
@@ -15,10 +17,37 @@ pub struct SystemStats {
     pub uptime: u64,
 }
 
+impl SystemStats {
+    pub fn memory_percent(&self) -> f64 {
+        if self.memory_total == 0 {
+            return 0.0;
+        }
+        self.memory_used as f64 / self.memory_total as f64 * 100.0
+    }
+
+    /// Usage of the fullest writable disk. Read-only image filesystems such
+    /// as snap packages always report full and are ignored.
+    pub fn disk_percent(&self) -> f64 {
+        self.disks
+            .iter()
+            .filter(|d| d.total_space > 0 && !READ_ONLY_FILESYSTEMS.contains(&d.file_system.as_str()))
+            .map(|d| (d.total_space - d.available_space) as f64 / d.total_space as f64 * 100.0)
+            .fold(0.0, f64::max)
+    }
+
+    /// Bytes received and transmitted on all interfaces since boot.
+    pub fn network_totals(&self) -> (u64, u64) {
+        self.networks
+            .iter()
+            .fold((0, 0), |(rx, tx), n| (rx + n.total_received, tx + n.total_transmitted))
+    }
+}
+
 #[derive(Serialize, Clone)]
 pub struct DiskInfo {
     pub name: String,
     pub mount_point: String,
+    pub file_system: String,
     pub total_space: u64,
     pub available_space: u64,
 }
@@ -28,6 +57,16 @@ pub struct NetworkInfo {
     pub interface: String,
     pub received: u64,
     pub transmitted: u64,
+    pub total_received: u64,
+    pub total_transmitted: u64,
+}
+
+/// What the host can hand out to containers.
+#[derive(Serialize, Clone)]
+pub struct HostCapacity {
+    pub cpus: usize,
+    pub memory_total: u64,
+    pub swap_total: u64,
 }
 
 pub struct SystemService {
@@ -59,6 +98,7 @@ impl SystemService {
         let disks = disks_list.iter().map(|d| DiskInfo {
             name: format!("{:?}", d.name()),
             mount_point: d.mount_point().to_string_lossy().to_string(),
+            file_system: d.file_system().to_string_lossy().to_string(),
             total_space: d.total_space(),
             available_space: d.available_space(),
         }).collect();
@@ -68,6 +108,8 @@ impl SystemService {
             interface: name.clone(),
             received: data.received(),
             transmitted: data.transmitted(),
+            total_received: data.total_received(),
+            total_transmitted: data.total_transmitted(),
         }).collect();
 
         SystemStats {
@@ -82,4 +124,17 @@ impl SystemService {
             uptime,
         }
     }
+
+    /// Cheaper than `get_stats`, which also refreshes processes, disks and
+    /// networks.
+    pub fn capacity(&self) -> HostCapacity {
+        let mut sys = self.sys.lock().unwrap();
+        sys.refresh_memory();
+
+        HostCapacity {
+            cpus: sys.cpus().len(),
+            memory_total: sys.total_memory(),
+            swap_total: sys.total_swap(),
+        }
+    }
 }
@@ -0,0 +1,627 @@
+use crate::db::Database;
+use crate::services::docker_service::{split_tag, DockerService};
+use crate::services::recreate_service::{not_found, RecreateError, RecreateOptions, RecreateReport, RecreateService};
+use crate::services::registry_service::RegistryService;
+use crate::services::settings_service::{parse_bool, parse_number, SettingsService};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveTime, Timelike, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+/// Settings keys. All are optional; see [`UpdateSchedule`] for defaults.
+pub const SETTING_ENABLED: &str = "updates.enabled";
+pub const SETTING_INTERVAL_HOURS: &str = "updates.interval_hours";
+/// `HH:MM-HH:MM` in UTC, may wrap past midnight. Empty allows any time.
+pub const SETTING_MAINTENANCE_WINDOW: &str = "updates.maintenance_window";
+
+/// Containers with this label set to `true` are updated automatically.
+pub const LABEL_AUTO_UPDATE: &str = "dockium.update.auto";
+/// Containers with this label set to `false` are never checked.
+pub const LABEL_CHECK: &str = "dockium.update.check";
+
+const SETTINGS_PREFIX: &str = "updates.";
+const LAST_CHECK_KEY: &str = "internal.updates.last_check_at";
+const DEFAULT_INTERVAL_HOURS: u32 = 6;
+const MAX_INTERVAL_HOURS: u32 = 24 * 365;
+
+/// How often the scheduler checks whether a check or update is due.
+const SCHEDULER_TICK: Duration = Duration::from_secs(60);
+
+/// Latest check result for a running container.
+#[derive(Serialize, sqlx::FromRow)]
+pub struct UpdateCheck {
+    pub container_id: String,
+    pub container_name: String,
+    pub image: String,
+    /// Digest the container's image was pulled at.
+    pub local_digest: Option<String>,
+    /// Digest the registry serves for the tag now.
+    pub remote_digest: Option<String>,
+    pub update_available: bool,
+    pub auto_update: bool,
+    pub checked_at: DateTime<Utc>,
+    pub error: Option<String>,
+}
+
+/// An update that was performed, or attempted and rolled back.
+#[derive(Serialize, sqlx::FromRow)]
+pub struct ImageUpdate {
+    pub id: i64,
+    pub container_name: String,
+    pub image: String,
+    pub previous_container_id: String,
+    pub container_id: Option<String>,
+    pub previous_digest: Option<String>,
+    pub digest: Option<String>,
+    /// `auto` or `manual`.
+    pub trigger: String,
+    pub username: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    /// `updated`, `rolled_back` or `failed`.
+    pub status: String,
+    pub error: Option<String>,
+}
+
+/// Scheduled checks as configured in the `updates.*` settings.
+#[derive(Serialize)]
+pub struct UpdateSchedule {
+    pub enabled: bool,
+    pub interval_hours: u32,
+    pub maintenance_window: Option<String>,
+    /// Whether automatic updates may run right now.
+    pub in_maintenance_window: bool,
+    pub last_check_at: Option<DateTime<Utc>>,
+    pub next_check_at: Option<DateTime<Utc>>,
+}
+
+/// Checks an `updates.*` setting before it is saved.
+pub fn validate_setting(key: &str, value: &str) -> std::result::Result<(), String> {
+    let result = match key {
+        SETTING_ENABLED => parse_bool(value).map(|_| ()),
+        SETTING_INTERVAL_HOURS => parse_number(value, 1, MAX_INTERVAL_HOURS).map(|_| ()),
+        SETTING_MAINTENANCE_WINDOW => parse_window(value).map(|_| ()),
+        _ if key.starts_with(SETTINGS_PREFIX) => return Err(format!("Unknown setting {}", key)),
+        _ => return Ok(()),
+    };
+    result.map_err(|e| format!("Invalid setting {}: {}", key, e))
+}
+
+/// Parses `HH:MM-HH:MM`; empty means no window.
+fn parse_window(value: &str) -> std::result::Result<Option<(NaiveTime, NaiveTime)>, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(None);
+    }
+    let invalid = || "expected HH:MM-HH:MM in UTC".to_string();
+    let (start, end) = value.split_once('-').ok_or_else(invalid)?;
+    let parse = |t: &str| NaiveTime::parse_from_str(t.trim(), "%H:%M").map_err(|_| invalid());
+    let (start, end) = (parse(start)?, parse(end)?);
+    if start == end {
+        return Err("window start and end must differ".to_string());
+    }
+    Ok(Some((start, end)))
+}
+
+fn in_window(window: Option<(NaiveTime, NaiveTime)>, now: DateTime<Utc>) -> bool {
+    let Some((start, end)) = window else {
+        return true;
+    };
+    let now = now.time().with_second(0).and_then(|t| t.with_nanosecond(0)).unwrap_or(now.time());
+    if start < end {
+        start <= now && now < end
+    } else {
+        now >= start || now < end
+    }
+}
+
+/// Repository of a reference in the short form Docker uses in repo
+/// digests, e.g. `nginx` for `docker.io/library/nginx:1.25`.
+fn familiar_repository(image: &str) -> &str {
+    let repository = match image.split_once('@') {
+        Some((repository, _)) => repository,
+        None => split_tag(image).0,
+    };
+    let repository = repository
+        .strip_prefix("docker.io/")
+        .or_else(|| repository.strip_prefix("index.docker.io/"))
+        .unwrap_or(repository);
+    repository.strip_prefix("library/").unwrap_or(repository)
+}
+
+/// What [`UpdateService::record`] needs to know about an update attempt.
+struct Attempt<'a> {
+    container_name: &'a str,
+    image: &'a str,
+    previous_container_id: &'a str,
+    previous_digest: Option<&'a str>,
+    digest: Option<&'a str>,
+    trigger: &'a str,
+    username: Option<&'a str>,
+    started_at: DateTime<Utc>,
+    auto_update: bool,
+}
+
+pub struct UpdateService {
+    db: Arc<Database>,
+    docker: Arc<DockerService>,
+    registries: Arc<RegistryService>,
+    settings: Arc<SettingsService>,
+    recreate: Arc<RecreateService>,
+    /// Held while checking or updating so runs don't overlap.
+    running: Mutex<()>,
+}
+
+impl UpdateService {
+    pub fn new(
+        db: Arc<Database>,
+        docker: Arc<DockerService>,
+        registries: Arc<RegistryService>,
+        settings: Arc<SettingsService>,
+        recreate: Arc<RecreateService>,
+    ) -> Self {
+        Self {
+            db,
+            docker,
+            registries,
+            settings,
+            recreate,
+            running: Mutex::new(()),
+        }
+    }
+
+    /// Checks on the configured interval and applies pending automatic
+    /// updates whenever the maintenance window is open. Settings are re-read
+    /// on every tick.
+    pub async fn run(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(SCHEDULER_TICK);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let schedule = match self.schedule().await {
+                Ok(schedule) => schedule,
+                Err(e) => {
+                    tracing::warn!("Failed to load update schedule: {}", e);
+                    continue;
+                }
+            };
+            // Only set when enabled
+            if schedule.next_check_at.is_some_and(|next| next <= Utc::now()) {
+                if let Err(e) = self.check_all().await {
+                    tracing::warn!("Image update check failed: {}", e);
+                }
+            }
+            if schedule.enabled && schedule.in_maintenance_window {
+                if let Err(e) = self.apply_pending().await {
+                    tracing::warn!("Automatic image updates failed: {}", e);
+                }
+            }
+        }
+    }
+
+    pub async fn schedule(&self) -> Result<UpdateSchedule> {
+        let settings = self.settings.get_all_settings().await?;
+        let get = |key: &str| settings.get(key).map(String::as_str);
+        let invalid = |key: &str, e: String| anyhow!("Invalid setting {}: {}", key, e);
+
+        let enabled = get(SETTING_ENABLED)
+            .map(parse_bool)
+            .transpose()
+            .map_err(|e| invalid(SETTING_ENABLED, e))?
+            .unwrap_or(false);
+        let interval_hours = get(SETTING_INTERVAL_HOURS)
+            .map(|v| parse_number(v, 1, MAX_INTERVAL_HOURS))
+            .transpose()
+            .map_err(|e| invalid(SETTING_INTERVAL_HOURS, e))?
+            .unwrap_or(DEFAULT_INTERVAL_HOURS);
+        let window = parse_window(get(SETTING_MAINTENANCE_WINDOW).unwrap_or_default())
+            .map_err(|e| invalid(SETTING_MAINTENANCE_WINDOW, e))?;
+
+        // Internal keys are hidden from get_all_settings
+        let last_check_at = self
+            .settings
+            .get_setting(LAST_CHECK_KEY)
+            .await?
+            .and_then(|v| DateTime::parse_from_rfc3339(&v).ok())
+            .map(|t| t.with_timezone(&Utc));
+        let next_check_at = enabled.then(|| match last_check_at {
+            Some(last) => last + chrono::Duration::hours(interval_hours as i64),
+            None => Utc::now(),
+        });
+
+        Ok(UpdateSchedule {
+            enabled,
+            interval_hours,
+            maintenance_window: window.map(|(start, end)| format!("{}-{}", start.format("%H:%M"), end.format("%H:%M"))),
+            in_maintenance_window: in_window(window, Utc::now()),
+            last_check_at,
+            next_check_at,
+        })
+    }
+
+    /// Compares every running container's image digest with the registry.
+    /// Replaces all previous results, so removed containers drop out.
+    pub async fn check_all(&self) -> Result<Vec<UpdateCheck>> {
+        let _guard = self.running.lock().await;
+        let containers = self.docker.list_containers(false).await?;
+        let mut remote: HashMap<String, std::result::Result<String, String>> = HashMap::new();
+        let mut checks = Vec::new();
+
+        for container in containers {
+            let labels = container.labels.unwrap_or_default();
+            let image = container.image.unwrap_or_default();
+            // Images referenced by id or digest have no tag to follow
+            if labels.get(LABEL_CHECK).map(String::as_str) == Some("false")
+                || image.is_empty()
+                || image.starts_with("sha256:")
+                || image.contains('@')
+            {
+                continue;
+            }
+            let (Some(container_id), Some(image_id)) = (container.id, container.image_id) else {
+                continue;
+            };
+            let container_name = container
+                .names
+                .and_then(|n| n.into_iter().next())
+                .map(|n| n.trim_start_matches('/').to_string())
+                .unwrap_or_else(|| container_id.clone());
+
+            let local = self.repo_digests(&image_id, &image).await;
+            let remote = match remote.get(&image) {
+                Some(digest) => digest.clone(),
+                None => {
+                    let digest = self.registries.remote_digest(&image).await.map_err(|e| e.to_string());
+                    remote.insert(image.clone(), digest.clone());
+                    digest
+                }
+            };
+
+            let (update_available, error) = match (&local, &remote) {
+                (_, Err(e)) => (false, Some(e.clone())),
+                (local, Ok(_)) if local.is_empty() => {
+                    (false, Some("Image has no registry digest; it may have been built locally".to_string()))
+                }
+                (local, Ok(remote)) => (!local.contains(remote), None),
+            };
+            checks.push(UpdateCheck {
+                container_id,
+                container_name,
+                image,
+                local_digest: local.into_iter().next(),
+                remote_digest: remote.ok(),
+                update_available,
+                auto_update: labels.get(LABEL_AUTO_UPDATE).map(String::as_str) == Some("true"),
+                checked_at: Utc::now(),
+                error,
+            });
+        }
+
+        let mut tx = self.db.pool.begin().await?;
+        sqlx::query("DELETE FROM image_update_checks").execute(&mut *tx).await?;
+        for check in &checks {
+            insert_check(&mut tx, check).await?;
+        }
+        tx.commit().await?;
+
+        self.settings.set_setting(LAST_CHECK_KEY, &Utc::now().to_rfc3339()).await?;
+        Ok(checks)
+    }
+
+    pub async fn list_checks(&self) -> Result<Vec<UpdateCheck>> {
+        Ok(sqlx::query_as("SELECT * FROM image_update_checks ORDER BY container_name")
+            .fetch_all(&self.db.pool)
+            .await?)
+    }
+
+    /// Ids of containers with a newer image in the registry.
+    pub async fn available(&self) -> Result<HashSet<String>> {
+        let ids: Vec<String> = sqlx::query_scalar("SELECT container_id FROM image_update_checks WHERE update_available = 1")
+            .fetch_all(&self.db.pool)
+            .await?;
+        Ok(ids.into_iter().collect())
+    }
+
+    /// Most recent first.
+    pub async fn list_history(&self, limit: i64, offset: i64) -> Result<(Vec<ImageUpdate>, i64)> {
+        let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM image_updates")
+            .fetch_one(&self.db.pool)
+            .await?;
+        let updates = sqlx::query_as("SELECT * FROM image_updates ORDER BY id DESC LIMIT ? OFFSET ?")
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(&self.db.pool)
+            .await?;
+        Ok((updates, total))
+    }
+
+    /// Pulls the container's image and recreates it. The attempt is
+    /// recorded in the history whether or not it succeeds.
+    pub async fn apply(&self, id: &str, trigger: &str, username: Option<&str>) -> std::result::Result<ImageUpdate, RecreateError> {
+        let _guard = self.running.lock().await;
+        self.apply_locked(id, trigger, username).await
+    }
+
+    async fn apply_pending(&self) -> Result<()> {
+        let _guard = self.running.lock().await;
+        for id in pending_updates(&self.db).await? {
+            match self.apply_locked(&id, "auto", None).await {
+                Ok(update) => tracing::info!("Updated {} to {}", update.container_name, update.image),
+                Err(e) => tracing::warn!("Automatic update of {} failed: {}", id, e),
+            }
+        }
+        Ok(())
+    }
+
+    async fn apply_locked(&self, id: &str, trigger: &str, username: Option<&str>) -> std::result::Result<ImageUpdate, RecreateError> {
+        let started_at = Utc::now();
+        let old = self.docker.inspect_container(id).await.map_err(not_found)?;
+        let image = old.config.as_ref().and_then(|c| c.image.clone()).unwrap_or_default();
+        let previous_digest = match &old.image {
+            Some(image_id) => self.repo_digests(image_id, &image).await.into_iter().next(),
+            None => None,
+        };
+
+        let options = RecreateOptions {
+            pull: true,
+            ..Default::default()
+        };
+        let result = match self.recreate.recreate(id, options).await {
+            // Nothing was attempted
+            Err(e @ (RecreateError::NotFound | RecreateError::InvalidInput(_))) => return Err(e),
+            result => result,
+        };
+        let digest = match result.as_ref().ok().and_then(|r| r.image_id.as_deref()) {
+            Some(image_id) => self.repo_digests(image_id, &image).await.into_iter().next(),
+            None => None,
+        };
+
+        let auto_update = old
+            .config
+            .as_ref()
+            .and_then(|c| c.labels.as_ref())
+            .and_then(|l| l.get(LABEL_AUTO_UPDATE))
+            .is_some_and(|v| v == "true");
+        let attempt = Attempt {
+            container_name: old.name.as_deref().unwrap_or_default().trim_start_matches('/'),
+            image: &image,
+            previous_container_id: old.id.as_deref().unwrap_or_default(),
+            previous_digest: previous_digest.as_deref(),
+            digest: digest.as_deref(),
+            trigger,
+            username,
+            started_at,
+            auto_update,
+        };
+        let update = self.record(&attempt, &result).await?;
+        result.map(|_| update)
+    }
+
+    /// Adds the attempt to the history and, on success, swaps the old
+    /// container's check result for an up-to-date one for the new container.
+    async fn record(&self, attempt: &Attempt<'_>, result: &std::result::Result<RecreateReport, RecreateError>) -> Result<ImageUpdate> {
+        let (container_id, status, error) = match result {
+            Ok(report) => (Some(report.id.as_str()), "updated", None),
+            Err(e @ RecreateError::RolledBack(_)) => (None, "rolled_back", Some(e.to_string())),
+            Err(e) => (None, "failed", Some(e.to_string())),
+        };
+
+        let mut tx = self.db.pool.begin().await?;
+        let id = sqlx::query(
+            "INSERT INTO image_updates
+                (container_name, image, previous_container_id, container_id, previous_digest, digest, trigger, username, started_at, finished_at, status, error)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(attempt.container_name)
+        .bind(attempt.image)
+        .bind(attempt.previous_container_id)
+        .bind(container_id)
+        .bind(attempt.previous_digest)
+        .bind(attempt.digest)
+        .bind(attempt.trigger)
+        .bind(attempt.username)
+        .bind(attempt.started_at)
+        .bind(Utc::now())
+        .bind(status)
+        .bind(&error)
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+
+        if let Some(container_id) = container_id {
+            sqlx::query("DELETE FROM image_update_checks WHERE container_id = ?")
+                .bind(attempt.previous_container_id)
+                .execute(&mut *tx)
+                .await?;
+            let check = UpdateCheck {
+                container_id: container_id.to_string(),
+                container_name: attempt.container_name.to_string(),
+                image: attempt.image.to_string(),
+                local_digest: attempt.digest.map(str::to_string),
+                remote_digest: attempt.digest.map(str::to_string),
+                update_available: false,
+                auto_update: attempt.auto_update,
+                checked_at: Utc::now(),
+                error: None,
+            };
+            insert_check(&mut tx, &check).await?;
+        }
+        tx.commit().await?;
+
+        Ok(sqlx::query_as("SELECT * FROM image_updates WHERE id = ?")
+            .bind(id)
+            .fetch_one(&self.db.pool)
+            .await?)
+    }
+
+    /// Registry digests recorded for the image under the reference's
+    /// repository. Empty for images that were built locally.
+    async fn repo_digests(&self, image_id: &str, image: &str) -> Vec<String> {
+        let repository = familiar_repository(image);
+        let Ok(inspect) = self.docker.inspect_image(image_id).await else {
+            return Vec::new();
+        };
+        inspect
+            .repo_digests
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|d| {
+                let (repo, digest) = d.split_once('@')?;
+                (familiar_repository(repo) == repository).then(|| digest.to_string())
+            })
+            .collect()
+    }
+}
+
+/// Containers due for an automatic update. A failed attempt isn't retried
+/// until the next check.
+async fn pending_updates(db: &Database) -> Result<Vec<String>> {
+    Ok(sqlx::query_scalar(
+        "SELECT c.container_id FROM image_update_checks c
+         WHERE c.update_available = 1 AND c.auto_update = 1
+           AND NOT EXISTS (
+               SELECT 1 FROM image_updates u
+               WHERE u.previous_container_id = c.container_id AND u.started_at >= c.checked_at
+           )
+         ORDER BY c.container_name",
+    )
+    .fetch_all(&db.pool)
+    .await?)
+}
+
+async fn insert_check(tx: &mut sqlx::SqliteConnection, check: &UpdateCheck) -> Result<()> {
+    sqlx::query(
+        "INSERT OR REPLACE INTO image_update_checks
+            (container_id, container_name, image, local_digest, remote_digest, update_available, auto_update, checked_at, error)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&check.container_id)
+    .bind(&check.container_name)
+    .bind(&check.image)
+    .bind(&check.local_digest)
+    .bind(&check.remote_digest)
+    .bind(check.update_available)
+    .bind(check.auto_update)
+    .bind(check.checked_at)
+    .bind(&check.error)
+    .execute(tx)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(value: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(value, "%H:%M:%S").unwrap()
+    }
+
+    fn at(value: &str) -> DateTime<Utc> {
+        chrono::NaiveDate::from_ymd_opt(2026, 10, 18).unwrap().and_time(time(value)).and_utc()
+    }
+
+    #[test]
+    fn familiar_repository_matches_docker_repo_digests() {
+        assert_eq!(familiar_repository("nginx"), "nginx");
+        assert_eq!(familiar_repository("nginx:1.25"), "nginx");
+        assert_eq!(familiar_repository("library/nginx:1.25"), "nginx");
+        assert_eq!(familiar_repository("docker.io/library/nginx:1.25"), "nginx");
+        assert_eq!(familiar_repository("index.docker.io/acme/app"), "acme/app");
+        assert_eq!(familiar_repository("nginx@sha256:abc"), "nginx");
+        assert_eq!(familiar_repository("ghcr.io/acme/app:v1"), "ghcr.io/acme/app");
+        assert_eq!(familiar_repository("localhost:5000/app"), "localhost:5000/app");
+    }
+
+    #[test]
+    fn parse_window_accepts_hh_mm_ranges() {
+        assert_eq!(parse_window("").unwrap(), None);
+        assert_eq!(parse_window("  ").unwrap(), None);
+        assert_eq!(parse_window("01:00-03:30").unwrap(), Some((time("01:00:00"), time("03:30:00"))));
+        assert_eq!(parse_window(" 22:00 - 02:00 ").unwrap(), Some((time("22:00:00"), time("02:00:00"))));
+
+        for value in ["22:00", "1-3", "25:00-02:00", "01:00-03:00:00", "03:00-03:00"] {
+            assert!(parse_window(value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn in_window_includes_the_start_and_excludes_the_end() {
+        let window = parse_window("01:00-03:30").unwrap();
+        assert!(in_window(None, at("12:00:00")));
+        assert!(in_window(window, at("01:00:00")));
+        assert!(in_window(window, at("03:29:59")));
+        assert!(!in_window(window, at("03:30:00")));
+        assert!(!in_window(window, at("00:59:59")));
+    }
+
+    #[test]
+    fn in_window_wraps_past_midnight() {
+        let window = parse_window("22:00-02:00").unwrap();
+        for now in ["22:00:00", "23:59:59", "00:00:00", "01:59:59"] {
+            assert!(in_window(window, at(now)), "{}", now);
+        }
+        for now in ["02:00:00", "12:00:00", "21:59:59"] {
+            assert!(!in_window(window, at(now)), "{}", now);
+        }
+    }
+
+    #[test]
+    fn settings_are_validated_before_they_are_saved() {
+        assert!(validate_setting(SETTING_ENABLED, "false").is_ok());
+        assert!(validate_setting(SETTING_INTERVAL_HOURS, "6").is_ok());
+        assert!(validate_setting(SETTING_MAINTENANCE_WINDOW, "").is_ok());
+        assert!(validate_setting("prune.schedule.enabled", "anything").is_ok());
+
+        assert!(validate_setting(SETTING_ENABLED, "1").is_err());
+        assert!(validate_setting(SETTING_INTERVAL_HOURS, "0").is_err());
+        assert!(validate_setting(SETTING_MAINTENANCE_WINDOW, "night").is_err());
+        assert!(validate_setting("updates.typo", "1").unwrap_err().contains("Unknown setting"));
+    }
+
+    async fn check(db: &Database, id: &str, update_available: bool, auto_update: bool, checked_at: DateTime<Utc>) {
+        let check = UpdateCheck {
+            container_id: id.into(),
+            container_name: id.into(),
+            image: "nginx:latest".into(),
+            local_digest: Some("sha256:old".into()),
+            remote_digest: Some("sha256:new".into()),
+            update_available,
+            auto_update,
+            checked_at,
+            error: None,
+        };
+        insert_check(&mut db.pool.acquire().await.unwrap(), &check).await.unwrap();
+    }
+
+    async fn attempt(db: &Database, previous_container_id: &str, started_at: DateTime<Utc>) {
+        sqlx::query(
+            "INSERT INTO image_updates (container_name, image, previous_container_id, trigger, started_at, finished_at, status)
+             VALUES (?, 'nginx:latest', ?, 'auto', ?, ?, 'rolled_back')",
+        )
+        .bind(previous_container_id)
+        .bind(previous_container_id)
+        .bind(started_at)
+        .bind(started_at)
+        .execute(&db.pool)
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn failed_updates_are_not_retried_until_the_next_check() {
+        let db = Database::in_memory().await;
+        check(&db, "due", true, true, at("10:00:00")).await;
+        check(&db, "failed", true, true, at("10:00:00")).await;
+        attempt(&db, "failed", at("10:05:00")).await;
+        check(&db, "rechecked", true, true, at("11:00:00")).await;
+        attempt(&db, "rechecked", at("10:05:00")).await;
+        check(&db, "manual", true, false, at("10:00:00")).await;
+        check(&db, "current", false, true, at("10:00:00")).await;
+
+        assert_eq!(pending_updates(&db).await.unwrap(), ["due", "rechecked"]);
+    }
+}
@@ -0,0 +1,340 @@
+use crate::db::Database;
+use crate::models::{Role, User, UserAccount};
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+#[derive(Debug)]
+pub enum UserError {
+    NotFound,
+    UsernameTaken,
+    InvalidInput(String),
+    /// The change would leave no enabled admin account.
+    LastAdmin,
+    Internal(anyhow::Error),
+}
+
+impl fmt::Display for UserError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UserError::NotFound => write!(f, "User not found"),
+            UserError::UsernameTaken => write!(f, "Username already exists"),
+            UserError::InvalidInput(msg) => write!(f, "{}", msg),
+            UserError::LastAdmin => write!(f, "Cannot remove, demote or disable the last admin"),
+            UserError::Internal(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<sqlx::Error> for UserError {
+    fn from(e: sqlx::Error) -> Self {
+        UserError::Internal(e.into())
+    }
+}
+
+impl From<bcrypt::BcryptError> for UserError {
+    fn from(e: bcrypt::BcryptError) -> Self {
+        UserError::Internal(e.into())
+    }
+}
+
+impl From<tokio::task::JoinError> for UserError {
+    fn from(e: tokio::task::JoinError) -> Self {
+        UserError::Internal(e.into())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, UserError>;
+
+type AccountRow = (String, String, String, bool, chrono::NaiveDateTime);
+
+pub struct UserService {
+    db: Arc<Database>,
+}
+
+impl UserService {
+    pub fn new(db: Arc<Database>) -> Self {
+        Self { db }
+    }
+
+    pub async fn list(&self) -> Result<Vec<UserAccount>> {
+        let rows: Vec<AccountRow> = sqlx::query_as(
+            "SELECT id, username, role, disabled, created_at FROM users ORDER BY created_at, username",
+        )
+        .fetch_all(&self.db.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(to_account).collect())
+    }
+
+    pub async fn get(&self, id: &str) -> Result<UserAccount> {
+        let row: Option<AccountRow> = sqlx::query_as(
+            "SELECT id, username, role, disabled, created_at FROM users WHERE id = ?",
+        )
+        .bind(id)
+        .fetch_optional(&self.db.pool)
+        .await?;
+
+        row.map(to_account).ok_or(UserError::NotFound)
+    }
+
+    pub async fn create(&self, username: &str, password: &str, role: Role) -> Result<UserAccount> {
+        let username = username.trim();
+        if username.is_empty() {
+            return Err(UserError::InvalidInput("Username must not be empty".into()));
+        }
+        let password_hash = hash_password(password).await?;
+        let id = uuid::Uuid::new_v4().to_string();
+
+        let result = sqlx::query("INSERT INTO users (id, username, password_hash, role) VALUES (?, ?, ?, ?)")
+            .bind(&id)
+            .bind(username)
+            .bind(&password_hash)
+            .bind(role.as_str())
+            .execute(&self.db.pool)
+            .await;
+
+        match result {
+            Ok(_) => self.get(&id).await,
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Err(UserError::UsernameTaken),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Creates the initial admin account, unless any user exists. Returns
+    /// `None` when one does. The check and the insert are one statement, so
+    /// concurrent setup requests can't both create an admin.
+    pub async fn create_first_admin(&self, username: &str, password: &str) -> Result<Option<UserAccount>> {
+        let username = username.trim();
+        if username.is_empty() {
+            return Err(UserError::InvalidInput("Username must not be empty".into()));
+        }
+        let password_hash = hash_password(password).await?;
+        let id = uuid::Uuid::new_v4().to_string();
+
+        let result = sqlx::query(
+            "INSERT INTO users (id, username, password_hash, role)
+             SELECT ?, ?, ?, ? WHERE NOT EXISTS (SELECT 1 FROM users)",
+        )
+        .bind(&id)
+        .bind(username)
+        .bind(&password_hash)
+        .bind(Role::Admin.as_str())
+        .execute(&self.db.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+        self.get(&id).await.map(Some)
+    }
+
+    /// Returns the user if the credentials match an enabled account.
+    pub async fn verify_credentials(&self, username: &str, password: &str) -> Result<Option<User>> {
+        let row: Option<(String, String, String, String, bool)> = sqlx::query_as(
+            "SELECT id, username, password_hash, role, disabled FROM users WHERE username = ?",
+        )
+        .bind(username)
+        .fetch_optional(&self.db.pool)
+        .await?;
+
+        // Always run a bcrypt verification, even for unknown users, so response
+        // timing doesn't reveal which usernames exist.
+        let password = password.to_string();
+        let hash = row.as_ref().map(|(_, _, hash, _, _)| hash.clone());
+        let password_ok = tokio::task::spawn_blocking(move || match hash {
+            Some(hash) => bcrypt::verify(&password, &hash).unwrap_or(false),
+            None => {
+                let _ = bcrypt::verify(&password, dummy_hash());
+                false
+            }
+        })
+        .await?;
+
+        Ok(match row {
+            Some((id, username, _, role, disabled)) if password_ok && !disabled => {
+                Some(User { id, username, role })
+            }
+            _ => None,
+        })
+    }
+
+    pub async fn update_role(&self, id: &str, role: Role) -> Result<UserAccount> {
+        let mut tx = self.db.pool.begin().await?;
+        let current = current_role(&mut tx, id).await?;
+        if current == Role::Admin.as_str() && role != Role::Admin {
+            ensure_other_admin(&mut tx, id).await?;
+        }
+
+        sqlx::query("UPDATE users SET role = ? WHERE id = ?")
+            .bind(role.as_str())
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        self.get(id).await
+    }
+
+    pub async fn reset_password(&self, id: &str, password: &str) -> Result<()> {
+        let password_hash = hash_password(password).await?;
+        let result = sqlx::query("UPDATE users SET password_hash = ? WHERE id = ?")
+            .bind(&password_hash)
+            .bind(id)
+            .execute(&self.db.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserError::NotFound);
+        }
+
+        // Sign the user out everywhere
+        sqlx::query("UPDATE sessions SET revoked = 1 WHERE user_id = ?")
+            .bind(id)
+            .execute(&self.db.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Disabled accounts keep their data but can no longer log in, refresh or
+    /// use previously issued tokens.
+    pub async fn set_disabled(&self, id: &str, disabled: bool) -> Result<UserAccount> {
+        let mut tx = self.db.pool.begin().await?;
+        let current = current_role(&mut tx, id).await?;
+        if disabled && current == Role::Admin.as_str() {
+            ensure_other_admin(&mut tx, id).await?;
+        }
+
+        sqlx::query("UPDATE users SET disabled = ? WHERE id = ?")
+            .bind(disabled)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        self.get(id).await
+    }
+
+    pub async fn delete(&self, id: &str) -> Result<()> {
+        let mut tx = self.db.pool.begin().await?;
+        let current = current_role(&mut tx, id).await?;
+        if current == Role::Admin.as_str() {
+            ensure_other_admin(&mut tx, id).await?;
+        }
+
+        sqlx::query("DELETE FROM users WHERE id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+/// Hashes on the blocking pool, since bcrypt takes long enough to stall the
+/// runtime's workers.
+pub async fn hash_password(password: &str) -> Result<String> {
+    if password.len() < MIN_PASSWORD_LENGTH {
+        return Err(UserError::InvalidInput(format!(
+            "Password must be at least {} characters",
+            MIN_PASSWORD_LENGTH
+        )));
+    }
+    let password = password.to_string();
+    Ok(tokio::task::spawn_blocking(move || bcrypt::hash(password, bcrypt::DEFAULT_COST)).await??)
+}
+
+/// Hash verified against when the username doesn't exist, computed once with
+/// the same cost as real password hashes.
+fn dummy_hash() -> &'static str {
+    static HASH: OnceLock<String> = OnceLock::new();
+    HASH.get_or_init(|| bcrypt::hash("dockium-dummy-password", bcrypt::DEFAULT_COST).unwrap_or_default())
+}
+
+fn to_account((id, username, role, disabled, created_at): AccountRow) -> UserAccount {
+    UserAccount {
+        id,
+        username,
+        role,
+        disabled,
+        created_at,
+    }
+}
+
+async fn current_role(tx: &mut sqlx::SqliteConnection, id: &str) -> Result<String> {
+    let role: Option<String> = sqlx::query_scalar("SELECT role FROM users WHERE id = ?")
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+    role.ok_or(UserError::NotFound)
+}
+
+async fn ensure_other_admin(tx: &mut sqlx::SqliteConnection, id: &str) -> Result<()> {
+    let others: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM users WHERE role = ? AND disabled = 0 AND id != ?",
+    )
+    .bind(Role::Admin.as_str())
+    .bind(id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if others == 0 {
+        return Err(UserError::LastAdmin);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PASSWORD: &str = "correct horse";
+
+    async fn service() -> UserService {
+        UserService::new(Arc::new(Database::in_memory().await))
+    }
+
+    #[tokio::test]
+    async fn first_admin_is_only_created_once() {
+        let service = service().await;
+        assert!(matches!(service.create_first_admin("  ", PASSWORD).await, Err(UserError::InvalidInput(_))));
+        assert!(matches!(service.create_first_admin("admin", "short").await, Err(UserError::InvalidInput(_))));
+
+        let admin = service.create_first_admin(" admin ", PASSWORD).await.unwrap().unwrap();
+        assert_eq!((admin.username.as_str(), admin.role.as_str()), ("admin", "admin"));
+        assert!(service.create_first_admin("second", PASSWORD).await.unwrap().is_none());
+        assert_eq!(service.list().await.unwrap().len(), 1);
+
+        assert!(service.verify_credentials("admin", PASSWORD).await.unwrap().is_some());
+        assert!(service.verify_credentials("admin", "wrong password").await.unwrap().is_none());
+        assert!(service.verify_credentials("nobody", PASSWORD).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn the_last_enabled_admin_cannot_be_demoted_disabled_or_deleted() {
+        let service = service().await;
+        let admin = service.create_first_admin("admin", PASSWORD).await.unwrap().unwrap();
+        let operator = service.create("operator", PASSWORD, Role::Operator).await.unwrap();
+
+        assert!(matches!(service.update_role(&admin.id, Role::Viewer).await, Err(UserError::LastAdmin)));
+        assert!(matches!(service.set_disabled(&admin.id, true).await, Err(UserError::LastAdmin)));
+        assert!(matches!(service.delete(&admin.id).await, Err(UserError::LastAdmin)));
+        // Changes that keep the admin are fine
+        assert_eq!(service.update_role(&admin.id, Role::Admin).await.unwrap().role, "admin");
+        assert!(!service.set_disabled(&admin.id, false).await.unwrap().disabled);
+
+        // A disabled admin doesn't count as another one
+        let second = service.create("second", PASSWORD, Role::Admin).await.unwrap();
+        service.set_disabled(&second.id, true).await.unwrap();
+        assert!(matches!(service.delete(&admin.id).await, Err(UserError::LastAdmin)));
+
+        service.set_disabled(&second.id, false).await.unwrap();
+        assert_eq!(service.update_role(&admin.id, Role::Operator).await.unwrap().role, "operator");
+        assert!(matches!(service.delete(&second.id).await, Err(UserError::LastAdmin)));
+        service.delete(&operator.id).await.unwrap();
+        service.delete(&admin.id).await.unwrap();
+        assert!(matches!(service.get(&admin.id).await, Err(UserError::NotFound)));
+    }
+}